@@ -0,0 +1,295 @@
+use super::shows::accessible_show_ids;
+use super::{data, list, non_empty, page_params};
+use super::{ApiResult, ApiTags, Created, DataBody, Deleted, ListBody};
+use crate::handlers::episodes::{
+    delete_episode_and_media, ensure_show_permission, find_accessible_episode, transition_episode,
+};
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::user::Model as User;
+use entities::{chapter, episode};
+use poem::error::InternalServerError;
+use poem::web::Data;
+use poem_openapi::param::{Path, Query};
+use poem_openapi::payload::Json;
+use poem_openapi::{Enum, Object, OpenApi};
+use sea_orm::prelude::Uuid;
+use service::permissions::Permission;
+use service::{EpisodeFilter, Mutation as MutationCore, Query as QueryCore};
+
+/// Where an episode is in the publishing workflow.
+#[derive(Enum, Clone, Copy)]
+#[oai(rename_all = "snake_case")]
+pub enum Status {
+    Draft,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl From<EpisodeStatus> for Status {
+    fn from(status: EpisodeStatus) -> Self {
+        match status {
+            EpisodeStatus::Draft => Status::Draft,
+            EpisodeStatus::Scheduled => Status::Scheduled,
+            EpisodeStatus::Published => Status::Published,
+            EpisodeStatus::Archived => Status::Archived,
+        }
+    }
+}
+
+impl From<Status> for EpisodeStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Draft => EpisodeStatus::Draft,
+            Status::Scheduled => EpisodeStatus::Scheduled,
+            Status::Published => EpisodeStatus::Published,
+            Status::Archived => EpisodeStatus::Archived,
+        }
+    }
+}
+
+#[derive(Object)]
+pub struct Episode {
+    id: Uuid,
+    show_id: Option<Uuid>,
+    user_id: Uuid,
+    title: String,
+    summary: String,
+    tags: String,
+    /// Where the audio can be downloaded.
+    url: Option<String>,
+    status: Status,
+    published_at: Option<DateTime<Utc>>,
+    scheduled_for: Option<DateTime<Utc>>,
+    media_type: Option<String>,
+    /// Size of the audio file in bytes.
+    media_size: Option<i64>,
+    duration_ms: Option<i64>,
+    bitrate: Option<i32>,
+    sample_rate: Option<i32>,
+    channels: Option<i16>,
+    codec: Option<String>,
+    artwork_url: Option<String>,
+}
+
+impl From<episode::Model> for Episode {
+    fn from(episode: episode::Model) -> Self {
+        Self {
+            id: episode.id,
+            show_id: episode.show_id,
+            user_id: episode.user_id,
+            title: episode.title,
+            summary: episode.summary,
+            tags: episode.tags,
+            url: episode.url,
+            status: episode.status.into(),
+            published_at: episode.published_at,
+            scheduled_for: episode.scheduled_for,
+            media_type: episode.media_type,
+            media_size: episode.media_size,
+            duration_ms: episode.duration_ms,
+            bitrate: episode.bitrate,
+            sample_rate: episode.sample_rate,
+            channels: episode.channels,
+            codec: episode.codec,
+            artwork_url: episode.artwork_url,
+        }
+    }
+}
+
+/// The fields of an episode that can be edited directly. Audio is uploaded through the
+/// HTML forms and the status goes through the workflow endpoint.
+#[derive(Object)]
+pub struct EpisodeInput {
+    show_id: Uuid,
+    title: String,
+    #[oai(default)]
+    summary: String,
+    #[oai(default)]
+    tags: String,
+    url: Option<String>,
+}
+
+impl From<EpisodeInput> for episode::Model {
+    fn from(input: EpisodeInput) -> Self {
+        Self {
+            id: Uuid::nil(),
+            title: input.title,
+            summary: input.summary,
+            tags: input.tags,
+            url: input.url,
+            user_id: Uuid::nil(),
+            media_key: None,
+            media_size: None,
+            media_type: None,
+            show_id: Some(input.show_id),
+            status: EpisodeStatus::Draft,
+            published_at: None,
+            scheduled_for: None,
+            duration_ms: None,
+            bitrate: None,
+            sample_rate: None,
+            channels: None,
+            codec: None,
+            artwork_url: None,
+            embedded_chapters: None,
+            guid: None,
+        }
+    }
+}
+
+#[derive(Object)]
+pub struct StatusInput {
+    status: Status,
+    /// Required when scheduling.
+    scheduled_for: Option<DateTime<Utc>>,
+}
+
+#[derive(Object)]
+pub struct Chapter {
+    id: Uuid,
+    start_ms: i64,
+    title: String,
+    url: Option<String>,
+    image_url: Option<String>,
+}
+
+impl From<chapter::Model> for Chapter {
+    fn from(chapter: chapter::Model) -> Self {
+        Self {
+            id: chapter.id,
+            start_ms: chapter.start_ms,
+            title: chapter.title,
+            url: chapter.url,
+            image_url: chapter.image_url,
+        }
+    }
+}
+
+pub struct EpisodesApi;
+
+#[OpenApi(prefix_path = "/episodes", tag = "ApiTags::Episodes")]
+impl EpisodesApi {
+    /// List episodes of the shows the user may manage
+    #[oai(path = "/", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn index(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        page: Query<Option<u64>>,
+        items_per_page: Query<Option<u64>>,
+        show_id: Query<Option<Uuid>>,
+        status: Query<Option<Status>>,
+        /// Matched against the title.
+        q: Query<Option<String>>,
+    ) -> ApiResult<Json<ListBody<Episode>>> {
+        let (page, per_page) = page_params(page.0, items_per_page.0);
+        let show_ids = match show_id.0 {
+            Some(show_id) => {
+                ensure_show_permission(&state, &user, Some(show_id), Permission::EpisodeEdit)
+                    .await?;
+                Some(vec![show_id])
+            }
+            None => accessible_show_ids(&state, &user).await?,
+        };
+        let filter = EpisodeFilter {
+            show_ids,
+            status: status.0.map(Into::into),
+            search: non_empty(&q.0).map(str::to_string),
+        };
+
+        let episodes = QueryCore::find_episodes_page(&state.conn, &filter, page, per_page)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(list(episodes))
+    }
+
+    /// Get an episode
+    #[oai(path = "/:id", method = "get")]
+    async fn read(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Json<DataBody<Episode>>> {
+        Ok(data(find_accessible_episode(&state, &user, id.0).await?.into()))
+    }
+
+    /// Create a draft episode
+    #[oai(path = "/", method = "post")]
+    async fn create(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        body: Json<EpisodeInput>,
+    ) -> ApiResult<Created<Episode>> {
+        let mut episode: episode::Model = body.0.into();
+        ensure_show_permission(&state, &user, episode.show_id, Permission::EpisodeEdit).await?;
+        episode.user_id = user.id;
+
+        let episode = MutationCore::create_episode(&state.conn, episode)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Created::Created(data(episode.into())))
+    }
+
+    /// Replace the editable fields of an episode
+    #[oai(path = "/:id", method = "put")]
+    async fn update(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+        body: Json<EpisodeInput>,
+    ) -> ApiResult<Json<DataBody<Episode>>> {
+        find_accessible_episode(&state, &user, id.0).await?;
+        let episode: episode::Model = body.0.into();
+        ensure_show_permission(&state, &user, episode.show_id, Permission::EpisodeEdit).await?;
+
+        let episode = MutationCore::update_episode_by_id(&state.conn, id.0, episode)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(data(episode.into()))
+    }
+
+    /// Move an episode through the publishing workflow
+    ///
+    /// Needs the `episode:publish` permission. Answers 409 for transitions the workflow
+    /// does not allow.
+    #[oai(path = "/:id/status", method = "post")]
+    async fn change_status(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+        body: Json<StatusInput>,
+    ) -> ApiResult<Json<DataBody<Episode>>> {
+        let episode = find_accessible_episode(&state, &user, id.0).await?;
+        ensure_show_permission(&state, &user, episode.show_id, Permission::EpisodePublish).await?;
+        let episode =
+            transition_episode(&state, &episode, body.0.status.into(), body.0.scheduled_for).await?;
+        Ok(data(episode.into()))
+    }
+
+    /// Delete an episode along with its audio
+    #[oai(path = "/:id", method = "delete")]
+    async fn destroy(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Deleted> {
+        let episode = find_accessible_episode(&state, &user, id.0).await?;
+        delete_episode_and_media(&state, episode).await?;
+        Ok(Deleted::Deleted)
+    }
+
+    /// List the chapters of an episode
+    #[oai(path = "/:id/chapters", method = "get")]
+    async fn chapters(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+    ) -> ApiResult<Json<DataBody<Vec<Chapter>>>> {
+        find_accessible_episode(&state, &user, id.0).await?;
+
+        let chapters = QueryCore::find_chapters_by_episode(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(data(chapters.into_iter().map(Chapter::from).collect()))
+    }
+}
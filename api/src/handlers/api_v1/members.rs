@@ -0,0 +1,150 @@
+use super::{data, list, non_empty, page_params};
+use super::{ApiResult, ApiTags, Created, DataBody, Deleted, ListBody};
+use crate::handlers::auth::permissions::ensure_permission;
+use crate::AppState;
+use chrono::NaiveDate;
+use entities::member;
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::web::Data;
+use poem::Error;
+use poem_openapi::param::{Path, Query};
+use poem_openapi::payload::Json;
+use poem_openapi::{Object, OpenApi};
+use sea_orm::prelude::Uuid;
+use sea_orm::TryIntoModel;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Object)]
+pub struct Member {
+    id: Uuid,
+    first_name: String,
+    last_name: String,
+    email: String,
+    mobile_phone: String,
+    birth_date: NaiveDate,
+}
+
+impl From<member::Model> for Member {
+    fn from(member: member::Model) -> Self {
+        Self {
+            id: member.id,
+            first_name: member.first_name,
+            last_name: member.last_name,
+            email: member.email,
+            mobile_phone: member.mobile_phone,
+            birth_date: member.birth_date,
+        }
+    }
+}
+
+#[derive(Object)]
+pub struct MemberInput {
+    first_name: String,
+    last_name: String,
+    email: String,
+    mobile_phone: String,
+    birth_date: NaiveDate,
+}
+
+impl From<MemberInput> for member::Model {
+    fn from(input: MemberInput) -> Self {
+        Self {
+            id: Uuid::nil(),
+            first_name: input.first_name,
+            last_name: input.last_name,
+            email: input.email,
+            mobile_phone: input.mobile_phone,
+            birth_date: input.birth_date,
+        }
+    }
+}
+
+async fn find_member(state: &AppState, id: Uuid) -> poem::Result<member::Model> {
+    QueryCore::find_member_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_string("Member not found", StatusCode::NOT_FOUND))
+}
+
+pub struct MembersApi;
+
+#[OpenApi(prefix_path = "/members", tag = "ApiTags::Members")]
+impl MembersApi {
+    /// List members
+    #[oai(path = "/", method = "get")]
+    async fn index(
+        &self,
+        state: Data<&AppState>,
+        page: Query<Option<u64>>,
+        items_per_page: Query<Option<u64>>,
+        /// Matched against the names and the email address.
+        q: Query<Option<String>>,
+    ) -> ApiResult<Json<ListBody<Member>>> {
+        let (page, per_page) = page_params(page.0, items_per_page.0);
+
+        let members = QueryCore::find_members_page(&state.conn, non_empty(&q.0), page, per_page)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(list(members))
+    }
+
+    /// Get a member
+    #[oai(path = "/:id", method = "get")]
+    async fn read(&self, state: Data<&AppState>, id: Path<Uuid>) -> ApiResult<Json<DataBody<Member>>> {
+        Ok(data(find_member(&state, id.0).await?.into()))
+    }
+
+    /// Create a member
+    ///
+    /// Needs the `member:edit` permission.
+    #[oai(path = "/", method = "post")]
+    async fn create(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        body: Json<MemberInput>,
+    ) -> ApiResult<Created<Member>> {
+        ensure_permission(&state, &user, Permission::MemberEdit).await?;
+
+        let member = MutationCore::create_member(&state.conn, body.0.into())
+            .await
+            .and_then(TryIntoModel::try_into_model)
+            .map_err(InternalServerError)?;
+        Ok(Created::Created(data(member.into())))
+    }
+
+    /// Replace a member
+    ///
+    /// Needs the `member:edit` permission.
+    #[oai(path = "/:id", method = "put")]
+    async fn update(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+        body: Json<MemberInput>,
+    ) -> ApiResult<Json<DataBody<Member>>> {
+        ensure_permission(&state, &user, Permission::MemberEdit).await?;
+        find_member(&state, id.0).await?;
+
+        let member = MutationCore::update_member_by_id(&state.conn, id.0, body.0.into())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(data(member.into()))
+    }
+
+    /// Delete a member
+    #[oai(path = "/:id", method = "delete")]
+    async fn destroy(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Deleted> {
+        ensure_permission(&state, &user, Permission::MemberEdit).await?;
+        find_member(&state, id.0).await?;
+
+        MutationCore::delete_member(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Deleted::Deleted)
+    }
+}
@@ -0,0 +1,219 @@
+//! Versioned JSON API for scripts and the mobile app.
+//!
+//! Every response is JSON: single resources come as `{"data": ...}`, listings as
+//! `{"data": [...], "meta": {...}}` and failures as `{"error": {"status", "message"}}`.
+//! Permissions are the same as in the HTML handlers. The OpenAPI document is generated
+//! from the types in this module and served next to Swagger UI and ReDoc.
+
+mod episodes;
+mod members;
+mod posts;
+mod shows;
+mod users;
+
+use entities::user::Model as User;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::{Endpoint, EndpointExt, Error, IntoResponse, Request, Response, Route};
+use poem_openapi::payload::Json;
+use poem_openapi::types::{ParseFromJSON, ToJSON};
+use poem_openapi::{ApiResponse, Object, OpenApiService, Tags};
+use service::Page;
+
+const DEFAULT_PER_PAGE: u64 = 25;
+const MAX_PER_PAGE: u64 = 100;
+
+#[derive(Tags)]
+enum ApiTags {
+    Episodes,
+    Shows,
+    Members,
+    Posts,
+    Users,
+}
+
+/// Body of a response carrying a single resource.
+#[derive(Object)]
+pub struct DataBody<T: ParseFromJSON + ToJSON> {
+    data: T,
+}
+
+/// Body of a response carrying one page of a listing.
+#[derive(Object)]
+pub struct ListBody<T: ParseFromJSON + ToJSON> {
+    data: Vec<T>,
+    meta: PageMeta,
+}
+
+#[derive(Object)]
+pub struct PageMeta {
+    /// One based.
+    page: u64,
+    per_page: u64,
+    total_items: u64,
+    total_pages: u64,
+}
+
+#[derive(Object)]
+pub struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Object)]
+pub struct ErrorDetail {
+    /// Same as the HTTP status code.
+    status: u16,
+    message: String,
+}
+
+#[derive(ApiResponse)]
+pub enum Created<T: ParseFromJSON + ToJSON> {
+    /// The resource was created.
+    #[oai(status = 201)]
+    Created(Json<DataBody<T>>),
+}
+
+#[derive(ApiResponse)]
+pub enum Deleted {
+    /// The resource is gone.
+    #[oai(status = 204)]
+    Deleted,
+}
+
+/// The failures every endpoint may answer with.
+#[derive(ApiResponse)]
+pub enum ApiError {
+    /// The request is malformed or fails validation.
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Not logged in.
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorBody>),
+    /// The user may not do this.
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    /// No such resource.
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The resource is not in a state that allows the change.
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    /// Something went wrong on the server.
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+fn error_body(err: &Error) -> Json<ErrorBody> {
+    let status = err.status();
+    let message = if status.is_server_error() {
+        eprintln!("API error: {err:?}");
+        status.canonical_reason().unwrap_or_default().to_string()
+    } else if err.to_string() == status.to_string() {
+        status.canonical_reason().unwrap_or_default().to_string()
+    } else {
+        err.to_string()
+    };
+    Json(ErrorBody {
+        error: ErrorDetail {
+            status: status.as_u16(),
+            message,
+        },
+    })
+}
+
+/// Lets handlers use `?` on the helpers shared with the HTML handlers.
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let body = error_body(&err);
+        match err.status() {
+            StatusCode::BAD_REQUEST => ApiError::BadRequest(body),
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized(body),
+            StatusCode::FORBIDDEN => ApiError::Forbidden(body),
+            StatusCode::NOT_FOUND => ApiError::NotFound(body),
+            StatusCode::CONFLICT => ApiError::Conflict(body),
+            _ => ApiError::InternalServerError(body),
+        }
+    }
+}
+
+pub(crate) fn data<T: ParseFromJSON + ToJSON>(data: T) -> Json<DataBody<T>> {
+    Json(DataBody { data })
+}
+
+pub(crate) fn list<M, T: ParseFromJSON + ToJSON + From<M>>(page: Page<M>) -> Json<ListBody<T>> {
+    Json(ListBody {
+        data: page.items.into_iter().map(T::from).collect(),
+        meta: PageMeta {
+            page: page.page,
+            per_page: page.per_page,
+            total_items: page.total_items,
+            total_pages: page.total_pages,
+        },
+    })
+}
+
+/// The requested page and page size, with the size kept within bounds.
+pub(crate) fn page_params(page: Option<u64>, items_per_page: Option<u64>) -> (u64, u64) {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = items_per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    (page, per_page)
+}
+
+/// Blank query parameters such as `?q=` do not filter.
+pub(crate) fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Makes the logged in user available to the handlers as `Data<&User>`. Requests with an
+/// API token have been logged in by the bearer token middleware by the time they get here.
+async fn authenticate<E: Endpoint>(next: E, mut req: Request) -> poem::Result<Response> {
+    let user = req
+        .extensions()
+        .get::<Session>()
+        .and_then(|session| session.get::<User>("current_user"))
+        .ok_or_else(|| Error::from_string("Log in to use the API", StatusCode::UNAUTHORIZED))?;
+    req.extensions_mut().insert(user);
+    next.call(req).await.map(IntoResponse::into_response)
+}
+
+/// Turns errors raised outside the handlers, such as unparsable bodies, into error bodies.
+async fn error_response(err: Error) -> Response {
+    if err.is_from_response() {
+        return err.into_response();
+    }
+    let status = err.status();
+    error_body(&err).with_status(status).into_response()
+}
+
+/// The API itself under `/v1`, plus its OpenAPI document and documentation UIs.
+pub fn api_routes(public_url: &str) -> Route {
+    let service = OpenApiService::new(
+        (
+            episodes::EpisodesApi,
+            shows::ShowsApi,
+            members::MembersApi,
+            posts::PostsApi,
+            users::UsersApi,
+        ),
+        "pod-crab API",
+        "1.0",
+    )
+    .description(
+        "Authenticate with a personal API token, created under /tokens and sent as \
+         `Authorization: Bearer <token>`, or with the session cookie of a logged in user.",
+    )
+    .server(format!("{public_url}/api/v1"));
+
+    Route::new()
+        .at("/openapi.json", service.spec_endpoint())
+        .nest("/docs", service.swagger_ui())
+        .nest("/redoc", service.redoc())
+        .nest(
+            "/v1",
+            service.around(authenticate).catch_all_error(error_response),
+        )
+}
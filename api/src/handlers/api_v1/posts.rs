@@ -0,0 +1,137 @@
+use super::{data, list, non_empty, page_params};
+use super::{ApiResult, ApiTags, Created, DataBody, Deleted, ListBody};
+use crate::handlers::auth::permissions::ensure_permission;
+use crate::AppState;
+use entities::post;
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::web::Data;
+use poem::Error;
+use poem_openapi::param::{Path, Query};
+use poem_openapi::payload::Json;
+use poem_openapi::{Object, OpenApi};
+use sea_orm::prelude::Uuid;
+use sea_orm::TryIntoModel;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Object)]
+pub struct Post {
+    id: Uuid,
+    title: String,
+    text: String,
+}
+
+impl From<post::Model> for Post {
+    fn from(post: post::Model) -> Self {
+        Self {
+            id: post.id,
+            title: post.title,
+            text: post.text,
+        }
+    }
+}
+
+#[derive(Object)]
+pub struct PostInput {
+    title: String,
+    text: String,
+}
+
+impl From<PostInput> for post::Model {
+    fn from(input: PostInput) -> Self {
+        Self {
+            id: Uuid::nil(),
+            title: input.title,
+            text: input.text,
+        }
+    }
+}
+
+async fn find_post(state: &AppState, id: Uuid) -> poem::Result<post::Model> {
+    QueryCore::find_post_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_string("Post not found", StatusCode::NOT_FOUND))
+}
+
+pub struct PostsApi;
+
+#[OpenApi(prefix_path = "/posts", tag = "ApiTags::Posts")]
+impl PostsApi {
+    /// List posts
+    #[oai(path = "/", method = "get")]
+    async fn index(
+        &self,
+        state: Data<&AppState>,
+        page: Query<Option<u64>>,
+        items_per_page: Query<Option<u64>>,
+        /// Matched against the title.
+        q: Query<Option<String>>,
+    ) -> ApiResult<Json<ListBody<Post>>> {
+        let (page, per_page) = page_params(page.0, items_per_page.0);
+
+        let posts = QueryCore::find_posts_page(&state.conn, non_empty(&q.0), page, per_page)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(list(posts))
+    }
+
+    /// Get a post
+    #[oai(path = "/:id", method = "get")]
+    async fn read(&self, state: Data<&AppState>, id: Path<Uuid>) -> ApiResult<Json<DataBody<Post>>> {
+        Ok(data(find_post(&state, id.0).await?.into()))
+    }
+
+    /// Create a post
+    ///
+    /// Needs the `post:edit` permission.
+    #[oai(path = "/", method = "post")]
+    async fn create(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        body: Json<PostInput>,
+    ) -> ApiResult<Created<Post>> {
+        ensure_permission(&state, &user, Permission::PostEdit).await?;
+
+        let post = MutationCore::create_post(&state.conn, body.0.into())
+            .await
+            .and_then(TryIntoModel::try_into_model)
+            .map_err(InternalServerError)?;
+        Ok(Created::Created(data(post.into())))
+    }
+
+    /// Replace a post
+    ///
+    /// Needs the `post:edit` permission.
+    #[oai(path = "/:id", method = "put")]
+    async fn update(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+        body: Json<PostInput>,
+    ) -> ApiResult<Json<DataBody<Post>>> {
+        ensure_permission(&state, &user, Permission::PostEdit).await?;
+        find_post(&state, id.0).await?;
+
+        let post = MutationCore::update_post_by_id(&state.conn, id.0, body.0.into())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(data(post.into()))
+    }
+
+    /// Delete a post
+    #[oai(path = "/:id", method = "delete")]
+    async fn destroy(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Deleted> {
+        ensure_permission(&state, &user, Permission::PostEdit).await?;
+        find_post(&state, id.0).await?;
+
+        MutationCore::delete_post(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Deleted::Deleted)
+    }
+}
@@ -0,0 +1,184 @@
+use super::{data, list, non_empty, page_params};
+use super::{ApiResult, ApiTags, Created, DataBody, Deleted, ListBody};
+use crate::handlers::auth::permissions::{ensure_permission, user_permissions};
+use crate::handlers::episodes::ensure_show_permission;
+use crate::AppState;
+use entities::show;
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::web::Data;
+use poem::Error;
+use poem_openapi::param::{Path, Query};
+use poem_openapi::payload::Json;
+use poem_openapi::{Object, OpenApi};
+use sea_orm::prelude::Uuid;
+use sea_orm::TryIntoModel;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Object)]
+pub struct Show {
+    id: Uuid,
+    title: String,
+    description: String,
+    artwork_url: Option<String>,
+    /// Language code such as `en` or `de-CH`.
+    language: String,
+    /// iTunes category.
+    category: String,
+    author: String,
+    owner_name: String,
+    owner_email: String,
+    explicit: bool,
+}
+
+impl From<show::Model> for Show {
+    fn from(show: show::Model) -> Self {
+        Self {
+            id: show.id,
+            title: show.title,
+            description: show.description,
+            artwork_url: show.artwork_url,
+            language: show.language,
+            category: show.category,
+            author: show.author,
+            owner_name: show.owner_name,
+            owner_email: show.owner_email,
+            explicit: show.explicit,
+        }
+    }
+}
+
+#[derive(Object)]
+pub struct ShowInput {
+    title: String,
+    description: String,
+    artwork_url: Option<String>,
+    language: String,
+    category: String,
+    author: String,
+    owner_name: String,
+    owner_email: String,
+    #[oai(default)]
+    explicit: bool,
+}
+
+impl From<ShowInput> for show::Model {
+    fn from(input: ShowInput) -> Self {
+        Self {
+            id: Uuid::nil(),
+            title: input.title,
+            description: input.description,
+            artwork_url: input.artwork_url,
+            language: input.language,
+            category: input.category,
+            author: input.author,
+            owner_name: input.owner_name,
+            owner_email: input.owner_email,
+            explicit: input.explicit,
+        }
+    }
+}
+
+/// Shows the user may manage, `None` meaning all of them.
+pub(super) async fn accessible_show_ids(state: &AppState, user: &User) -> poem::Result<Option<Vec<Uuid>>> {
+    if user_permissions(state, user).await?.has(Permission::EpisodeEdit) {
+        return Ok(None);
+    }
+    let shows = QueryCore::find_shows_for_user(&state.conn, user)
+        .await
+        .map_err(InternalServerError)?;
+    Ok(Some(shows.iter().map(|show| show.id).collect()))
+}
+
+async fn find_show(state: &AppState, id: Uuid) -> poem::Result<show::Model> {
+    QueryCore::find_show_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_string("Show not found", StatusCode::NOT_FOUND))
+}
+
+pub struct ShowsApi;
+
+#[OpenApi(prefix_path = "/shows", tag = "ApiTags::Shows")]
+impl ShowsApi {
+    /// List the shows the user may manage
+    #[oai(path = "/", method = "get")]
+    async fn index(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        page: Query<Option<u64>>,
+        items_per_page: Query<Option<u64>>,
+        /// Matched against the title.
+        q: Query<Option<String>>,
+    ) -> ApiResult<Json<ListBody<Show>>> {
+        let (page, per_page) = page_params(page.0, items_per_page.0);
+        let show_ids = accessible_show_ids(&state, &user).await?;
+
+        let shows = QueryCore::find_shows_page(&state.conn, show_ids.as_deref(), non_empty(&q.0), page, per_page)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(list(shows))
+    }
+
+    /// Get a show
+    #[oai(path = "/:id", method = "get")]
+    async fn read(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Json<DataBody<Show>>> {
+        let show = find_show(&state, id.0).await?;
+        ensure_show_permission(&state, &user, Some(id.0), Permission::EpisodeEdit).await?;
+        Ok(data(show.into()))
+    }
+
+    /// Create a show
+    ///
+    /// Needs the `show:manage` permission.
+    #[oai(path = "/", method = "post")]
+    async fn create(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        body: Json<ShowInput>,
+    ) -> ApiResult<Created<Show>> {
+        ensure_permission(&state, &user, Permission::ShowManage).await?;
+
+        let show = MutationCore::create_show(&state.conn, body.0.into())
+            .await
+            .and_then(TryIntoModel::try_into_model)
+            .map_err(InternalServerError)?;
+        Ok(Created::Created(data(show.into())))
+    }
+
+    /// Replace a show
+    ///
+    /// Needs the `show:manage` permission.
+    #[oai(path = "/:id", method = "put")]
+    async fn update(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&User>,
+        id: Path<Uuid>,
+        body: Json<ShowInput>,
+    ) -> ApiResult<Json<DataBody<Show>>> {
+        ensure_permission(&state, &user, Permission::ShowManage).await?;
+        find_show(&state, id.0).await?;
+
+        let show = MutationCore::update_show_by_id(&state.conn, id.0, body.0.into())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(data(show.into()))
+    }
+
+    /// Delete a show along with its episodes
+    #[oai(path = "/:id", method = "delete")]
+    async fn destroy(&self, state: Data<&AppState>, user: Data<&User>, id: Path<Uuid>) -> ApiResult<Deleted> {
+        ensure_permission(&state, &user, Permission::ShowManage).await?;
+        find_show(&state, id.0).await?;
+
+        MutationCore::delete_show(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Deleted::Deleted)
+    }
+}
@@ -0,0 +1,130 @@
+use super::{data, list, non_empty, page_params};
+use super::{ApiResult, ApiTags, DataBody, Deleted, ListBody};
+use crate::handlers::auth::browser_only_middleware::ensure_browser;
+use crate::handlers::auth::providers::OidcProviders;
+use crate::handlers::auth::revoke_provider_tokens;
+use crate::handlers::auth::permissions::ensure_permission;
+use crate::handlers::users::{ensure_may_grant, find_roles};
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use entities::user;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::Data;
+use poem::Error;
+use poem_openapi::param::{Path, Query};
+use poem_openapi::payload::Json;
+use poem_openapi::{Object, OpenApi};
+use sea_orm::prelude::Uuid;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Object)]
+pub struct User {
+    id: Uuid,
+    email: String,
+    name: String,
+    role: String,
+    /// Set while the user may not log in.
+    deactivated_at: Option<DateTime<Utc>>,
+}
+
+impl From<user::Model> for User {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            role: user.role,
+            deactivated_at: user.deactivated_at,
+        }
+    }
+}
+
+// Users are created when they first log in, so the API only reads them and logs them out.
+pub struct UsersApi;
+
+#[OpenApi(prefix_path = "/users", tag = "ApiTags::Users")]
+impl UsersApi {
+    /// List users
+    ///
+    /// Needs the `user:manage` permission.
+    #[oai(path = "/", method = "get")]
+    async fn index(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&user::Model>,
+        page: Query<Option<u64>>,
+        items_per_page: Query<Option<u64>>,
+        role: Query<Option<String>>,
+        /// Matched against the name and the email address.
+        q: Query<Option<String>>,
+    ) -> ApiResult<Json<ListBody<User>>> {
+        ensure_permission(&state, &user, Permission::UserManage).await?;
+        let (page, per_page) = page_params(page.0, items_per_page.0);
+
+        let users = QueryCore::find_users_page(
+            &state.conn,
+            non_empty(&role.0),
+            non_empty(&q.0),
+            page,
+            per_page,
+        )
+        .await
+        .map_err(InternalServerError)?;
+        Ok(list(users))
+    }
+
+    /// Get the user the request is authenticated as
+    #[oai(path = "/me", method = "get")]
+    async fn me(&self, user: Data<&user::Model>) -> Json<DataBody<User>> {
+        data(user.0.clone().into())
+    }
+
+    /// Get a user
+    #[oai(path = "/:id", method = "get")]
+    async fn read(
+        &self,
+        state: Data<&AppState>,
+        user: Data<&user::Model>,
+        id: Path<Uuid>,
+    ) -> ApiResult<Json<DataBody<User>>> {
+        ensure_permission(&state, &user, Permission::UserManage).await?;
+
+        let user = QueryCore::find_user_by_id(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_string("User not found", StatusCode::NOT_FOUND))?;
+        Ok(data(user.into()))
+    }
+
+    /// Log a user out
+    ///
+    /// Ends every browser session of the user. Needs the `user:manage` permission and a role
+    /// above the user's, and cannot be called with an API token. API tokens of the user keep
+    /// working; revoke those separately.
+    #[oai(path = "/:id/sessions", method = "delete")]
+    async fn log_out(
+        &self,
+        state: Data<&AppState>,
+        providers: Data<&OidcProviders>,
+        session: &Session,
+        user: Data<&user::Model>,
+        id: Path<Uuid>,
+    ) -> ApiResult<Deleted> {
+        ensure_browser(session)?;
+        ensure_permission(&state, &user, Permission::UserManage).await?;
+
+        let target = QueryCore::find_user_by_id(&state.conn, id.0)
+            .await
+            .map_err(InternalServerError)?
+            .ok_or_else(|| Error::from_string("User not found", StatusCode::NOT_FOUND))?;
+        ensure_may_grant(&find_roles(&state).await?, &user, &target.role)?;
+        let ended = MutationCore::end_user_sessions_of_user(&state.conn, id.0, None)
+            .await
+            .map_err(InternalServerError)?;
+        revoke_provider_tokens(&providers, ended);
+        Ok(Deleted::Deleted)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::handlers::auth::API_TOKEN_KEY;
+use crate::AppState;
+use chrono::{Duration, Utc};
+use poem::error::InternalServerError;
+use poem::http::{header, StatusCode};
+use poem::session::Session;
+use poem::{Endpoint, Error, IntoResponse, Request, Response};
+use service::api_tokens::{has_scope, TokenScope};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+/// How stale `last_used_at` may get before it is written again.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+fn bearer_token(req: &Request) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+/// Logs requests carrying `Authorization: Bearer <token>` in as the token's owner.
+///
+/// The user ends up in a session of its own under `current_user`, exactly where the
+/// OpenID Connect login puts it, so every handler and middleware treats token requests
+/// like browser requests. That session is never written back as a cookie. It also holds
+/// the token's id under [`API_TOKEN_KEY`], which keeps token requests away from account
+/// management, see the browser only middleware.
+pub async fn bearer_token_middleware<E: Endpoint>(next: E, mut req: Request) -> poem::Result<Response> {
+    let Some(token) = bearer_token(&req) else {
+        return next.call(req).await.map(IntoResponse::into_response);
+    };
+    let state = req
+        .data::<AppState>()
+        .ok_or_else(|| Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let conn = &state.conn;
+    let now = Utc::now();
+
+    let (api_token, user) = QueryCore::find_api_token_user(conn, &token, now)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_string("Invalid or expired API token", StatusCode::UNAUTHORIZED))?;
+    if user.deactivated_at.is_some() {
+        return Err(Error::from_string("This account has been deactivated", StatusCode::UNAUTHORIZED));
+    }
+    let needed = TokenScope::for_method(req.method().as_str());
+    if !has_scope(&api_token.scopes, needed) {
+        return Err(Error::from_string(
+            format!("This API token lacks the {} scope", needed.as_str()),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+
+    let stale = api_token
+        .last_used_at
+        .is_none_or(|last_used_at| now - last_used_at > Duration::seconds(LAST_USED_RESOLUTION_SECONDS));
+    if stale {
+        MutationCore::touch_api_token(conn, api_token.id, now)
+            .await
+            .map_err(InternalServerError)?;
+    }
+
+    let session = Session::default();
+    session.set("current_user", user);
+    session.set(API_TOKEN_KEY, api_token.id);
+    req.extensions_mut().insert(session);
+    next.call(req).await.map(IntoResponse::into_response)
+}
@@ -0,0 +1,80 @@
+use crate::handlers::auth::API_TOKEN_KEY;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::{Endpoint, Error, IntoResponse, Request, Response};
+use sea_orm::prelude::Uuid;
+
+/// Refuses requests authenticated with an API token.
+///
+/// Tokens are meant for scripts using the API. Letting them manage tokens, sessions or
+/// accounts would let a leaked token mint a token that never expires or end the sessions
+/// of whoever notices the leak.
+pub async fn browser_only_middleware<E: Endpoint>(next: E, req: Request) -> poem::Result<Response> {
+    if let Some(session) = req.extensions().get::<Session>() {
+        ensure_browser(session)?;
+    }
+    next.call(req).await.map(IntoResponse::into_response)
+}
+
+/// The check of [`browser_only_middleware`], for API endpoints that must not be called with
+/// a token while the rest of the API may.
+#[allow(clippy::result_large_err)]
+pub(crate) fn ensure_browser(session: &Session) -> poem::Result<()> {
+    if session.get::<Uuid>(API_TOKEN_KEY).is_some() {
+        return Err(Error::from_string(
+            "API tokens cannot manage tokens, sessions or accounts",
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::TestApp;
+    use chrono::Utc;
+    use poem::http::{header, Method, StatusCode};
+    use poem::Request;
+    use service::api_tokens::TokenScope;
+    use service::permissions::SUPER_ADMIN_ROLE;
+    use service::{Mutation as MutationCore, Query as QueryCore};
+
+    async fn send_with_token(app: &TestApp, token: &str, method: Method, uri: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri_str(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .content_type("application/x-www-form-urlencoded")
+            .body("name=forever&scopes=read&scopes=write");
+        app.send(req).await.status()
+    }
+
+    #[tokio::test]
+    async fn api_tokens_cannot_manage_tokens_sessions_or_users() {
+        let app = TestApp::new().await;
+        let user = app.create_user("admin@example.com", SUPER_ADMIN_ROLE).await;
+        let (_, token) =
+            MutationCore::create_api_token(&app.state.conn, user.id, "script", &TokenScope::ALL, None, Utc::now())
+                .await
+                .unwrap();
+
+        let api_log_out = format!("/api/v1/users/{}/sessions", user.id);
+        for (method, uri) in [
+            (Method::POST, "/tokens/"),
+            (Method::GET, "/tokens/"),
+            (Method::DELETE, "/sessions/"),
+            (Method::GET, "/users/"),
+            (Method::POST, "/users/invitations"),
+            (Method::DELETE, api_log_out.as_str()),
+        ] {
+            let status = send_with_token(&app, &token, method.clone(), uri).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{method} {uri}");
+        }
+        let tokens = QueryCore::find_api_tokens_by_user(&app.state.conn, user.id).await.unwrap();
+        assert_eq!(tokens.len(), 1);
+
+        // The same account still manages its tokens from the browser.
+        app.log_in(&user).await;
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,85 @@
+//! Everything that can go wrong while logging in through a provider, and the ways a
+//! login can be refused whichever way it is done.
+
+use crate::AppState;
+use poem::http::StatusCode;
+use poem::web::Html;
+use poem::{IntoResponse, Response};
+use sea_orm::DbErr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("There is no login provider named {0}")]
+    UnknownProvider(String),
+    /// The callback does not belong to a login started in this browser, or the login was
+    /// finished already and the callback is replayed.
+    #[error("This login is not in progress anymore. Please start again.")]
+    NoLoginInProgress,
+    #[error("The login was started with another provider. Please start again.")]
+    ProviderMismatch,
+    /// The `state` parameter does not match the one sent to the provider, as happens with
+    /// forged callbacks.
+    #[error("The login could not be verified. Please start again.")]
+    StateMismatch,
+    /// The provider reported an error instead of sending a code, e.g. `access_denied`.
+    #[error("{provider} did not log you in: {message}")]
+    Provider { provider: String, message: String },
+    #[error("{0} did not send an authorization code")]
+    MissingCode(String),
+    /// The provider refused the code, typically because it expired or was used before.
+    #[error("{provider} did not accept the login: {message}")]
+    CodeRejected { provider: String, message: String },
+    #[error("{provider} could not be reached: {message}")]
+    ProviderUnavailable { provider: String, message: String },
+    #[error("{0} did not return an ID token")]
+    MissingIdToken(String),
+    /// The ID token's signature, audience, expiry or nonce is wrong.
+    #[error("The ID token from {provider} is invalid: {message}")]
+    InvalidIdToken { provider: String, message: String },
+    #[error("{0} did not confirm an email address we can use for your account")]
+    NoUsableEmail(String),
+    /// Only invited people may sign up, see `REGISTRATION`.
+    #[error("There is no account for your email address. Ask an admin to invite you.")]
+    NotInvited,
+    #[error("Your account has been deactivated")]
+    Deactivated,
+    #[error("Something went wrong on our side")]
+    Database(#[from] DbErr),
+}
+
+impl AuthError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthError::UnknownProvider(_) => StatusCode::NOT_FOUND,
+            AuthError::NoLoginInProgress
+            | AuthError::ProviderMismatch
+            | AuthError::StateMismatch
+            | AuthError::MissingCode(_)
+            | AuthError::CodeRejected { .. }
+            | AuthError::InvalidIdToken { .. } => StatusCode::BAD_REQUEST,
+            AuthError::Provider { .. }
+            | AuthError::NoUsableEmail(_)
+            | AuthError::NotInvited
+            | AuthError::Deactivated => StatusCode::FORBIDDEN,
+            AuthError::ProviderUnavailable { .. } | AuthError::MissingIdToken(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            AuthError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The error page, with a way back to the login page.
+    pub fn render(&self, state: &AppState) -> Response {
+        eprintln!("Login failed: {self:?}");
+
+        let mut ctx = tera::Context::new();
+        ctx.insert("message", &self.to_string());
+        match state.templates.render("auth/error.html.tera", &ctx) {
+            Ok(body) => Html(body).with_status(self.status()).into_response(),
+            Err(err) => {
+                eprintln!("Failed to render the login error page: {err}");
+                self.status().into_response()
+            }
+        }
+    }
+}
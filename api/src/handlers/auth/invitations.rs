@@ -0,0 +1,110 @@
+//! Accepting an invitation. The link in the invitation mail leads to a page where the
+//! invitee picks a name and a password, or logs in through a provider with the invited
+//! address, which accepts the invitation as well.
+
+use super::local::{ensure_enabled, hash_password_blocking, log_in_locally, render, render_message};
+use super::providers::OidcProviders;
+use super::{provider_links, ClientInfo};
+use crate::AppState;
+use chrono::Utc;
+use entities::invitation;
+use poem::error::InternalServerError;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path};
+use poem::{handler, IntoResponse, Response, Result};
+use serde::Deserialize;
+use service::accounts::validate_password;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Deserialize)]
+pub struct AcceptForm {
+    name: String,
+    password: String,
+    password_confirmation: String,
+}
+
+#[allow(clippy::result_large_err)]
+fn render_expired(state: &AppState) -> Result<Response> {
+    Ok(render_message(
+        state,
+        "This invitation has expired",
+        "Invitations work once and only for a while. Ask whoever invited you for a new one.",
+    )?
+    .into_response())
+}
+
+/// The invitation page. `ctx` carries what the form shows, e.g. an error.
+#[allow(clippy::result_large_err)]
+fn render_invitation(
+    state: &AppState,
+    providers: &OidcProviders,
+    invitation: &invitation::Model,
+    token: &str,
+    mut ctx: tera::Context,
+) -> Result<Html<String>> {
+    ctx.insert("invitation", invitation);
+    ctx.insert("token", token);
+    ctx.insert("providers", &provider_links(providers));
+    ctx.insert("local_accounts", &state.config.auth.local_accounts);
+    render(state, "auth/invitation.html.tera", &ctx)
+}
+
+#[handler]
+pub async fn accept_form(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    Path(token): Path<String>,
+) -> Result<Response> {
+    let invitation = QueryCore::find_pending_invitation_by_token(&state.conn, &token, Utc::now())
+        .await
+        .map_err(InternalServerError)?;
+    let Some(invitation) = invitation else {
+        return render_expired(&state);
+    };
+
+    Ok(render_invitation(&state, &providers, &invitation, &token, tera::Context::new())?.into_response())
+}
+
+/// Creates the invitee's account with a password and logs them in.
+#[handler]
+pub async fn accept(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    client: ClientInfo,
+    Path(token): Path<String>,
+    Form(form): Form<AcceptForm>,
+) -> Result<Response> {
+    ensure_enabled(&state)?;
+    let now = Utc::now();
+    let invitation = QueryCore::find_pending_invitation_by_token(&state.conn, &token, now)
+        .await
+        .map_err(InternalServerError)?;
+    let Some(invitation) = invitation else {
+        return render_expired(&state);
+    };
+
+    let name = form.name.trim();
+    let error = if name.is_empty() {
+        Some("Tell us your name".to_string())
+    } else if form.password != form.password_confirmation {
+        Some("The passwords do not match".to_string())
+    } else {
+        validate_password(&form.password).err()
+    };
+    if let Some(error) = error {
+        let mut ctx = tera::Context::new();
+        ctx.insert("error", &error);
+        ctx.insert("name", name);
+        return Ok(render_invitation(&state, &providers, &invitation, &token, ctx)?.into_response());
+    }
+
+    let password_hash = hash_password_blocking(form.password).await?;
+    let user = MutationCore::accept_invitation(&state.conn, invitation, name, Some(password_hash), now)
+        .await
+        .map_err(InternalServerError)?;
+    let Some(user) = user else {
+        return render_expired(&state);
+    };
+    log_in_locally(&state, session, client, user).await
+}
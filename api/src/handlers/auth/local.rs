@@ -0,0 +1,558 @@
+//! Local accounts for people without an account at any of the providers: registration
+//! with a password, email verification, password reset and magic links.
+//!
+//! All of it is off unless `auth.local_accounts` is `true`, and signing up is only open
+//! while `auth.registration` is `"open"`, see `config/default.toml`. Answers never reveal
+//! whether an address belongs to an account, and mail is sent in the background so timing
+//! does not either.
+
+use super::error::AuthError;
+use super::providers::OidcProviders;
+use super::{log_in, render_login, ClientInfo};
+use crate::AppState;
+use chrono::Utc;
+use entities::user::Model as User;
+use mailer::Email;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Query};
+use poem::{handler, Error, IntoResponse, Response, Result};
+use serde::Deserialize;
+use service::accounts::{
+    hash_password, looks_like_email, normalize_email, validate_password, verify_password,
+    waste_password_check, TokenPurpose,
+};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Deserialize)]
+pub struct PasswordLoginForm {
+    email: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterForm {
+    name: String,
+    email: String,
+    password: String,
+    password_confirmation: String,
+}
+
+#[derive(Deserialize)]
+pub struct EmailForm {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct TokenParams {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetForm {
+    token: String,
+    password: String,
+    password_confirmation: String,
+}
+
+#[allow(clippy::result_large_err)]
+pub(super) fn ensure_enabled(state: &AppState) -> Result<()> {
+    if state.config.auth.local_accounts {
+        Ok(())
+    } else {
+        Err(Error::from_status(StatusCode::NOT_FOUND))
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub(super) fn render(state: &AppState, template: &str, ctx: &tera::Context) -> Result<Html<String>> {
+    let body = state
+        .templates
+        .render(template, ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// A page telling the visitor what happens next, e.g. to check their inbox.
+#[allow(clippy::result_large_err)]
+pub(super) fn render_message(state: &AppState, title: &str, message: &str) -> Result<Html<String>> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", title);
+    ctx.insert("message", message);
+    render(state, "auth/message.html.tera", &ctx)
+}
+
+fn form_error(error: &str, email: &str) -> tera::Context {
+    let mut ctx = tera::Context::new();
+    ctx.insert("error", error);
+    ctx.insert("email", email);
+    ctx
+}
+
+/// Renders `template` from `frontend/templates/emails` and sends it to `to`. Sending
+/// happens in the background: failures are only logged.
+#[allow(clippy::result_large_err)]
+pub(crate) fn send_email(
+    state: &AppState,
+    to: &str,
+    subject: &str,
+    template: &str,
+    mut ctx: tera::Context,
+) -> Result<()> {
+    ctx.insert("public_url", state.public_url());
+    let body = state
+        .templates
+        .render(&format!("emails/{template}.txt.tera"), &ctx)
+        .map_err(InternalServerError)?;
+    let email = Email {
+        to: to.to_string(),
+        subject: subject.to_string(),
+        body,
+    };
+
+    let mailer = state.mailer.clone();
+    tokio::spawn(async move {
+        let to = email.to.clone();
+        if let Err(err) = mailer.send(email).await {
+            eprintln!("Failed to send mail to {to}: {err}");
+        }
+    });
+    Ok(())
+}
+
+/// Creates a one-time token for `user` and mails them a link to `path` carrying it.
+async fn send_token_link(
+    state: &AppState,
+    user: &User,
+    purpose: TokenPurpose,
+    subject: &str,
+    path: &str,
+) -> Result<()> {
+    let token = MutationCore::create_one_time_token(&state.conn, user.id, purpose, Utc::now())
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("name", &user.name);
+    ctx.insert("link", &format!("{}{path}?token={token}", state.public_url()));
+    ctx.insert("valid_minutes", &purpose.lifetime().num_minutes());
+    send_email(state, &user.email, subject, purpose.as_str(), ctx)
+}
+
+async fn send_verification(state: &AppState, user: &User) -> Result<()> {
+    send_token_link(state, user, TokenPurpose::VerifyEmail, "Confirm your email address", "/auth/verify").await
+}
+
+/// Argon2 is slow on purpose, so keep it off the threads serving requests.
+pub(super) async fn hash_password_blocking(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || hash_password(&password))
+        .await
+        .map_err(InternalServerError)?
+        .map_err(|err| Error::from_string(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Checks `password` against a user's hash, taking as long when there is no user or the
+/// user has no password.
+async fn check_password(password_hash: Option<String>, password: String) -> Result<bool> {
+    tokio::task::spawn_blocking(move || match password_hash {
+        Some(password_hash) => verify_password(&password_hash, &password),
+        None => {
+            waste_password_check(&password);
+            false
+        }
+    })
+    .await
+    .map_err(InternalServerError)
+}
+
+/// Logs `user` in, or shows why they may not log in.
+pub(super) async fn log_in_locally(state: &AppState, session: &Session, client: ClientInfo, user: User) -> Result<Response> {
+    match log_in(state, session, client, user, None).await {
+        Ok(redirect) => Ok(redirect.into_response()),
+        Err(AuthError::Database(err)) => Err(InternalServerError(err)),
+        Err(err) => Ok(err.render(state)),
+    }
+}
+
+#[handler]
+pub async fn password_login(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    client: ClientInfo,
+    Form(form): Form<PasswordLoginForm>,
+) -> Result<Response> {
+    ensure_enabled(&state)?;
+    let email = normalize_email(&form.email);
+
+    let user = QueryCore::find_user_by_email(&state.conn, &email)
+        .await
+        .map_err(InternalServerError)?;
+    let password_hash = user.as_ref().and_then(|user| user.password_hash.clone());
+    let password_matches = check_password(password_hash, form.password).await?;
+    let Some(user) = user.filter(|_| password_matches) else {
+        return Ok(render_login(&state, &providers, form_error("Wrong email or password", &email))?.into_response());
+    };
+    if user.email_verified_at.is_none() {
+        send_verification(&state, &user).await?;
+        return Ok(render_login(
+            &state,
+            &providers,
+            form_error("Confirm your email address first. We have sent you a new link.", &email),
+        )?
+        .into_response());
+    }
+
+    log_in_locally(&state, session, client, user).await
+}
+
+/// Signing up is for invited people only: they follow the link in their invitation.
+#[allow(clippy::result_large_err)]
+fn ensure_registration_open(state: &AppState) -> Result<()> {
+    if state.config.auth.registration.is_open() {
+        Ok(())
+    } else {
+        Err(Error::from_string(
+            "Signing up is by invitation only",
+            StatusCode::FORBIDDEN,
+        ))
+    }
+}
+
+#[handler]
+pub async fn register_form(state: Data<&AppState>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    ensure_registration_open(&state)?;
+    render(&state, "auth/register.html.tera", &tera::Context::new())
+}
+
+#[handler]
+pub async fn register(state: Data<&AppState>, Form(form): Form<RegisterForm>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    ensure_registration_open(&state)?;
+    let name = form.name.trim();
+    let email = normalize_email(&form.email);
+
+    let error = if name.is_empty() {
+        Some("Tell us your name".to_string())
+    } else if !looks_like_email(&email) {
+        Some("That does not look like an email address".to_string())
+    } else if form.password != form.password_confirmation {
+        Some("The passwords do not match".to_string())
+    } else {
+        validate_password(&form.password).err()
+    };
+    if let Some(error) = error {
+        let mut ctx = form_error(&error, &email);
+        ctx.insert("name", name);
+        return render(&state, "auth/register.html.tera", &ctx);
+    }
+
+    let existing = QueryCore::find_user_by_email(&state.conn, &email)
+        .await
+        .map_err(InternalServerError)?;
+    match existing {
+        // Tell the owner instead of the visitor, who might be somebody else.
+        Some(user) => {
+            let mut ctx = tera::Context::new();
+            ctx.insert("name", &user.name);
+            send_email(&state, &user.email, "You already have an account", "already_registered", ctx)?;
+        }
+        None => {
+            let password_hash = hash_password_blocking(form.password).await?;
+            let user = MutationCore::register_local_user(&state.conn, &email, name, password_hash)
+                .await
+                .map_err(InternalServerError)?;
+            send_verification(&state, &user).await?;
+        }
+    }
+
+    render_message(
+        &state,
+        "Check your inbox",
+        &format!("We have sent a link to {email}. Follow it to finish signing up."),
+    )
+}
+
+#[handler]
+pub async fn verify(
+    state: Data<&AppState>,
+    session: &Session,
+    client: ClientInfo,
+    Query(params): Query<TokenParams>,
+) -> Result<Response> {
+    ensure_enabled(&state)?;
+    let now = Utc::now();
+    let user = MutationCore::consume_one_time_token(&state.conn, &params.token, TokenPurpose::VerifyEmail, now)
+        .await
+        .map_err(InternalServerError)?;
+    let Some(user) = user else {
+        return Ok(render_message(
+            &state,
+            "This link has expired",
+            "Log in with your password to get a new one.",
+        )?
+        .into_response());
+    };
+
+    let user = MutationCore::verify_email(&state.conn, user, now)
+        .await
+        .map_err(InternalServerError)?;
+    log_in_locally(&state, session, client, user).await
+}
+
+#[handler]
+pub async fn forgot_form(state: Data<&AppState>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    render(&state, "auth/forgot.html.tera", &tera::Context::new())
+}
+
+#[handler]
+pub async fn forgot(state: Data<&AppState>, Form(form): Form<EmailForm>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    let email = normalize_email(&form.email);
+
+    let user = QueryCore::find_user_by_email(&state.conn, &email)
+        .await
+        .map_err(InternalServerError)?;
+    if let Some(user) = user {
+        send_token_link(&state, &user, TokenPurpose::ResetPassword, "Reset your password", "/auth/reset").await?;
+    }
+
+    render_message(
+        &state,
+        "Check your inbox",
+        &format!("If {email} belongs to an account, we have sent it a link to choose a new password."),
+    )
+}
+
+#[handler]
+pub async fn reset_form(state: Data<&AppState>, Query(params): Query<TokenParams>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    let mut ctx = tera::Context::new();
+    ctx.insert("token", &params.token);
+    render(&state, "auth/reset.html.tera", &ctx)
+}
+
+#[handler]
+pub async fn reset(
+    state: Data<&AppState>,
+    session: &Session,
+    client: ClientInfo,
+    Form(form): Form<ResetForm>,
+) -> Result<Response> {
+    ensure_enabled(&state)?;
+    let error = if form.password != form.password_confirmation {
+        Some("The passwords do not match".to_string())
+    } else {
+        validate_password(&form.password).err()
+    };
+    if let Some(error) = error {
+        let mut ctx = tera::Context::new();
+        ctx.insert("token", &form.token);
+        ctx.insert("error", &error);
+        return Ok(render(&state, "auth/reset.html.tera", &ctx)?.into_response());
+    }
+
+    let now = Utc::now();
+    let user = MutationCore::consume_one_time_token(&state.conn, &form.token, TokenPurpose::ResetPassword, now)
+        .await
+        .map_err(InternalServerError)?;
+    let Some(user) = user else {
+        return Ok(render_message(
+            &state,
+            "This link has expired",
+            "Ask for a new one on the forgotten password page.",
+        )?
+        .into_response());
+    };
+
+    let password_hash = hash_password_blocking(form.password).await?;
+    let user = MutationCore::set_password(&state.conn, user, password_hash, now)
+        .await
+        .map_err(InternalServerError)?;
+    log_in_locally(&state, session, client, user).await
+}
+
+/// Mails a link that logs the owner of the address in without a password.
+#[handler]
+pub async fn send_magic_link(state: Data<&AppState>, Form(form): Form<EmailForm>) -> Result<impl IntoResponse> {
+    ensure_enabled(&state)?;
+    let email = normalize_email(&form.email);
+
+    let user = QueryCore::find_user_by_email(&state.conn, &email)
+        .await
+        .map_err(InternalServerError)?;
+    if let Some(user) = user {
+        send_token_link(&state, &user, TokenPurpose::MagicLink, "Your login link", "/auth/magic").await?;
+    }
+
+    render_message(
+        &state,
+        "Check your inbox",
+        &format!("If {email} belongs to an account, we have sent it a link to log in with."),
+    )
+}
+
+#[handler]
+pub async fn magic_login(
+    state: Data<&AppState>,
+    session: &Session,
+    client: ClientInfo,
+    Query(params): Query<TokenParams>,
+) -> Result<Response> {
+    ensure_enabled(&state)?;
+    let now = Utc::now();
+    let user = MutationCore::consume_one_time_token(&state.conn, &params.token, TokenPurpose::MagicLink, now)
+        .await
+        .map_err(InternalServerError)?;
+    let Some(user) = user else {
+        return Ok(render_message(
+            &state,
+            "This link has expired",
+            "Login links work once and only for a short while. Ask for a new one on the login page.",
+        )?
+        .into_response());
+    };
+
+    let user = MutationCore::claim_email(&state.conn, user, now)
+        .await
+        .map_err(InternalServerError)?;
+    log_in_locally(&state, session, client, user).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{body_text, TestApp};
+    use poem::http::{Method, StatusCode};
+    use service::invitations::RegistrationPolicy;
+    use service::Query as QueryCore;
+
+    const PASSWORD: &str = "correct horse battery";
+
+    async fn app_with_local_accounts() -> TestApp {
+        TestApp::with_config(|config| config.auth.local_accounts = true).await
+    }
+
+    /// The token of the link to `path` in `mail`.
+    fn token_in(mail: &str, path: &str) -> String {
+        let (_, link) = mail.split_once(&format!("{path}?token=")).expect("the mail has a link");
+        link.split_whitespace().next().unwrap().to_string()
+    }
+
+    async fn register(app: &TestApp, email: &str) -> String {
+        let form = [
+            ("name", "Jane"),
+            ("email", email),
+            ("password", PASSWORD),
+            ("password_confirmation", PASSWORD),
+        ];
+        body_text(app.send_form(Method::POST, "/auth/register", &form).await).await
+    }
+
+    async fn log_in(app: &TestApp, email: &str, password: &str) -> poem::Response {
+        let form = [("email", email), ("password", password)];
+        app.send_form(Method::POST, "/auth/password", &form).await
+    }
+
+    async fn reset(app: &TestApp, token: &str, password: &str, confirmation: &str) -> poem::Response {
+        let form = [("token", token), ("password", password), ("password_confirmation", confirmation)];
+        app.send_form(Method::POST, "/auth/reset", &form).await
+    }
+
+    async fn logged_in(app: &TestApp) -> bool {
+        app.get("/tokens/").await.status() == StatusCode::OK
+    }
+
+    #[tokio::test]
+    async fn registrations_are_confirmed_by_mail() {
+        let app = app_with_local_accounts().await;
+        let body = register(&app, " Jane@Example.com").await;
+        assert!(body.contains("We have sent a link to jane@example.com"));
+        let mail = app.sent_mail(1).await;
+        assert!(mail[0].contains("To: jane@example.com\nSubject: Confirm your email address\n"));
+        let token = token_in(&mail[0], "/auth/verify");
+
+        // Until then the password is not enough, and asking again sends another link.
+        let response = log_in(&app, "jane@example.com", PASSWORD).await;
+        assert!(body_text(response).await.contains("Confirm your email address first"));
+        assert!(!logged_in(&app).await);
+        let mail = app.sent_mail(2).await;
+        assert_ne!(token_in(&mail[1], "/auth/verify"), token);
+
+        let response = app.get(&format!("/auth/verify?token={token}")).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(logged_in(&app).await);
+        let user = QueryCore::find_user_by_email(&app.state.conn, "jane@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.email_verified_at.is_some());
+
+        app.log_out();
+        let response = app.get(&format!("/auth/verify?token={token}")).await;
+        assert!(body_text(response).await.contains("This link has expired"));
+        assert!(!logged_in(&app).await);
+        assert_eq!(log_in(&app, "jane@example.com", PASSWORD).await.status(), StatusCode::SEE_OTHER);
+        assert!(logged_in(&app).await);
+
+        // Signing up again only tells the owner.
+        app.log_out();
+        let body = register(&app, "jane@example.com").await;
+        assert!(body.contains("We have sent a link to jane@example.com"));
+        let mail = app.sent_mail(3).await;
+        assert!(mail[2].contains("Subject: You already have an account\n"));
+        assert!(!mail[2].contains("?token="));
+    }
+
+    #[tokio::test]
+    async fn passwords_are_reset_by_mail() {
+        let app = app_with_local_accounts().await;
+        app.create_user("jane@example.com", "user").await;
+
+        let response = app.send_form(Method::POST, "/auth/forgot", &[("email", "nobody@example.com")]).await;
+        assert!(body_text(response).await.contains("If nobody@example.com belongs to an account"));
+        let response = app.send_form(Method::POST, "/auth/forgot", &[("email", "JANE@example.com")]).await;
+        assert!(body_text(response).await.contains("If jane@example.com belongs to an account"));
+        let mail = app.sent_mail(1).await;
+        assert_eq!(mail.len(), 1, "unknown addresses get no mail");
+        assert!(mail[0].contains("To: jane@example.com\nSubject: Reset your password\n"));
+        let token = token_in(&mail[0], "/auth/reset");
+
+        let response = reset(&app, &token, PASSWORD, "something else").await;
+        assert!(body_text(response).await.contains("The passwords do not match"));
+        let response = reset(&app, &token, "short", "short").await;
+        assert!(body_text(response).await.contains("Use at least"));
+        assert!(!logged_in(&app).await);
+
+        // Mistakes keep the link usable.
+        let response = reset(&app, &token, PASSWORD, PASSWORD).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(logged_in(&app).await);
+
+        app.log_out();
+        let response = reset(&app, &token, "another password", "another password").await;
+        assert!(body_text(response).await.contains("This link has expired"));
+        assert_eq!(log_in(&app, "jane@example.com", "another password").await.status(), StatusCode::OK);
+        assert!(!logged_in(&app).await);
+        assert_eq!(log_in(&app, "jane@example.com", PASSWORD).await.status(), StatusCode::SEE_OTHER);
+        assert!(logged_in(&app).await);
+    }
+
+    #[tokio::test]
+    async fn local_accounts_follow_the_config() {
+        let app = TestApp::new().await;
+        assert_eq!(app.get("/auth/register").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(app.get("/auth/forgot").await.status(), StatusCode::NOT_FOUND);
+
+        let app = TestApp::with_config(|config| {
+            config.auth.local_accounts = true;
+            config.auth.registration = RegistrationPolicy::InviteOnly;
+        })
+        .await;
+        assert_eq!(app.get("/auth/register").await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(register(&app, "jane@example.com").await, "Signing up is by invitation only");
+        assert_eq!(app.get("/auth/forgot").await.status(), StatusCode::OK);
+    }
+}
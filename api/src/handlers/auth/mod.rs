@@ -1,259 +1,560 @@
-pub(crate) mod required_role_middleware;
+pub(crate) mod require_permission_middleware;
 pub(crate) mod login_required_middleware;
+pub(crate) mod bearer_token_middleware;
+pub(crate) mod browser_only_middleware;
+pub(crate) mod error;
+pub(crate) mod invitations;
+pub(crate) mod local;
+pub(crate) mod permissions;
+pub(crate) mod providers;
+pub(crate) mod session_tracking_middleware;
 
-use openidconnect::core::{
-    CoreAuthDisplay, CoreAuthPrompt, CoreClaimName, CoreClaimType, CoreClient,
-    CoreClientAuthMethod, CoreGenderClaim, CoreGrantType, CoreIdTokenVerifier,
-    CoreJsonWebKey, CoreJweContentEncryptionAlgorithm, CoreJweKeyManagementAlgorithm,
-    CoreResponseMode, CoreResponseType, CoreSubjectIdentifierType, CoreTokenIntrospectionResponse,
-    CoreTokenResponse,
-};
+use openidconnect::core::{CoreIdTokenVerifier, CoreResponseType};
 use openidconnect::{
-    AdditionalProviderMetadata, AuthenticationFlow, Client, ClientId, ClientSecret,
-    EmptyAdditionalClaims, IssuerUrl, Nonce, ProviderMetadata, RedirectUrl, RevocationUrl,
-    StandardErrorResponse,
+    AuthenticationFlow, AuthorizationCode, CsrfToken, Nonce, PkceCodeChallenge, PkceCodeVerifier,
+    OAuth2TokenResponse, RequestTokenError, Scope,
 };
-use std::collections::HashMap;
-
-use serde::{Deserialize, Serialize};
 
+use crate::handlers::auth::error::AuthError;
+use crate::handlers::auth::providers::{http_client, OidcProvider, OidcProviders};
 use crate::AppState;
-use entities::user::ActiveModel as UserActiveModel;
-use oauth2::basic::{BasicErrorResponseType, BasicRevocationErrorResponse};
-use oauth2::{
-    AuthorizationCode, CsrfToken, EndpointMaybeSet, EndpointNotSet, EndpointSet, Scope,
-    StandardRevocableToken,
-};
-use poem::error::NotFoundError;
-use poem::http::StatusCode;
+use chrono::Utc;
+use entities::user::Model as User;
+use entities::user_session;
+use poem::error::InternalServerError;
+use poem::http::{header, StatusCode};
 use poem::session::Session;
-use poem::web::{Data, Redirect};
-use poem::{get, handler, IntoResponse, Result, Route};
-use sea_orm::ActiveValue::Set;
-use sea_orm::{ActiveModelTrait, NotSet};
-use std::env;
-use std::string::ToString;
-
-use service::Query as QueryCore;
-
-// Teach openidconnect-rs about a Google custom extension to the OpenID Discovery response that we can use as the RFC
-// 7009 OAuth 2.0 Token Revocation endpoint. For more information about the Google specific Discovery response see the
-// Google OpenID Connect service documentation at: https://developers.google.com/identity/protocols/oauth2/openid-connect#discovery
-#[derive(Clone, Debug, Deserialize, Serialize)]
-struct RevocationEndpointProviderMetadata {
-    revocation_endpoint: String,
+use poem::web::{Data, Html, Path, Query, RealIp, Redirect};
+use poem::{get, handler, post, Error, FromRequest, IntoResponse, Request, RequestBody, Response, Result, Route};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use service::{ExternalIdentity, IdentitySignIn, Mutation as MutationCore, ProviderGrant, SessionClient};
+
+const REDIRECT_AFTER_LOGIN_KEY: &str = "redirect_after_login";
+const PENDING_LOGIN_KEY: &str = "pending_login";
+/// The `user_session` row of the login, see the session tracking middleware.
+const SESSION_ID_KEY: &str = "session_id";
+/// The API token a request authenticated with, see the bearer token middleware.
+const API_TOKEN_KEY: &str = "api_token_id";
+/// Longest `User-Agent` header kept for the sessions page.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+/// Providers configured with the `GOOGLE_*` variables call back here instead of
+/// `/auth/google/callback`.
+const LEGACY_CALLBACK_PROVIDER: &str = "google";
+
+/// The logged in user, or `401 Unauthorized` for anonymous requests.
+#[allow(clippy::result_large_err)]
+pub(crate) fn current_user(session: &Session) -> Result<User> {
+    session
+        .get::<User>("current_user")
+        .ok_or_else(|| Error::from_status(StatusCode::UNAUTHORIZED))
 }
-impl AdditionalProviderMetadata for RevocationEndpointProviderMetadata {}
-type GoogleProviderMetadata = ProviderMetadata<
-    RevocationEndpointProviderMetadata,
-    CoreAuthDisplay,
-    CoreClientAuthMethod,
-    CoreClaimName,
-    CoreClaimType,
-    CoreGrantType,
-    CoreJweContentEncryptionAlgorithm,
-    CoreJweKeyManagementAlgorithm,
-    CoreJsonWebKey,
-    CoreResponseMode,
-    CoreResponseType,
-    CoreSubjectIdentifierType,
->;
-
-const REDIRECT_AFTER_LOGIN_KEY: &str = "redirect_after_login"; 
-const NONCE_KEY: &str = "nonce";
-pub type GoogleClient = Client<
-    EmptyAdditionalClaims,
-    CoreAuthDisplay,
-    CoreGenderClaim,
-    CoreJweContentEncryptionAlgorithm,
-    CoreJsonWebKey,
-    CoreAuthPrompt,
-    StandardErrorResponse<BasicErrorResponseType>,
-    CoreTokenResponse,
-    CoreTokenIntrospectionResponse,
-    StandardRevocableToken,
-    BasicRevocationErrorResponse,
-    EndpointSet,
-    EndpointNotSet,
-    EndpointNotSet,
-    EndpointSet,
-    EndpointMaybeSet,
-    EndpointMaybeSet,
->;
-
-fn handle_error<T: std::error::Error>(fail: &T, msg: &'static str) {
-    let mut err_msg = format!("ERROR: {}", msg);
-    let mut cur_fail: Option<&dyn std::error::Error> = Some(fail);
-    while let Some(cause) = cur_fail {
-        err_msg += &format!("\n    caused by: {}", cause);
-        cur_fail = cause.source();
+
+/// The id of the logged in browser session, if the request comes from one.
+pub(crate) fn current_session_id(session: &Session) -> Option<Uuid> {
+    session.get::<Uuid>(SESSION_ID_KEY)
+}
+
+/// Where a request comes from, as shown on the sessions page.
+async fn client_info(req: &Request) -> SessionClient {
+    let ip_address = RealIp::from_request_without_body(req)
+        .await
+        .ok()
+        .and_then(|RealIp(ip)| ip)
+        .map(|ip| ip.to_string());
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+    SessionClient { ip_address, user_agent }
+}
+
+/// Extracts where a request comes from, for handlers that start sessions.
+pub(crate) struct ClientInfo(SessionClient);
+
+impl<'a> FromRequest<'a> for ClientInfo {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(ClientInfo(client_info(req).await))
     }
-    println!("{}", err_msg);
 }
 
-pub async fn setup_openid_client() -> anyhow::Result<GoogleClient> {
-    let google_client_id = ClientId::new(
-        env::var("GOOGLE_CLIENT_ID").expect("Missing the GOOGLE_CLIENT_ID environment variable."),
-    );
-    let google_client_secret = ClientSecret::new(
-        env::var("GOOGLE_CLIENT_SECRET")
-            .expect("Missing the GOOGLE_CLIENT_SECRET environment variable."),
-    );
-    let issuer_url =
-        IssuerUrl::new("https://accounts.google.com".to_string()).unwrap_or_else(|err| {
-            handle_error(&err, "Invalid issuer URL");
-            unreachable!();
-        });
+/// Logs `user` in and sends them back to the page that asked them to, unless they were
+/// deactivated. `grant` is what the provider handed out when the user logged in through one.
+async fn log_in(
+    state: &AppState,
+    session: &Session,
+    ClientInfo(client): ClientInfo,
+    user: User,
+    grant: Option<ProviderGrant>,
+) -> Result<Redirect, AuthError> {
+    if user.deactivated_at.is_some() {
+        return Err(AuthError::Deactivated);
+    }
+    let user = match &state.config.auth.bootstrap_admin_email {
+        Some(email) => {
+            let role = user.role.clone();
+            let user = MutationCore::promote_bootstrap_admin(&state.conn, user, email).await?;
+            if user.role != role {
+                println!("Made {} the super admin", user.email);
+            }
+            user
+        }
+        None => user,
+    };
+    let user_session = MutationCore::start_user_session(&state.conn, user.id, client, grant, Utc::now()).await?;
+    let redirect_to = session
+        .get::<String>(REDIRECT_AFTER_LOGIN_KEY)
+        .unwrap_or_else(|| "/".to_string());
 
-    let auth_redirect_url = env::var("GOOGLE_REDIRECT_URI").unwrap_or_else(|_| "http://127.0.0.1:8000/auth/callback".to_string());
-    
-    let http_client = get_http_client();
+    // Start over under a new session id, so nothing from before the login carries over
+    // into it and an id planted in the browser beforehand is worthless.
+    session.renew();
+    session.clear();
+    session.set(SESSION_ID_KEY, user_session.id);
+    session.set("current_user", user);
+    Ok(Redirect::see_other(redirect_to))
+}
 
-    let provider_metadata = GoogleProviderMetadata::discover_async(issuer_url, &http_client)
-        .await
-        .unwrap_or_else(|err| {
-            handle_error(&err, "Failed to discover OpenID Provider");
-            unreachable!();
+/// Revokes the provider access tokens of sessions that ended, in the background. Failures
+/// are only logged: the sessions are over either way.
+pub(crate) fn revoke_provider_tokens(providers: &OidcProviders, user_sessions: Vec<user_session::Model>) {
+    for user_session in user_sessions {
+        let (Some(name), Some(access_token)) = (user_session.provider, user_session.provider_access_token) else {
+            continue;
+        };
+        let Some(provider) = providers.get(&name).cloned() else {
+            continue;
+        };
+        tokio::spawn(async move {
+            if let Err(err) = provider.revoke_token(access_token).await {
+                eprintln!("Failed to revoke the {} access token: {err:#}", provider.label);
+            }
         });
+    }
+}
 
-    let revocation_endpoint = provider_metadata
-        .additional_metadata()
-        .revocation_endpoint
-        .clone();
-    println!(
-        "Discovered Google revocation endpoint: {}",
-        revocation_endpoint
-    );
+/// Ends the browser's session and sends it to the login page.
+#[handler]
+async fn logout(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+) -> Result<Redirect> {
+    if let (Ok(user), Some(id)) = (current_user(session), current_session_id(session)) {
+        let ended = MutationCore::end_user_session(&state.conn, id, Some(user.id))
+            .await
+            .map_err(InternalServerError)?;
+        revoke_provider_tokens(&providers, ended.into_iter().collect());
+    }
+    session.purge();
+    Ok(Redirect::see_other("/auth/login"))
+}
 
-    // Set up the config for the Google OAuth2 process.
-    let client: GoogleClient = CoreClient::from_provider_metadata(
-        provider_metadata,
-        google_client_id,
-        Some(google_client_secret),
-    )
-    // This example will be running its own server at localhost:8080.
-    // See below for the server implementation.
-    .set_redirect_uri(
-        RedirectUrl::new(auth_redirect_url).unwrap_or_else(|err| {
-            handle_error(&err, "Invalid redirect URL");
-            unreachable!();
-        }),
-    )
-    // Google supports OAuth 2.0 Token Revocation (RFC-7009)
-    .set_revocation_url(
-        RevocationUrl::new(revocation_endpoint).unwrap_or_else(|err| {
-            handle_error(&err, "Invalid revocation endpoint URL");
-            unreachable!();
-        }),
-    );
-    Ok(client)
+fn find_provider<'a>(providers: &'a OidcProviders, name: &str) -> Result<&'a OidcProvider, AuthError> {
+    providers
+        .get(name)
+        .ok_or_else(|| AuthError::UnknownProvider(name.to_string()))
+}
+
+/// A login button.
+#[derive(Serialize)]
+struct ProviderLink<'a> {
+    name: &'a str,
+    label: &'a str,
 }
 
-fn get_http_client() -> reqwest::Client {
-    reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .unwrap_or_else(|err| {
-            handle_error(&err, "Failed to create HTTP client");
-            unreachable!();
+fn provider_links(providers: &OidcProviders) -> Vec<ProviderLink<'_>> {
+    providers
+        .iter()
+        .map(|provider| ProviderLink {
+            name: &provider.name,
+            label: &provider.label,
         })
+        .collect()
+}
+
+/// Renders the login page, with the local login forms when local accounts are enabled.
+/// `ctx` carries what the forms show besides the providers, e.g. an error.
+fn render_login(
+    state: &AppState,
+    providers: &OidcProviders,
+    mut ctx: tera::Context,
+) -> Result<Html<String>> {
+    ctx.insert("providers", &provider_links(providers));
+    ctx.insert("local_accounts", &state.config.auth.local_accounts);
+    ctx.insert("registration_open", &state.config.auth.registration.is_open());
+
+    let body = state
+        .templates
+        .render("auth/login.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// Lists the ways people can log in.
+#[handler]
+async fn login(state: Data<&AppState>, providers: Data<&OidcProviders>) -> Result<impl IntoResponse> {
+    render_login(&state, &providers, tera::Context::new())
+}
+
+/// What is remembered about a login between sending the browser to the provider and the
+/// provider sending it back. It is taken out of the session by the first callback, so every
+/// login can be finished once.
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    provider: String,
+    csrf_state: String,
+    nonce: String,
+    pkce_verifier: String,
+}
+
+/// What providers send to the callback, see RFC 6749 section 4.1.2.
+#[derive(Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
 }
 
+/// Sends the browser off to the provider's login page.
 #[handler]
-async fn login(session: &Session, auth_client: Data<&GoogleClient>) -> Result<impl IntoResponse> {
-    let (authorize_url, _csrf_state, nonce) = auth_client
-        .0
+async fn start_login(
+    state: Data<&AppState>,
+    session: &Session,
+    providers: Data<&OidcProviders>,
+    Path(name): Path<String>,
+) -> Response {
+    let provider = match find_provider(&providers, &name) {
+        Ok(provider) => provider,
+        Err(err) => return err.render(&state),
+    };
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let mut request = provider
+        .client
         .authorize_url(
             AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
             CsrfToken::new_random,
             Nonce::new_random,
         )
-        .add_scope(Scope::new("email".to_string()))
-        .url();
-    
-    session.set(NONCE_KEY, nonce);
-    
-    // Access-Control-Allow-Origin
-    Ok(StatusCode::FOUND
-        .with_header("HX-Redirect", authorize_url.to_string())
-        .with_header("Access-Control-Allow-Origin", "*"))
+        .set_pkce_challenge(pkce_challenge);
+    for scope in &provider.scopes {
+        request = request.add_scope(Scope::new(scope.clone()));
+    }
+    let (authorize_url, csrf_state, nonce) = request.url();
+
+    session.set(
+        PENDING_LOGIN_KEY,
+        PendingLogin {
+            provider: provider.name.clone(),
+            csrf_state: csrf_state.secret().clone(),
+            nonce: nonce.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+        },
+    );
+
+    Redirect::see_other(authorize_url.to_string()).into_response()
+}
+
+/// Verifies what the provider sent back and finds the user to log in.
+async fn finish_login(
+    state: &AppState,
+    session: &Session,
+    provider: &OidcProvider,
+    params: CallbackParams,
+) -> Result<(User, ProviderGrant), AuthError> {
+    let pending = session
+        .get::<PendingLogin>(PENDING_LOGIN_KEY)
+        .ok_or(AuthError::NoLoginInProgress)?;
+    session.remove(PENDING_LOGIN_KEY);
+
+    if pending.provider != provider.name {
+        return Err(AuthError::ProviderMismatch);
+    }
+    if params.state.as_deref() != Some(pending.csrf_state.as_str()) {
+        return Err(AuthError::StateMismatch);
+    }
+    if let Some(error) = params.error {
+        return Err(AuthError::Provider {
+            provider: provider.label.clone(),
+            message: params.error_description.unwrap_or(error),
+        });
+    }
+    let code = params
+        .code
+        .ok_or_else(|| AuthError::MissingCode(provider.label.clone()))?;
+
+    let token_response = provider
+        .client
+        .exchange_code(AuthorizationCode::new(code))
+        .map_err(|err| AuthError::ProviderUnavailable {
+            provider: provider.label.clone(),
+            message: err.to_string(),
+        })?
+        .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+        .request_async(&http_client())
+        .await
+        .map_err(|err| match err {
+            RequestTokenError::ServerResponse(response) => AuthError::CodeRejected {
+                provider: provider.label.clone(),
+                message: response.to_string(),
+            },
+            err => AuthError::ProviderUnavailable {
+                provider: provider.label.clone(),
+                message: err.to_string(),
+            },
+        })?;
+
+    let id_token_verifier: CoreIdTokenVerifier = provider.client.id_token_verifier();
+    let claims = token_response
+        .extra_fields()
+        .id_token()
+        .ok_or_else(|| AuthError::MissingIdToken(provider.label.clone()))?
+        .claims(&id_token_verifier, &Nonce::new(pending.nonce))
+        .map_err(|err| AuthError::InvalidIdToken {
+            provider: provider.label.clone(),
+            message: err.to_string(),
+        })?;
+
+    let identity = ExternalIdentity {
+        provider: provider.name.clone(),
+        subject: claims.subject().to_string(),
+        email: claims.email().map(|email| email.to_string()),
+        email_verified: claims.email_verified().unwrap_or(false),
+        name: claims
+            .name()
+            .and_then(|name| name.get(None))
+            .map(|name| name.to_string()),
+    };
+    let user = match MutationCore::sign_in_with_identity(&state.conn, &identity, state.config.auth.registration, Utc::now()).await? {
+        IdentitySignIn::SignedIn(user) => user,
+        IdentitySignIn::NoUsableEmail => return Err(AuthError::NoUsableEmail(provider.label.clone())),
+        IdentitySignIn::NotInvited => return Err(AuthError::NotInvited),
+    };
+    let grant = ProviderGrant {
+        provider: provider.name.clone(),
+        access_token: token_response.access_token().secret().clone(),
+    };
+    Ok((user, grant))
+}
+
+/// Finishes the login with provider `name`, rendering the error page if that fails.
+async fn handle_callback(
+    state: &AppState,
+    providers: &OidcProviders,
+    session: &Session,
+    client: ClientInfo,
+    name: &str,
+    params: CallbackParams,
+) -> Response {
+    let result = match find_provider(providers, name) {
+        Ok(provider) => finish_login(state, session, provider, params).await,
+        Err(err) => Err(err),
+    };
+    let result = match result {
+        Ok((user, grant)) => log_in(state, session, client, user, Some(grant)).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(redirect) => redirect.into_response(),
+        Err(err) => err.render(state),
+    }
 }
 
 #[handler]
 async fn auth_callback(
-    auth_client: Data<&GoogleClient>,
-    app_state: Data<&AppState>,
-    query: poem::web::Query<HashMap<String, String>>,
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
     session: &Session,
-) -> Result<Redirect> {
-    let code = query.get("code");
-    if let Some(code) = code {
-        let http_client = get_http_client();
-
-        let token_response = auth_client
-            .0
-            .exchange_code(AuthorizationCode::new(code.to_string()))
-            .unwrap()
-            .request_async(&http_client)
-            .await
-            .unwrap_or_else(|err| {
-                handle_error(&err, "Failed to exchange code for token");
-                unreachable!();
-            });
-        
-        let nonce = session.get::<Nonce>(NONCE_KEY).unwrap();
-
-        let id_token_verifier: CoreIdTokenVerifier = auth_client.id_token_verifier();
-        match token_response
-            .extra_fields()
-            .id_token()
-            .expect("Server did not return an ID token")
-            .claims(
-                &id_token_verifier,
-                &nonce,
-            ) {
-            Ok(id_token_claims) => {
-                if let Some(email) = id_token_claims.email() {
-                    let boffo = QueryCore::find_user_by_email(&app_state.conn, email.as_str())
-                        .await;
-
-                    match boffo {
-                        Ok(Some(user)) => {
-                            session.set("current_user", user);
-                        }
-                        Err(err) => {
-                            eprintln!("Error: {:?}", err);
-                            return Err(NotFoundError.into());
-                        },
-                        Ok(None) => {
-                            let u = UserActiveModel {
-                                id: NotSet,
-                                email: Set(email.to_string()),
-                                name: Set("".to_string()),
-                                role: NotSet,
-                            }
-                                .insert(&app_state.conn)
-                                .await;
-                            if let Ok(u) = u {
-                                session.set("current_user", u);
-                            }
-                        }
-                    }
-                }
-
-                return Ok(Redirect::temporary(session.get(REDIRECT_AFTER_LOGIN_KEY).unwrap_or("/".to_string())));
-            }
-            Err(err) => {
-                println!("Failed to verify ID token: {}", &err);
-            }
-        } 
-    }
-    Err(NotFoundError.into())
+    client: ClientInfo,
+    Path(name): Path<String>,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    handle_callback(&state, &providers, session, client, &name, params).await
+}
+
+#[handler]
+async fn legacy_auth_callback(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    client: ClientInfo,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    handle_callback(&state, &providers, session, client, LEGACY_CALLBACK_PROVIDER, params).await
 }
 
-// A function to define all routes related to posts
 pub fn routes() -> Route {
     Route::new()
         .at("/login", get(login))
-        .at("/callback", get(auth_callback))
+        .at("/logout", post(logout))
+        .at("/callback", get(legacy_auth_callback))
+        .at("/password", post(local::password_login))
+        .at("/register", get(local::register_form).post(local::register))
+        .at("/verify", get(local::verify))
+        .at("/forgot", get(local::forgot_form).post(local::forgot))
+        .at("/reset", get(local::reset_form).post(local::reset))
+        .at("/magic", get(local::magic_login).post(local::send_magic_link))
+        .at("/invitations/:token", get(invitations::accept_form).post(invitations::accept))
+        .at("/:provider/login", get(start_login))
+        .at("/:provider/callback", get(auth_callback))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::issuer::{StubIssuer, StubLogin};
+    use crate::test_support::{body_text, TestApp};
+    use poem::http::{header, StatusCode};
+    use poem::Response;
+    use std::collections::HashMap;
+
+    async fn app_with_issuer() -> (TestApp, StubIssuer) {
+        let issuer = StubIssuer::start().await;
+        let app = TestApp::with_config(|config| {
+            config.auth.providers = vec![issuer.provider_config("stub", &config.server.public_url)];
+        })
+        .await;
+        (app, issuer)
+    }
+
+    fn location(response: &Response) -> String {
+        response.headers()[header::LOCATION].to_str().unwrap().to_string()
+    }
+
+    /// Starts a login and returns what the browser is sent to the provider with.
+    async fn start_login(app: &TestApp) -> HashMap<String, String> {
+        let response = app.get("/auth/stub/login").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let url = location(&response);
+        let (_, query) = url.split_once('?').unwrap();
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    /// Jane logging in at the provider for the login the app started with `authorize`.
+    fn login(authorize: &HashMap<String, String>) -> StubLogin {
+        StubLogin {
+            subject: "jane-1".to_string(),
+            email: "Jane@Example.com".to_string(),
+            email_verified: true,
+            nonce: authorize["nonce"].clone(),
+            code_challenge: authorize["code_challenge"].clone(),
+        }
+    }
+
+    async fn callback(app: &TestApp, query: &[(&str, &str)]) -> Response {
+        let query = serde_urlencoded::to_string(query).unwrap();
+        app.get(&format!("/auth/stub/callback?{query}")).await
+    }
+
+    #[tokio::test]
+    async fn callbacks_log_in_once() {
+        let (app, issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+        let code = issuer.issue_code(login(&authorize));
+
+        let query = [("code", code.as_str()), ("state", authorize["state"].as_str())];
+        let response = callback(&app, &query).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), "/");
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::OK);
+        let user = service::Query::find_user_by_email(&app.state.conn, "jane@example.com")
+            .await
+            .unwrap();
+        assert!(user.is_some(), "the account is stored with the normalized address");
+
+        // Replaying the callback, logged in or from another browser, gets nowhere.
+        let response = callback(&app, &query).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("not in progress anymore"));
+        app.log_out();
+        let response = callback(&app, &query).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn callbacks_with_another_state_are_refused() {
+        let (app, issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+        let code = issuer.issue_code(login(&authorize));
+
+        let response = callback(&app, &[("code", &code), ("state", "forged")]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("could not be verified"));
+
+        // The login is over: not even the right state finishes it now.
+        let response = callback(&app, &[("code", &code), ("state", &authorize["state"])]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn provider_errors_are_shown() {
+        let (app, _issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+
+        let query = [
+            ("error", "access_denied"),
+            ("error_description", "The user said no"),
+            ("state", authorize["state"].as_str()),
+        ];
+        let response = callback(&app, &query).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(body_text(response).await.contains("Stub did not log you in: The user said no"));
+    }
+
+    #[tokio::test]
+    async fn id_tokens_for_another_login_are_refused() {
+        let (app, issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+        let code = issuer.issue_code(StubLogin {
+            nonce: "somebody else's nonce".to_string(),
+            ..login(&authorize)
+        });
+
+        let response = callback(&app, &[("code", &code), ("state", &authorize["state"])]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("The ID token from Stub is invalid"));
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_and_used_codes_are_refused() {
+        let (app, issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+        let response = callback(&app, &[("code", "made-up"), ("state", &authorize["state"])]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("Stub did not accept the login"));
+
+        let authorize = start_login(&app).await;
+        let code = issuer.issue_code(login(&authorize));
+        let response = callback(&app, &[("code", &code), ("state", &authorize["state"])]).await;
+        assert_eq!(location(&response), "/");
+        app.log_out();
+
+        // A new login cannot be finished with the code of the last one.
+        let authorize = start_login(&app).await;
+        let response = callback(&app, &[("code", &code), ("state", &authorize["state"])]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("unknown or was used before"));
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn codes_for_another_code_challenge_are_refused() {
+        let (app, issuer) = app_with_issuer().await;
+        let authorize = start_login(&app).await;
+        // The code was handed out to whoever started another login, e.g. an attacker who
+        // got it from a redirect.
+        let code = issuer.issue_code(StubLogin {
+            code_challenge: "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_string(),
+            ..login(&authorize)
+        });
+
+        let response = callback(&app, &[("code", &code), ("state", &authorize["state"])]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("does not match the code challenge"));
+        assert_eq!(app.get("/tokens/").await.status(), StatusCode::FOUND);
+    }
 }
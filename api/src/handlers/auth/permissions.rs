@@ -0,0 +1,59 @@
+//! Permission checks for handlers, and the `granted` test for templates:
+//!
+//! ```tera
+//! {% if permissions is granted("episode:publish") %}...{% endif %}
+//! ```
+//!
+//! where `permissions` is what [`session_permissions`] or `find_show_permissions` returned.
+
+use super::current_user;
+use crate::AppState;
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::Error;
+use service::permissions::{Permission, Permissions};
+use service::Query as QueryCore;
+use tera::Value;
+
+pub(crate) async fn user_permissions(state: &AppState, user: &User) -> poem::Result<Permissions> {
+    QueryCore::find_user_permissions(&state.conn, user)
+        .await
+        .map_err(InternalServerError)
+}
+
+/// What the logged in user may do everywhere; nothing for anonymous requests.
+pub(crate) async fn session_permissions(state: &AppState, session: &Session) -> poem::Result<Permissions> {
+    match current_user(session) {
+        Ok(user) => user_permissions(state, &user).await,
+        Err(_) => Ok(Permissions::default()),
+    }
+}
+
+/// `403 Forbidden` unless the user's role grants `permission`.
+pub(crate) async fn ensure_permission(state: &AppState, user: &User, permission: Permission) -> poem::Result<()> {
+    if user_permissions(state, user).await?.has(permission) {
+        Ok(())
+    } else {
+        Err(Error::from_string(
+            format!("You lack the {} permission", permission.as_str()),
+            StatusCode::FORBIDDEN,
+        ))
+    }
+}
+
+/// The `granted` test. Names no permission is known by are an error, so a typo in a
+/// template does not quietly hide a button for everybody.
+pub fn granted(value: Option<&Value>, args: &[Value]) -> tera::Result<bool> {
+    let name = args
+        .first()
+        .and_then(Value::as_str)
+        .ok_or_else(|| tera::Error::msg("The granted test needs a permission, e.g. granted(\"post:edit\")"))?;
+    let permission = Permission::parse(name).ok_or_else(|| tera::Error::msg(format!("Unknown permission {name}")))?;
+
+    let granted = value
+        .and_then(Value::as_array)
+        .is_some_and(|permissions| permissions.iter().any(|granted| granted.as_str() == Some(permission.as_str())));
+    Ok(granted)
+}
@@ -0,0 +1,192 @@
+//! The OpenID Connect providers people can log in with.
+//!
+//! Providers are configured per deployment, see `auth.providers` in
+//! `config/default.toml`. Deployments from before provider support keep working: without
+//! `OIDC_PROVIDERS`, the `GOOGLE_CLIENT_ID`, `GOOGLE_CLIENT_SECRET` and
+//! `GOOGLE_REDIRECT_URI` variables set up a single `google` provider.
+
+use openidconnect::core::{
+    CoreAuthDisplay, CoreClaimName, CoreClaimType, CoreClient, CoreClientAuthMethod,
+    CoreGrantType, CoreJsonWebKey, CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm, CoreResponseMode, CoreResponseType, CoreRevocableToken,
+    CoreSubjectIdentifierType,
+};
+use openidconnect::{
+    AccessToken, AdditionalProviderMetadata, ClientId, ClientSecret, EndpointMaybeSet,
+    EndpointNotSet, EndpointSet, IssuerUrl, ProviderMetadata, RedirectUrl, RevocationUrl,
+};
+use serde::{Deserialize, Serialize};
+
+pub type OidcClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+/// Discovery fields beyond OpenID Connect Discovery 1.0 that we use.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RevocationEndpointMetadata {
+    /// RFC 7009 token revocation, see RFC 8414 section 2.
+    revocation_endpoint: Option<String>,
+}
+
+impl AdditionalProviderMetadata for RevocationEndpointMetadata {}
+
+type DiscoveredProviderMetadata = ProviderMetadata<
+    RevocationEndpointMetadata,
+    CoreAuthDisplay,
+    CoreClientAuthMethod,
+    CoreClaimName,
+    CoreClaimType,
+    CoreGrantType,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm,
+    CoreJsonWebKey,
+    CoreResponseMode,
+    CoreResponseType,
+    CoreSubjectIdentifierType,
+>;
+
+pub(crate) const DEFAULT_SCOPES: &str = "email profile";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ProviderKind {
+    Google,
+    Microsoft,
+    GitLab,
+    Keycloak,
+    Generic,
+}
+
+impl ProviderKind {
+    pub(crate) fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "google" => Some(ProviderKind::Google),
+            "microsoft" => Some(ProviderKind::Microsoft),
+            "gitlab" => Some(ProviderKind::GitLab),
+            "keycloak" => Some(ProviderKind::Keycloak),
+            "generic" => Some(ProviderKind::Generic),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn label(self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Google => Some("Google"),
+            ProviderKind::Microsoft => Some("Microsoft"),
+            ProviderKind::GitLab => Some("GitLab"),
+            ProviderKind::Keycloak => Some("Keycloak"),
+            ProviderKind::Generic => None,
+        }
+    }
+}
+
+/// How to reach one provider, as configured.
+#[derive(Clone, Debug)]
+pub struct ProviderConfig {
+    /// Used in URLs and to remember which provider an identity came from.
+    pub name: String,
+    pub label: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+}
+
+/// A provider that is ready to log people in.
+#[derive(Clone)]
+pub struct OidcProvider {
+    pub name: String,
+    pub label: String,
+    pub client: OidcClient,
+    pub scopes: Vec<String>,
+    /// Where access tokens are revoked on logout, if the provider supports that. Only
+    /// HTTPS endpoints are used, so development providers over HTTP keep their tokens.
+    pub revocation_url: Option<RevocationUrl>,
+}
+
+impl OidcProvider {
+    /// Tells the provider an access token it handed out is not needed anymore.
+    pub async fn revoke_token(&self, access_token: String) -> anyhow::Result<()> {
+        let Some(revocation_url) = self.revocation_url.clone() else {
+            return Ok(());
+        };
+        self.client
+            .clone()
+            .set_revocation_url(revocation_url)
+            .revoke_token(CoreRevocableToken::AccessToken(AccessToken::new(access_token)))?
+            .request_async(&http_client())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Every provider that could be set up, in configuration order.
+#[derive(Clone, Default)]
+pub struct OidcProviders {
+    providers: Vec<OidcProvider>,
+}
+
+impl OidcProviders {
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OidcProvider> {
+        self.providers.iter()
+    }
+
+    /// Discovers every configured provider. Providers that cannot be reached are logged
+    /// and left out, so one broken provider does not lock everybody out.
+    pub async fn discover(configs: Vec<ProviderConfig>) -> Self {
+        let http_client = http_client();
+        let mut providers = Vec::new();
+        for config in configs {
+            match discover_provider(&config, &http_client).await {
+                Ok(provider) => {
+                    println!("Enabled login with {} ({})", provider.label, config.issuer);
+                    providers.push(provider);
+                }
+                Err(err) => eprintln!("Failed to set up login with {}: {err:#}", config.name),
+            }
+        }
+        Self { providers }
+    }
+}
+
+async fn discover_provider(config: &ProviderConfig, http_client: &reqwest::Client) -> anyhow::Result<OidcProvider> {
+    let issuer_url = IssuerUrl::new(config.issuer.clone())?;
+    let metadata = DiscoveredProviderMetadata::discover_async(issuer_url, http_client).await?;
+    let revocation_url = metadata
+        .additional_metadata()
+        .revocation_endpoint
+        .clone()
+        .map(RevocationUrl::new)
+        .transpose()?;
+    let client = CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(config.client_id.clone()),
+        Some(ClientSecret::new(config.client_secret.clone())),
+    )
+    .set_redirect_uri(RedirectUrl::new(config.redirect_uri.clone())?);
+
+    Ok(OidcProvider {
+        name: config.name.clone(),
+        label: config.label.clone(),
+        client,
+        scopes: config.scopes.clone(),
+        revocation_url,
+    })
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::ClientBuilder::new()
+        // Following redirects would open the door to SSRF.
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to create HTTP client")
+}
@@ -0,0 +1,61 @@
+use poem::{Endpoint, IntoResponse, Middleware, Result, Response};
+use poem::error::{InternalServerError, NotFoundError};
+use poem::Request;
+use poem::session::Session;
+use poem::web::Redirect;
+use entities::user::Model as User;
+use service::permissions::Permission;
+use service::Query as QueryCore;
+use crate::handlers::auth::REDIRECT_AFTER_LOGIN_KEY;
+use crate::AppState;
+
+pub struct RequirePermission {
+    permission: Permission,
+}
+
+impl RequirePermission {
+    pub fn new(permission: Permission) -> Self {
+        Self { permission }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequirePermission {
+    type Output = RequirePermissionImpl<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequirePermissionImpl { permission: self.permission, ep }
+    }
+}
+
+/// The new endpoint type generated by the RequirePermission middleware.
+pub struct RequirePermissionImpl<E> {
+    permission: Permission,
+    ep: E,
+}
+
+impl<E: Endpoint> Endpoint for RequirePermissionImpl<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let session = req.extensions().get::<Session>();
+
+        if let Some(session) = session {
+            // Check if user is logged in and their role grants the permission
+            if let Some(user) = session.get::<User>("current_user") {
+                let state = req.data::<AppState>().ok_or(NotFoundError)?;
+                let permissions = QueryCore::find_user_permissions(&state.conn, &user)
+                    .await
+                    .map_err(InternalServerError)?;
+                return if permissions.has(self.permission) {
+                    self.ep.call(req).await.map(IntoResponse::into_response)
+                } else {
+                    Err(NotFoundError.into())
+                }
+            } else {
+                session.set(REDIRECT_AFTER_LOGIN_KEY, req.uri().path().to_string());
+            }
+        }
+
+        Ok(Redirect::temporary("/auth/login").into_response())
+    }
+}
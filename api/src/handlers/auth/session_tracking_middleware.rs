@@ -0,0 +1,57 @@
+use crate::handlers::auth::{client_info, SESSION_ID_KEY};
+use crate::AppState;
+use chrono::{Duration, Utc};
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::{Endpoint, Error, IntoResponse, Request, Response};
+use sea_orm::prelude::Uuid;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+/// How stale `last_seen_at` may get before it is written again.
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+/// Keeps browser sessions in line with their `user_session` rows.
+///
+/// A login only lasts as long as its row: once the row is gone, because the user or an
+/// admin ended the session or it was idle for too long, the browser is logged out on its
+/// next request. So is the browser of a user who was deactivated. The user is reloaded
+/// along with the row, so changes such as a new role take effect right away. Requests
+/// logged in with an API token pass untouched.
+pub async fn session_tracking_middleware<E: Endpoint>(next: E, req: Request) -> poem::Result<Response> {
+    let Some(session) = req.extensions().get::<Session>().cloned() else {
+        return next.call(req).await.map(IntoResponse::into_response);
+    };
+    let Some(current_user) = session.get::<User>("current_user") else {
+        return next.call(req).await.map(IntoResponse::into_response);
+    };
+    let state = req
+        .data::<AppState>()
+        .ok_or_else(|| Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let now = Utc::now();
+
+    let user_session = match session.get::<Uuid>(SESSION_ID_KEY) {
+        Some(id) => QueryCore::find_active_user_session(&state.conn, id, now)
+            .await
+            .map_err(InternalServerError)?,
+        None => None,
+    };
+    match user_session {
+        Some((user_session, user)) if user.deactivated_at.is_none() => {
+            // The hash is never stored in the session, so it must not count as a change.
+            let user = User { password_hash: None, ..user };
+            if user != current_user {
+                session.set("current_user", user);
+            }
+            if now - user_session.last_seen_at > Duration::seconds(LAST_SEEN_RESOLUTION_SECONDS) {
+                MutationCore::touch_user_session(&state.conn, user_session.id, client_info(&req).await, now)
+                    .await
+                    .map_err(InternalServerError)?;
+            }
+        }
+        _ => session.purge(),
+    }
+
+    next.call(req).await.map(IntoResponse::into_response)
+}
@@ -0,0 +1,203 @@
+use crate::handlers::auth::current_user;
+use crate::handlers::episodes::find_accessible_episode;
+use crate::AppState;
+use entities::chapter;
+use entities::episode;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use poem::error::InternalServerError;
+use poem::http::{header, StatusCode};
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path};
+use poem::{handler, Error, IntoResponse};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use service::chapters::{
+    format_timestamp, parse_timestamp, render_chapters_json, ImportedChapter, CHAPTERS_MIME_TYPE,
+};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Deserialize)]
+pub struct ChapterForm {
+    /// `HH:MM:SS`, `MM:SS` or seconds.
+    start: String,
+    title: String,
+    url: Option<String>,
+    image_url: Option<String>,
+}
+
+impl ChapterForm {
+    #[allow(clippy::result_large_err)]
+    fn into_model(self) -> poem::Result<chapter::Model> {
+        let start_ms = parse_timestamp(&self.start).ok_or_else(|| {
+            Error::from_string(
+                format!("{} is not a valid start time", self.start),
+                StatusCode::BAD_REQUEST,
+            )
+        })?;
+        let optional = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Ok(chapter::Model {
+            id: Uuid::nil(),
+            episode_id: Uuid::nil(),
+            start_ms,
+            title: self.title.trim().to_string(),
+            url: optional(self.url),
+            image_url: optional(self.image_url),
+        })
+    }
+}
+
+/// A chapter along with its start time as shown in the edit form.
+#[derive(Serialize)]
+pub(crate) struct ChapterRow {
+    #[serde(flatten)]
+    chapter: chapter::Model,
+    start: String,
+}
+
+pub(crate) fn chapter_rows(chapters: Vec<chapter::Model>) -> Vec<ChapterRow> {
+    chapters
+        .into_iter()
+        .map(|chapter| ChapterRow {
+            start: format_timestamp(chapter.start_ms),
+            chapter,
+        })
+        .collect()
+}
+
+/// Chapters that were embedded in the episode's audio file, if any.
+#[allow(clippy::result_large_err)]
+pub(crate) fn embedded_chapters(episode: &episode::Model) -> poem::Result<Vec<ImportedChapter>> {
+    match episode.embedded_chapters.as_deref() {
+        Some(json) => serde_json::from_str(json).map_err(InternalServerError),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Re-renders the chapter list of an episode's edit screen.
+async fn render_chapters(state: &AppState, episode: &episode::Model) -> poem::Result<Html<String>> {
+    let chapters = QueryCore::find_chapters_by_episode(&state.conn, episode.id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episode", episode);
+    ctx.insert("chapters", &chapter_rows(chapters));
+
+    let body = state
+        .templates
+        .render("episodes/chapters.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// Makes sure the chapter exists and belongs to the episode in the URL.
+async fn ensure_episode_chapter(state: &AppState, episode_id: Uuid, chapter_id: Uuid) -> poem::Result<()> {
+    let chapters = QueryCore::find_chapters_by_episode(&state.conn, episode_id)
+        .await
+        .map_err(InternalServerError)?;
+    if chapters.iter().any(|chapter| chapter.id == chapter_id) {
+        Ok(())
+    } else {
+        Err(Error::from_status(StatusCode::NOT_FOUND))
+    }
+}
+
+/// The chapters of a published episode as a Podcasting 2.0 JSON chapters document.
+#[handler]
+pub async fn chapters_json(state: Data<&AppState>, Path(id): Path<Uuid>) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    let episode = QueryCore::find_episode_by_id(conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .filter(|episode| episode.status == EpisodeStatus::Published)
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    let chapters = QueryCore::find_chapters_by_episode(conn, episode.id)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(render_chapters_json(&chapters)
+        .with_header(header::CONTENT_TYPE, CHAPTERS_MIME_TYPE)
+        // Web players fetch chapters from other origins.
+        .with_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+}
+
+#[handler]
+pub async fn create(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+    form: Form<ChapterForm>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+
+    MutationCore::create_chapter(&state.conn, id, form.0.into_model()?)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_chapters(&state, &episode).await
+}
+
+#[handler]
+pub async fn update(
+    state: Data<&AppState>,
+    session: &Session,
+    Path((id, chapter_id)): Path<(Uuid, Uuid)>,
+    form: Form<ChapterForm>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+    ensure_episode_chapter(&state, id, chapter_id).await?;
+
+    MutationCore::update_chapter_by_id(&state.conn, chapter_id, form.0.into_model()?)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_chapters(&state, &episode).await
+}
+
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    session: &Session,
+    Path((id, chapter_id)): Path<(Uuid, Uuid)>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+    ensure_episode_chapter(&state, id, chapter_id).await?;
+
+    MutationCore::delete_chapter(&state.conn, chapter_id)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_chapters(&state, &episode).await
+}
+
+/// Replaces the episode's chapters with the ones embedded in its audio file.
+#[handler]
+pub async fn import(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+
+    let chapters = embedded_chapters(&episode)?;
+    if chapters.is_empty() {
+        return Err(Error::from_string(
+            "The audio file has no chapters",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    MutationCore::replace_chapters(&state.conn, id, &chapters)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_chapters(&state, &episode).await
+}
@@ -0,0 +1,509 @@
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::permissions::user_permissions;
+use crate::handlers::auth::login_required_middleware::login_required_middleware;
+use crate::handlers::chapters::{self, chapter_rows};
+use crate::handlers::transcripts;
+use crate::{AppState, PaginationParams, DEFAULT_ITEMS_PER_PAGE};
+use entities::user::Model as User;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{episode, episode::Model as Episode};
+use poem::error::{BadRequest, InternalServerError};
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Multipart, Path, Query};
+use poem::{get, handler, patch, post, EndpointExt, Error, IntoResponse, Route};
+use serde::Deserialize;
+use sea_orm::prelude::Uuid;
+use sea_orm::DatabaseConnection;
+use service::feed::mime_type_for;
+use media::probe::{probe, AudioMetadata, EmbeddedImage};
+use media::MediaStore;
+use service::chapters::ImportedChapter;
+use service::EpisodeMedia;
+use service::permissions::{Permission, Permissions};
+use service::publishing::can_transition;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Deserialize, Default)]
+pub struct ShowFilter {
+    show_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct StatusForm {
+    status: EpisodeStatus,
+    /// As sent by a `datetime-local` input, interpreted as UTC.
+    scheduled_for: Option<String>,
+}
+
+/// What the user may do with the show. Episodes without a show predate shows, so only
+/// what the user may do everywhere counts for them.
+pub(crate) async fn show_permissions(state: &AppState, user: &User, show_id: Option<Uuid>) -> poem::Result<Permissions> {
+    match show_id {
+        Some(show_id) => QueryCore::find_show_permissions(&state.conn, user, show_id)
+            .await
+            .map_err(InternalServerError),
+        None => user_permissions(state, user).await,
+    }
+}
+
+pub(crate) async fn ensure_show_permission(
+    state: &AppState,
+    user: &User,
+    show_id: Option<Uuid>,
+    permission: Permission,
+) -> poem::Result<()> {
+    if show_permissions(state, user, show_id).await?.has(permission) {
+        Ok(())
+    } else {
+        Err(Error::from_status(StatusCode::FORBIDDEN))
+    }
+}
+
+/// Loads an episode, making sure the user may edit the episodes of the show it belongs to.
+pub(crate) async fn find_accessible_episode(state: &AppState, user: &User, id: Uuid) -> poem::Result<episode::Model> {
+    let episode = QueryCore::find_episode_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    ensure_show_permission(state, user, episode.show_id, Permission::EpisodeEdit).await?;
+    Ok(episode)
+}
+
+/// An audio file that came along with an episode form, already probed.
+pub(crate) struct AudioUpload {
+    pub(crate) file_name: String,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) metadata: AudioMetadata,
+}
+
+/// Splits a multipart episode form into the episode fields and the optional `audio` file.
+async fn read_episode_form(mut multipart: Multipart) -> poem::Result<(Episode, Option<AudioUpload>)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut upload = None;
+
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "audio" {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let mime_type = field
+                .content_type()
+                .filter(|mime_type| mime_type.starts_with("audio/"))
+                .unwrap_or_else(|| mime_type_for(&file_name))
+                .to_string();
+            let bytes = field.bytes().await?;
+            if bytes.is_empty() {
+                continue;
+            }
+            if !mime_type.starts_with("audio/") {
+                return Err(Error::from_string(
+                    format!("{file_name} is not an audio file"),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+            let metadata = probe(&bytes).map_err(|err| {
+                Error::from_string(
+                    format!("{file_name} is not a valid audio file: {err}"),
+                    StatusCode::BAD_REQUEST,
+                )
+            })?;
+            upload = Some(AudioUpload { file_name, bytes, metadata });
+        } else {
+            fields.push((name, field.text().await?));
+        }
+    }
+
+    let encoded = serde_urlencoded::to_string(&fields).map_err(BadRequest)?;
+    let episode = serde_urlencoded::from_str(&encoded).map_err(BadRequest)?;
+    Ok((episode, upload))
+}
+
+/// The media store key behind `url`, if it is a file this app stored for the episode.
+fn stored_key(state: &AppState, id: Uuid, url: &str) -> Option<String> {
+    let key = url.strip_prefix(&state.media.public_url(""))?;
+    key.starts_with(&format!("episodes/{id}/")).then(|| key.to_string())
+}
+
+/// Stores an image pulled out of an audio file under `name` plus an extension.
+async fn store_image(media: &dyn MediaStore, id: Uuid, name: &str, image: &EmbeddedImage) -> poem::Result<String> {
+    let extension = if image.mime_type == "image/png" { "png" } else { "jpg" };
+    let key = media::episode_media_key(&id.to_string(), &format!("{name}.{extension}"));
+    let stored = media
+        .put(&key, &image.data, &image.mime_type)
+        .await
+        .map_err(InternalServerError)?;
+    Ok(media.public_url(&stored.key))
+}
+
+/// Puts an uploaded file, and any images embedded in it, into the media store and
+/// records them on the episode together with the probed metadata.
+pub(crate) async fn store_audio(
+    conn: &DatabaseConnection,
+    media: &dyn MediaStore,
+    id: Uuid,
+    upload: AudioUpload,
+) -> poem::Result<episode::Model> {
+    let AudioUpload { file_name, bytes, metadata } = upload;
+    let key = media::episode_media_key(&id.to_string(), &file_name);
+    let stored = media
+        .put(&key, &bytes, metadata.mime_type)
+        .await
+        .map_err(InternalServerError)?;
+
+    let artwork_url = match &metadata.artwork {
+        Some(artwork) => Some(store_image(media, id, "artwork", artwork).await?),
+        None => None,
+    };
+    let mut chapters = Vec::with_capacity(metadata.chapters.len());
+    for (i, chapter) in metadata.chapters.iter().enumerate() {
+        let image_url = match &chapter.image {
+            Some(image) => Some(store_image(media, id, &format!("chapter-{}", i + 1), image).await?),
+            None => None,
+        };
+        chapters.push(ImportedChapter {
+            start_ms: chapter.start_ms.min(i64::MAX as u64) as i64,
+            title: chapter.title.clone(),
+            url: chapter.url.clone(),
+            image_url,
+        });
+    }
+    let embedded_chapters = if chapters.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&chapters).map_err(InternalServerError)?)
+    };
+
+    let episode_media = EpisodeMedia {
+        url: media.public_url(&stored.key),
+        key: stored.key,
+        size: stored.size as i64,
+        mime_type: stored.mime_type,
+        duration_ms: metadata.duration_ms.min(i64::MAX as u64) as i64,
+        bitrate: metadata.bitrate.min(i32::MAX as u32) as i32,
+        sample_rate: metadata.sample_rate.min(i32::MAX as u32) as i32,
+        channels: metadata.channels as i16,
+        codec: metadata.codec,
+        artwork_url,
+        embedded_chapters,
+    };
+    let episode = MutationCore::attach_episode_media(conn, id, episode_media)
+        .await
+        .map_err(InternalServerError)?;
+
+    // Chapters from the file are only a starting point; never overwrite edited ones.
+    let existing = QueryCore::find_chapters_by_episode(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    if existing.is_empty() && !chapters.is_empty() {
+        MutationCore::replace_chapters(conn, id, &chapters)
+            .await
+            .map_err(InternalServerError)?;
+    }
+    Ok(episode)
+}
+
+#[handler]
+pub async fn create(
+    state: Data<&AppState>,
+    session: &Session,
+    multipart: Multipart,
+) -> poem::Result<impl IntoResponse> {
+    let (mut form, upload) = read_episode_form(multipart).await?;
+    let conn = &state.conn;
+
+    // The owner of an episode is whoever is logged in, never whatever the form claims.
+    let current_user = current_user(session)?;
+    if form.show_id.is_none() {
+        return Err(Error::from_string("An episode needs a show", StatusCode::BAD_REQUEST));
+    }
+    ensure_show_permission(&state, &current_user, form.show_id, Permission::EpisodeEdit).await?;
+    form.user_id = current_user.id;
+
+    let episode = MutationCore::create_episode(conn, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    if let Some(upload) = upload {
+        store_audio(&state.conn, state.media.as_ref(), episode.id, upload).await?;
+    }
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/episodes"))
+}
+
+#[handler]
+pub async fn list(
+    state: Data<&AppState>,
+    session: &Session,
+    Query(params): Query<PaginationParams>,
+    Query(filter): Query<ShowFilter>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let user = current_user(session)?;
+    let page = params.page.unwrap_or(1);
+    let episodes_per_page = params.items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+
+    let shows = QueryCore::find_shows_for_user(conn, &user)
+        .await
+        .map_err(InternalServerError)?;
+    let show_ids: Option<Vec<Uuid>> = match filter.show_id {
+        Some(show_id) => {
+            ensure_show_permission(&state, &user, Some(show_id), Permission::EpisodeEdit).await?;
+            Some(vec![show_id])
+        }
+        None if user_permissions(&state, &user).await?.has(Permission::EpisodeEdit) => None,
+        None => Some(shows.iter().map(|show| show.id).collect()),
+    };
+
+    let (episodes, num_pages) =
+        QueryCore::find_episodes(conn, show_ids.as_deref(), page, episodes_per_page)
+            .await
+            .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episodes", &episodes);
+    ctx.insert("shows", &shows);
+    ctx.insert("show_id", &filter.show_id);
+    ctx.insert("page", &page);
+    ctx.insert("episodes_per_page", &episodes_per_page);
+    ctx.insert("num_pages", &num_pages);
+
+    let body = state
+        .templates
+        .render("episodes/list.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn new(
+    state: Data<&AppState>,
+    session: &Session,
+    Query(filter): Query<ShowFilter>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let shows = QueryCore::find_shows_for_user(&state.conn, &user)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("shows", &shows);
+    ctx.insert("show_id", &filter.show_id);
+    let body = state
+        .templates
+        .render("episodes/new.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn edit(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let user = current_user(session)?;
+
+    let episode = find_accessible_episode(&state, &user, id).await?;
+    let shows = QueryCore::find_shows_for_user(conn, &user)
+        .await
+        .map_err(InternalServerError)?;
+
+    let chapters = QueryCore::find_chapters_by_episode(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    let transcripts = QueryCore::find_transcripts_by_episode(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episode", &episode);
+    ctx.insert("shows", &shows);
+    ctx.insert("chapters", &chapter_rows(chapters));
+    ctx.insert("transcripts", &transcripts);
+    ctx.insert("permissions", &show_permissions(&state, &user, episode.show_id).await?);
+
+    let body = state
+        .templates
+        .render("episodes/edit.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn update(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+    multipart: Multipart,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let user = current_user(session)?;
+    let (form, upload) = read_episode_form(multipart).await?;
+
+    find_accessible_episode(&state, &user, id).await?;
+    if form.show_id.is_none() {
+        return Err(Error::from_string("An episode needs a show", StatusCode::BAD_REQUEST));
+    }
+    ensure_show_permission(&state, &user, form.show_id, Permission::EpisodeEdit).await?;
+
+    let mut episode = MutationCore::update_episode_by_id(conn, id, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    if let Some(upload) = upload {
+        let previous_key = episode.media_key.clone();
+        let previous_artwork = episode.artwork_url.as_deref().and_then(|url| stored_key(&state, id, url));
+        episode = store_audio(&state.conn, state.media.as_ref(), id, upload).await?;
+        let current_artwork = episode.artwork_url.as_deref().and_then(|url| stored_key(&state, id, url));
+        let replaced = [
+            previous_key.filter(|key| Some(key) != episode.media_key.as_ref()),
+            previous_artwork.filter(|key| Some(key) != current_artwork.as_ref()),
+        ];
+        for previous_key in replaced.into_iter().flatten() {
+            if let Err(err) = state.media.delete(&previous_key).await {
+                eprintln!("Failed to delete replaced media {previous_key}: {err}");
+            }
+        }
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episode", &episode);
+
+    let body = state
+        .templates
+        .render("episodes/episode_row.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// Moves an episode to `status`, refusing transitions the workflow does not allow.
+pub(crate) async fn transition_episode(
+    state: &AppState,
+    episode: &episode::Model,
+    status: EpisodeStatus,
+    scheduled_for: Option<DateTime<Utc>>,
+) -> poem::Result<episode::Model> {
+    if !can_transition(episode.status, status) {
+        return Err(Error::from_string(
+            format!("An episode cannot go from {:?} to {:?}", episode.status, status),
+            StatusCode::CONFLICT,
+        ));
+    }
+    let now = Utc::now();
+    if status == EpisodeStatus::Scheduled && scheduled_for.is_none_or(|at| at <= now) {
+        return Err(Error::from_string(
+            "Pick a time in the future to schedule the episode",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    MutationCore::change_episode_status(&state.conn, episode.id, status, scheduled_for, now)
+        .await
+        .map_err(InternalServerError)
+}
+
+#[handler]
+pub async fn change_status(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+    form: Form<StatusForm>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let form = form.0;
+
+    let episode = find_accessible_episode(&state, &user, id).await?;
+    ensure_show_permission(&state, &user, episode.show_id, Permission::EpisodePublish).await?;
+    let scheduled_for = match form.scheduled_for.as_deref().filter(|value| !value.is_empty()) {
+        Some(value) => Some(
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+                .map_err(BadRequest)?
+                .and_utc(),
+        ),
+        None => None,
+    };
+    let episode = transition_episode(&state, &episode, form.status, scheduled_for).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episode", &episode);
+
+    let body = state
+        .templates
+        .render("episodes/episode_row.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// Deletes an episode along with the audio and images stored for it.
+pub(crate) async fn delete_episode_and_media(state: &AppState, episode: episode::Model) -> poem::Result<()> {
+    let id = episode.id;
+    let chapters = QueryCore::find_chapters_by_episode(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    MutationCore::delete_episode(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let images = std::iter::once(&episode.artwork_url)
+        .chain(chapters.iter().map(|chapter| &chapter.image_url))
+        .filter_map(|url| stored_key(state, id, url.as_deref()?));
+    let media_keys: Vec<String> = episode.media_key.into_iter().chain(images).collect();
+    for media_key in media_keys {
+        if let Err(err) = state.media.delete(&media_key).await {
+            eprintln!("Failed to delete media {media_key}: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+
+    let episode = find_accessible_episode(&state, &user, id).await?;
+    delete_episode_and_media(&state, episode).await?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/episodes"))
+}
+
+// Access to individual episodes is decided per show inside the handlers.
+pub fn episode_routes() -> Route {
+    Route::new()
+        .at("/", get(list).around(login_required_middleware))
+        .at("/create", post(create).around(login_required_middleware))
+        .at("/new", get(new).around(login_required_middleware))
+        .at(
+            "/:id",
+            get(edit)
+                .patch(update)
+                .delete(destroy)
+                .around(login_required_middleware),
+        )
+        .at("/:id/status", post(change_status).around(login_required_middleware))
+        .at("/:id/chapters.json", get(chapters::chapters_json))
+        .at("/:id/chapters", post(chapters::create).around(login_required_middleware))
+        .at(
+            "/:id/chapters/import",
+            post(chapters::import).around(login_required_middleware),
+        )
+        .at(
+            "/:id/chapters/:chapter_id",
+            patch(chapters::update)
+                .delete(chapters::destroy)
+                .around(login_required_middleware),
+        )
+        .at("/:id/transcripts", post(transcripts::upload).around(login_required_middleware))
+        // Downloads are public, deleting takes the transcript's id instead of a file name.
+        .at(
+            "/:id/transcripts/:file",
+            get(transcripts::download)
+                .delete(transcripts::destroy.around(login_required_middleware)),
+        )
+}
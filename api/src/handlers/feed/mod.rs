@@ -0,0 +1,119 @@
+use crate::config::Config;
+use crate::handlers::public::episode_path;
+use crate::AppState;
+use entities::episode;
+use poem::error::InternalServerError;
+use poem::http::header;
+use poem::http::StatusCode;
+use poem::web::{Data, Path};
+use poem::{handler, Error, IntoResponse};
+use sea_orm::prelude::Uuid;
+use sea_orm::{DatabaseConnection, DbErr};
+use service::feed::{render_feed, FeedChannel, FeedItem, FeedTranscript};
+use service::transcripts::TranscriptFormat;
+use service::Query as QueryCore;
+
+/// Transcript formats advertised in feeds; apps pick the one they understand.
+const FEED_TRANSCRIPT_FORMATS: [TranscriptFormat; 3] =
+    [TranscriptFormat::Vtt, TranscriptFormat::Srt, TranscriptFormat::Json];
+
+/// Channel metadata for the site wide feed, taken from the `[podcast]` settings.
+fn feed_channel(config: &Config) -> FeedChannel {
+    let podcast = &config.podcast;
+    let public_url = &config.server.public_url;
+
+    FeedChannel {
+        title: podcast.title.clone(),
+        feed_url: format!("{public_url}/feed.xml"),
+        link: public_url.clone(),
+        description: podcast.description.clone(),
+        language: podcast.language.clone(),
+        owner_name: podcast.author.clone(),
+        author: podcast.author.clone(),
+        owner_email: podcast.owner_email.clone(),
+        image_url: podcast.image_url.clone(),
+        categories: podcast.categories.clone(),
+        explicit: podcast.explicit,
+    }
+}
+
+/// Feed items for `episodes`, pointing at their chapters and transcripts where they have any.
+async fn feed_items(
+    conn: &DatabaseConnection,
+    public_url: &str,
+    episodes: &[episode::Model],
+) -> Result<Vec<FeedItem>, DbErr> {
+    let ids: Vec<Uuid> = episodes.iter().map(|episode| episode.id).collect();
+    let with_chapters = QueryCore::find_episode_ids_with_chapters(conn, &ids).await?;
+    let transcripts = QueryCore::find_transcripts_by_episodes(conn, &ids).await?;
+
+    Ok(episodes
+        .iter()
+        .map(|episode| {
+            let episode_url = format!("{public_url}/episodes/{}", episode.id);
+            FeedItem {
+                chapters_url: with_chapters
+                    .contains(&episode.id)
+                    .then(|| format!("{episode_url}/chapters.json")),
+                link: episode
+                    .show_id
+                    .map(|show_id| format!("{public_url}{}", episode_path(show_id, episode.id))),
+                transcripts: transcripts
+                    .iter()
+                    .filter(|transcript| transcript.episode_id == episode.id)
+                    .flat_map(|transcript| {
+                        FEED_TRANSCRIPT_FORMATS.iter().map(|format| FeedTranscript {
+                            url: format!(
+                                "{episode_url}/transcripts/{}.{}",
+                                transcript.language,
+                                format.extension()
+                            ),
+                            mime_type: format.mime_type().to_string(),
+                            language: transcript.language.clone(),
+                        })
+                    })
+                    .collect(),
+                ..FeedItem::from(episode)
+            }
+        })
+        .collect())
+}
+
+/// The RSS feed of every published episode.
+pub async fn render_site_feed(conn: &DatabaseConnection, config: &Config) -> Result<String, DbErr> {
+    let episodes = QueryCore::find_published_episodes(conn).await?;
+    let items = feed_items(conn, &config.server.public_url, &episodes).await?;
+    Ok(render_feed(&feed_channel(config), &items))
+}
+
+/// The RSS feed of a show, or `None` if there is no such show.
+pub async fn render_show_feed(
+    conn: &DatabaseConnection,
+    config: &Config,
+    id: Uuid,
+) -> Result<Option<String>, DbErr> {
+    let Some(show) = QueryCore::find_show_by_id(conn, id).await? else {
+        return Ok(None);
+    };
+    let episodes = QueryCore::find_published_episodes_by_show(conn, id).await?;
+    let public_url = &config.server.public_url;
+    let items = feed_items(conn, public_url, &episodes).await?;
+    Ok(Some(render_feed(&FeedChannel::for_show(&show, public_url), &items)))
+}
+
+#[handler]
+pub async fn feed(state: Data<&AppState>) -> poem::Result<impl IntoResponse> {
+    let body = render_site_feed(&state.conn, &state.config)
+        .await
+        .map_err(InternalServerError)?;
+    Ok(body.with_header(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8"))
+}
+
+#[handler]
+pub async fn show_feed(state: Data<&AppState>, Path(id): Path<Uuid>) -> poem::Result<impl IntoResponse> {
+    let body = render_show_feed(&state.conn, &state.config, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    Ok(body.with_header(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8"))
+}
@@ -0,0 +1,327 @@
+//! Moving a podcast over from another host by uploading its RSS feed. The upload is only
+//! previewed; the preview carries the feed along to the form that commits the import.
+
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
+use crate::handlers::episodes::{store_audio, AudioUpload};
+use crate::AppState;
+use entities::user::Model as User;
+use media::probe::probe;
+use media::MediaStore;
+use poem::error::{BadRequest, InternalServerError};
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Multipart};
+use poem::{get, handler, post, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use service::feed_import::{parse_feed, FeedImportPlan};
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long fetching a single enclosure may take.
+const DOWNLOAD_TIMEOUT_SECONDS: u64 = 10 * 60;
+
+/// Copies the audio files of imported episodes from their old host into the media store.
+#[derive(Clone)]
+pub struct EnclosureDownloader {
+    conn: DatabaseConnection,
+    media: Arc<dyn MediaStore>,
+    http: reqwest::Client,
+    max_bytes: u64,
+}
+
+impl EnclosureDownloader {
+    /// Downloads are given up once they get larger than `max_bytes`, see
+    /// `imports.max_download_mb`.
+    pub fn new(conn: DatabaseConnection, media: Arc<dyn MediaStore>, max_bytes: u64) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECONDS))
+            .build()
+            .expect("the HTTP client has a valid configuration");
+        Self { conn, media, http, max_bytes }
+    }
+
+    /// Downloads `url` and attaches it to the episode like an uploaded file. The episode
+    /// keeps pointing at `url` if that fails.
+    pub async fn download(&self, episode_id: Uuid, url: &str) -> Result<(), String> {
+        // Feeds are uploaded by users, so their URLs must not reach anything but web servers.
+        let parsed = reqwest::Url::parse(url).map_err(|err| format!("{url} is not a URL: {err}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("{url} is not an http:// or https:// URL"));
+        }
+        let mut response = self
+            .http
+            .get(parsed)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| err.to_string())?;
+
+        // Read as it comes in, so a lying or missing Content-Length cannot fill the memory.
+        let too_large = || format!("{url} is larger than the {} bytes allowed", self.max_bytes);
+        if response.content_length().is_some_and(|length| length > self.max_bytes) {
+            return Err(too_large());
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+            if (bytes.len() + chunk.len()) as u64 > self.max_bytes {
+                return Err(too_large());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let metadata = probe(&bytes).map_err(|err| format!("{url} is not a supported audio file: {err}"))?;
+        let file_name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .unwrap_or_default()
+            .to_string();
+
+        let upload = AudioUpload {
+            file_name,
+            bytes,
+            metadata,
+        };
+        store_audio(&self.conn, self.media.as_ref(), episode_id, upload)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    /// Downloads every enclosure one after the other, logging failures.
+    pub async fn download_all(&self, episodes: Vec<(Uuid, String)>) {
+        let count = episodes.len();
+        let mut failed = 0;
+        for (episode_id, url) in episodes {
+            if let Err(err) = self.download(episode_id, &url).await {
+                failed += 1;
+                eprintln!("Failed to copy the audio of episode {episode_id}: {err}");
+            }
+        }
+        println!("Copied {} of {count} imported audio files", count - failed);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportForm {
+    feed: String,
+    show_id: Option<String>,
+    download_media: Option<String>,
+}
+
+/// The show to import into, from the value of the show select. Empty means a new show.
+fn parse_show_id(show_id: Option<&str>) -> poem::Result<Option<Uuid>> {
+    match show_id.map(str::trim).filter(|show_id| !show_id.is_empty()) {
+        Some(show_id) => Ok(Some(Uuid::parse_str(show_id).map_err(BadRequest)?)),
+        None => Ok(None),
+    }
+}
+
+/// Parses `feed` and compares it with the episodes of the show, if it goes into one.
+async fn plan_import(state: &AppState, feed: &str, show_id: Option<Uuid>) -> poem::Result<Result<FeedImportPlan, String>> {
+    let feed = match parse_feed(feed) {
+        Ok(feed) => feed,
+        Err(err) => return Ok(Err(format!("Cannot import this file: {err}"))),
+    };
+    let existing = match show_id {
+        Some(show_id) => {
+            QueryCore::find_show_by_id(&state.conn, show_id)
+                .await
+                .map_err(InternalServerError)?
+                .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+            QueryCore::find_all_episodes(&state.conn, Some(show_id))
+                .await
+                .map_err(InternalServerError)?
+        }
+        None => Vec::new(),
+    };
+    Ok(Ok(FeedImportPlan::new(feed, show_id, &existing)))
+}
+
+async fn render_upload_form(state: &AppState, user: &User, error: Option<&str>) -> poem::Result<Html<String>> {
+    let shows = QueryCore::find_shows_for_user(&state.conn, user)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("shows", &shows);
+    ctx.insert("error", &error);
+    let body = state
+        .templates
+        .render("imports/feed.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn upload_form(state: Data<&AppState>, session: &Session) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    render_upload_form(&state, &user, None).await
+}
+
+#[handler]
+pub async fn preview(
+    state: Data<&AppState>,
+    session: &Session,
+    mut multipart: Multipart,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let mut feed = None;
+    let mut show_id = None;
+    let mut download_media = false;
+    while let Some(field) = multipart.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "feed" => feed = Some(field.bytes().await?),
+            "show_id" => show_id = Some(field.text().await?),
+            "download_media" => download_media = true,
+            _ => {}
+        }
+    }
+
+    let Some(feed) = feed.filter(|feed| !feed.is_empty()) else {
+        return render_upload_form(&state, &user, Some("Choose the feed file to import")).await;
+    };
+    let Ok(feed) = String::from_utf8(feed) else {
+        return render_upload_form(&state, &user, Some("The feed file is not UTF-8 text")).await;
+    };
+    let show_id = parse_show_id(show_id.as_deref())?;
+    let plan = match plan_import(&state, &feed, show_id).await? {
+        Ok(plan) => plan,
+        Err(error) => return render_upload_form(&state, &user, Some(&error)).await,
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("plan", &plan);
+    ctx.insert("feed", &feed);
+    ctx.insert("download_media", &download_media);
+    let body = state
+        .templates
+        .render("imports/preview.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn import(
+    state: Data<&AppState>,
+    session: &Session,
+    Form(form): Form<ImportForm>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let show_id = parse_show_id(form.show_id.as_deref())?;
+    let plan = match plan_import(&state, &form.feed, show_id).await? {
+        Ok(plan) => plan,
+        Err(error) => return Err(Error::from_string(error, StatusCode::BAD_REQUEST)),
+    };
+
+    let result = MutationCore::apply_feed_import(&state.conn, &plan, user.id, chrono::Utc::now())
+        .await
+        .map_err(InternalServerError)?;
+    let downloads: Vec<(Uuid, String)> = if form.download_media.is_some() {
+        result
+            .created
+            .iter()
+            .filter_map(|(id, url)| Some((*id, url.clone()?)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Hundreds of files take longer than anybody wants to wait for a page.
+    if !downloads.is_empty() {
+        let downloader = EnclosureDownloader::new(
+            state.conn.clone(),
+            state.media.clone(),
+            state.config.imports.max_download_bytes,
+        );
+        tokio::spawn(async move { downloader.download_all(downloads).await });
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("show_id", &result.show_id);
+    ctx.insert("created", &result.created.len());
+    ctx.insert("updated", &result.updated);
+    ctx.insert("downloading", &form.download_media.is_some());
+    let body = state
+        .templates
+        .render("imports/done.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+pub fn import_routes() -> Route {
+    Route::new()
+        .at(
+            "/feed",
+            get(upload_form)
+                .post(import)
+                .with(RequirePermission::new(Permission::ShowManage)),
+        )
+        .at(
+            "/feed/preview",
+            post(preview).with(RequirePermission::new(Permission::ShowManage)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnclosureDownloader;
+    use crate::test_support::TestApp;
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::{get, handler, Body, Route, Server};
+    use sea_orm::prelude::Uuid;
+
+    const MAX_BYTES: u64 = 1024;
+
+    #[handler]
+    fn small() -> Vec<u8> {
+        vec![0; 100]
+    }
+
+    #[handler]
+    fn sized() -> Vec<u8> {
+        vec![0; 2 * MAX_BYTES as usize]
+    }
+
+    /// Sent without a Content-Length, like a server that does not know it up front.
+    #[handler]
+    fn streamed() -> Body {
+        Body::from_async_read(std::io::Cursor::new(vec![0; 2 * MAX_BYTES as usize]))
+    }
+
+    /// Serves the files on a random local port and returns its URL.
+    async fn start_host() -> String {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().copied().unwrap();
+        let routes = Route::new()
+            .at("/small.mp3", get(small))
+            .at("/sized.mp3", get(sized))
+            .at("/streamed.mp3", get(streamed));
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(routes));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn downloads_stay_on_the_web_and_below_the_limit() {
+        let app = TestApp::new().await;
+        let downloader = EnclosureDownloader::new(app.state.conn.clone(), app.state.media.clone(), MAX_BYTES);
+        let host = start_host().await;
+        let download = |url: String| {
+            let downloader = downloader.clone();
+            async move { downloader.download(Uuid::new_v4(), &url).await.unwrap_err() }
+        };
+
+        let err = download("file:///etc/passwd".to_string()).await;
+        assert!(err.contains("is not an http:// or https:// URL"), "{err}");
+        for file in ["sized", "streamed"] {
+            let err = download(format!("{host}/{file}.mp3")).await;
+            assert!(err.contains("is larger than the 1024 bytes allowed"), "{file}: {err}");
+        }
+        // Files within the limit are read and only then found not to be audio.
+        let err = download(format!("{host}/small.mp3")).await;
+        assert!(err.contains("is not a supported audio file"), "{err}");
+    }
+}
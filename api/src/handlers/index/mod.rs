@@ -1,14 +1,26 @@
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::permissions::session_permissions;
 use crate::{AppState, PaginationParams};
 use poem::error::InternalServerError;
+use poem::session::Session;
 use poem::web::{Data, Html, Query};
 use poem::{handler, IntoResponse};
+use service::Query as QueryCore;
 
 #[handler]
 pub async fn index(
     state: Data<&AppState>,
+    session: &Session,
     Query(_params): Query<PaginationParams>,
 ) -> poem::Result<impl IntoResponse> {
+    let shows = QueryCore::find_all_shows(&state.conn)
+        .await
+        .map_err(InternalServerError)?;
+
     let mut ctx = tera::Context::new();
+    ctx.insert("shows", &shows);
+    ctx.insert("logged_in", &current_user(session).is_ok());
+    ctx.insert("permissions", &session_permissions(&state, session).await?);
 
     let body = state
         .templates
@@ -0,0 +1,162 @@
+use crate::AppState;
+use media::{ByteRange, MediaError};
+use poem::error::InternalServerError;
+use poem::http::{header, StatusCode};
+use poem::web::{Data, Path};
+use poem::{handler, Body, Error, Request, Response};
+use service::feed::mime_type_for;
+
+/// What a `Range` header asks for, see RFC 9110 section 14.2.
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Whole,
+    Part(ByteRange),
+    /// Starts beyond the end of the object.
+    Unsatisfiable,
+}
+
+/// Reads a `Range` header for an object of `size` bytes. Headers that do not parse and
+/// requests for several ranges at once get the whole object, which RFC 9110 allows.
+fn parse_range(header: Option<&str>, size: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Whole;
+    };
+    let Some((start, end)) = spec.trim().split_once('-').filter(|_| !spec.contains(',')) else {
+        return RangeRequest::Whole;
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        // The last `end` bytes.
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Whole,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Whole,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+            _ => return RangeRequest::Whole,
+        },
+    };
+    if size == 0 || start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Part(ByteRange { start, end })
+}
+
+fn media_error(err: MediaError) -> Error {
+    match err {
+        MediaError::NotFound(_) | MediaError::InvalidKey(_) => Error::from_status(StatusCode::NOT_FOUND),
+        err => InternalServerError(err),
+    }
+}
+
+/// Streams stored media back out; used when the store has no public URL of its own.
+/// Players seek with `Range` requests, so those get just the part they ask for.
+#[handler]
+pub async fn serve(state: Data<&AppState>, Path(key): Path<String>, req: &Request) -> poem::Result<Response> {
+    let size = state.media.size(&key).await.map_err(media_error)?;
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type_for(&key))
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let response = match parse_range(range, size) {
+        RangeRequest::Whole => {
+            let reader = state.media.open(&key, None).await.map_err(media_error)?;
+            response
+                .header(header::CONTENT_LENGTH, size)
+                .body(Body::from_async_read(reader))
+        }
+        RangeRequest::Part(range) => {
+            let reader = state.media.open(&key, Some(range)).await.map_err(media_error)?;
+            response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{size}", range.start, range.end))
+                .header(header::CONTENT_LENGTH, range.end - range.start + 1)
+                .body(Body::from_async_read(reader))
+        }
+        RangeRequest::Unsatisfiable => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+            .finish(),
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_text, TestApp};
+    use poem::http::Method;
+
+    fn part(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Part(ByteRange { start, end })
+    }
+
+    #[test]
+    fn parses_range_headers() {
+        assert_eq!(parse_range(None, 10), RangeRequest::Whole);
+        assert_eq!(parse_range(Some("bytes=0-3"), 10), part(0, 3));
+        assert_eq!(parse_range(Some("bytes=4-"), 10), part(4, 9));
+        assert_eq!(parse_range(Some("bytes=-3"), 10), part(7, 9));
+        assert_eq!(parse_range(Some("bytes=-30"), 10), part(0, 9));
+        assert_eq!(parse_range(Some("bytes=5-100"), 10), part(5, 9));
+        assert_eq!(parse_range(Some("bytes=10-"), 10), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=-0"), 10), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=0-"), 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=3-1"), 10), RangeRequest::Whole);
+        assert_eq!(parse_range(Some("bytes=0-1,4-5"), 10), RangeRequest::Whole);
+        assert_eq!(parse_range(Some("items=0-1"), 10), RangeRequest::Whole);
+        assert_eq!(parse_range(Some("bytes=a-b"), 10), RangeRequest::Whole);
+    }
+
+    async fn get_range(app: &TestApp, range: Option<&str>) -> Response {
+        let mut req = Request::builder().method(Method::GET).uri_str("/media/episodes/1/a.mp3");
+        if let Some(range) = range {
+            req = req.header(header::RANGE, range);
+        }
+        app.send(req.finish()).await
+    }
+
+    #[tokio::test]
+    async fn serves_media_in_parts() {
+        let app = TestApp::new().await;
+        app.state
+            .media
+            .put("episodes/1/a.mp3", b"0123456789", "audio/mpeg")
+            .await
+            .unwrap();
+
+        let response = get_range(&app, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/mpeg");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+        assert_eq!(body_text(response).await, "0123456789");
+
+        let response = get_range(&app, Some("bytes=2-5")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(body_text(response).await, "2345");
+
+        let response = get_range(&app, Some("bytes=-2")).await;
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 8-9/10");
+        assert_eq!(body_text(response).await, "89");
+
+        let response = get_range(&app, Some("bytes=20-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+
+        let response = app.get("/media/episodes/1/missing.mp3").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.get("/media/episodes/../secret").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,6 +1,18 @@
+pub(crate) mod api_v1;
 pub(crate) mod posts;
+pub(crate) mod public;
 pub(crate) mod index;
 pub(crate) mod members;
+pub(crate) mod chapters;
+pub(crate) mod episodes;
+pub(crate) mod shows;
+pub(crate) mod tokens;
+pub(crate) mod sessions;
+pub(crate) mod users;
+pub(crate) mod transcripts;
+pub(crate) mod feed;
+pub(crate) mod imports;
+pub(crate) mod media;
 pub mod open_id_connect;
 pub(crate) mod auth;
 
@@ -1,12 +1,15 @@
 use crate::handlers::auth::login_required_middleware::login_required_middleware;
-use crate::handlers::auth::required_role_middleware::RequiredRoleMiddleware;
+use crate::handlers::auth::permissions::session_permissions;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
 use crate::{AppState, PaginationParams, DEFAULT_ITEMS_PER_PAGE};
 use entities::{post, post::Model as Post};
 use poem::error::InternalServerError;
 use poem::http::StatusCode;
+use poem::session::Session;
 use poem::web::{Data, Form, Html, Path, Query};
 use poem::{get, handler, post, EndpointExt, Error, IntoResponse, Route};
 use sea_orm::prelude::Uuid;
+use service::permissions::Permission;
 use service::{Mutation as MutationCore, Query as QueryCore};
 
 #[handler]
@@ -24,6 +27,7 @@ pub async fn create(state: Data<&AppState>, form: Form<Post>) -> poem::Result<im
 #[handler]
 pub async fn list(
     state: Data<&AppState>,
+    session: &Session,
     Query(params): Query<PaginationParams>,
 ) -> poem::Result<impl IntoResponse> {
     let conn = &state.conn;
@@ -39,6 +43,7 @@ pub async fn list(
     ctx.insert("page", &page);
     ctx.insert("posts_per_page", &posts_per_page);
     ctx.insert("num_pages", &num_pages);
+    ctx.insert("permissions", &session_permissions(&state, session).await?);
 
     let body = state
         .templates
@@ -118,17 +123,17 @@ pub fn post_routes() -> Route {
         .at("/", get(list).around(login_required_middleware))
         .at(
             "/create",
-            post(create).with(RequiredRoleMiddleware::new("super_admin")),
+            post(create).with(RequirePermission::new(Permission::PostEdit)),
         )
         .at(
             "/new",
-            get(new).with(RequiredRoleMiddleware::new("super_admin")),
+            get(new).with(RequirePermission::new(Permission::PostEdit)),
         )
         .at(
             "/:id",
             get(edit)
                 .patch(update)
                 .delete(destroy)
-                .with(RequiredRoleMiddleware::new("super_admin")),
+                .with(RequirePermission::new(Permission::PostEdit)),
         )
 }
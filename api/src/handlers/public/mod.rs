@@ -0,0 +1,178 @@
+//! Pages anyone can see without logging in: show homepages and episode pages.
+
+use crate::handlers::chapters::chapter_rows;
+use crate::handlers::transcripts::{cue_rows, stored_cues};
+use crate::AppState;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{episode, show};
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::web::{Data, Html, Path, Query};
+use poem::{get, handler, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use service::feed::format_duration;
+use service::seo::{episode_json_ld, show_json_ld};
+use service::Query as QueryCore;
+
+/// Longest description put into meta tags, in characters.
+const META_DESCRIPTION_LENGTH: usize = 200;
+
+#[derive(Deserialize, Default)]
+pub struct LanguageFilter {
+    language: Option<String>,
+}
+
+/// Path of a show's homepage.
+pub(crate) fn show_path(show_id: Uuid) -> String {
+    format!("/podcasts/{show_id}")
+}
+
+/// Path of an episode's public page.
+pub(crate) fn episode_path(show_id: Uuid, episode_id: Uuid) -> String {
+    format!("/podcasts/{show_id}/episodes/{episode_id}")
+}
+
+fn show_url(state: &AppState, show_id: Uuid) -> String {
+    format!("{}{}", state.public_url(), show_path(show_id))
+}
+
+fn feed_url(state: &AppState, show_id: Uuid) -> String {
+    format!("{}/shows/{show_id}/feed.xml", state.public_url())
+}
+
+async fn find_show(state: &AppState, id: Uuid) -> poem::Result<show::Model> {
+    QueryCore::find_show_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))
+}
+
+/// What a public page tells search engines and link previews about itself.
+#[derive(Serialize)]
+struct PageMeta {
+    title: String,
+    description: String,
+    url: String,
+    image_url: Option<String>,
+    /// OpenGraph type of the page.
+    og_type: &'static str,
+    json_ld: String,
+}
+
+/// Short plain description for meta tags, cut at a word boundary.
+fn meta_description(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= META_DESCRIPTION_LENGTH {
+        return text;
+    }
+    let cut: String = text.chars().take(META_DESCRIPTION_LENGTH).collect();
+    let cut = cut.rsplit_once(' ').map(|(head, _)| head).unwrap_or(&cut);
+    format!("{cut}…")
+}
+
+/// Inserts what every public page needs for its `<head>`.
+fn insert_page_meta(state: &AppState, ctx: &mut tera::Context, show: &show::Model, meta: PageMeta) {
+    ctx.insert("show", show);
+    ctx.insert("show_url", &show_url(state, show.id));
+    ctx.insert("feed_url", &feed_url(state, show.id));
+    ctx.insert("meta", &meta);
+}
+
+#[handler]
+pub async fn show_home(state: Data<&AppState>, Path(id): Path<Uuid>) -> poem::Result<impl IntoResponse> {
+    let show = find_show(&state, id).await?;
+    let episodes = QueryCore::find_published_episodes_by_show(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let show_url = show_url(&state, id);
+    let meta = PageMeta {
+        title: show.title.clone(),
+        description: meta_description(&show.description),
+        json_ld: show_json_ld(&show, &show_url, &feed_url(&state, id)),
+        url: show_url,
+        image_url: show.artwork_url.clone().filter(|url| !url.is_empty()),
+        og_type: "website",
+    };
+
+    let mut ctx = tera::Context::new();
+    insert_page_meta(&state, &mut ctx, &show, meta);
+    ctx.insert("episodes", &episodes);
+
+    let body = state
+        .templates
+        .render("public/show.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn episode_page(
+    state: Data<&AppState>,
+    Path((show_id, id)): Path<(Uuid, Uuid)>,
+    Query(filter): Query<LanguageFilter>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let show = find_show(&state, show_id).await?;
+    let episode: episode::Model = QueryCore::find_episode_by_id(conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .filter(|episode| episode.show_id == Some(show_id) && episode.status == EpisodeStatus::Published)
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+
+    let chapters = QueryCore::find_chapters_by_episode(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    let transcripts = QueryCore::find_transcripts_by_episode(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    let transcript = match filter.language.as_deref() {
+        Some(language) => transcripts.iter().find(|transcript| transcript.language == language),
+        None => transcripts.first(),
+    };
+    let cues = match transcript {
+        Some(transcript) => cue_rows(stored_cues(transcript)?),
+        None => Vec::new(),
+    };
+
+    let episode_url = format!("{}{}", state.public_url(), episode_path(show_id, id));
+    let meta = PageMeta {
+        title: format!("{} · {}", episode.title, show.title),
+        description: meta_description(&episode.summary),
+        json_ld: episode_json_ld(&show, &show_url(&state, show_id), &episode, &episode_url),
+        url: episode_url,
+        image_url: [&episode.artwork_url, &show.artwork_url]
+            .into_iter()
+            .flatten()
+            .find(|url| !url.is_empty())
+            .cloned(),
+        og_type: "article",
+    };
+
+    let mut ctx = tera::Context::new();
+    insert_page_meta(&state, &mut ctx, &show, meta);
+    ctx.insert("episode", &episode);
+    ctx.insert(
+        "duration",
+        &episode
+            .duration_ms
+            .map(|duration_ms| format_duration((duration_ms.max(0) as u64 + 500) / 1000)),
+    );
+    ctx.insert("chapters", &chapter_rows(chapters));
+    ctx.insert("transcripts", &transcripts);
+    ctx.insert("transcript", &transcript);
+    ctx.insert("cues", &cues);
+
+    let body = state
+        .templates
+        .render("public/episode.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+pub fn public_routes() -> Route {
+    Route::new()
+        .at("/:show_id", get(show_home))
+        .at("/:show_id/episodes/:id", get(episode_page))
+}
@@ -0,0 +1,160 @@
+//! Lets users see where they are logged in and end sessions they do not recognise. Users
+//! allowed to manage users additionally see everybody's sessions and can log anybody out.
+
+use crate::handlers::auth::login_required_middleware::login_required_middleware;
+use crate::handlers::auth::permissions::user_permissions;
+use crate::handlers::auth::providers::OidcProviders;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
+use crate::handlers::auth::{current_session_id, current_user, revoke_provider_tokens};
+use crate::AppState;
+use chrono::Utc;
+use entities::user::Model as User;
+use entities::user_session;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Html, Path};
+use poem::{delete, get, handler, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use service::sessions::describe_user_agent;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Serialize)]
+struct SessionRow {
+    #[serde(flatten)]
+    session: user_session::Model,
+    device: String,
+    current: bool,
+}
+
+/// Another user's session, as super admins see it.
+#[derive(Serialize)]
+struct UserSessionRow {
+    #[serde(flatten)]
+    row: SessionRow,
+    user: User,
+}
+
+fn session_row(session: user_session::Model, current: Option<Uuid>) -> SessionRow {
+    SessionRow {
+        device: describe_user_agent(session.user_agent.as_deref().unwrap_or_default()),
+        current: Some(session.id) == current,
+        session,
+    }
+}
+
+async fn render_sessions(
+    state: &AppState,
+    session: &Session,
+    user: &User,
+    template: &str,
+) -> poem::Result<Html<String>> {
+    let now = Utc::now();
+    let current = current_session_id(session);
+    let sessions: Vec<SessionRow> = QueryCore::find_active_user_sessions_by_user(&state.conn, user.id, now)
+        .await
+        .map_err(InternalServerError)?
+        .into_iter()
+        .map(|session| session_row(session, current))
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("sessions", &sessions);
+    if user_permissions(state, user).await?.has(Permission::UserManage) {
+        let all_sessions: Vec<UserSessionRow> = QueryCore::find_all_active_user_sessions(&state.conn, now)
+            .await
+            .map_err(InternalServerError)?
+            .into_iter()
+            .filter(|(_, owner)| owner.id != user.id)
+            .map(|(session, user)| UserSessionRow {
+                row: session_row(session, current),
+                user,
+            })
+            .collect();
+        ctx.insert("all_sessions", &all_sessions);
+    }
+
+    let body = state
+        .templates
+        .render(template, &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn list(state: Data<&AppState>, session: &Session) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    render_sessions(&state, session, &user, "sessions/list.html.tera").await
+}
+
+/// Ends one of the user's own sessions.
+#[handler]
+pub async fn revoke(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+
+    let ended = MutationCore::end_user_session(&state.conn, id, Some(user.id))
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    revoke_provider_tokens(&providers, vec![ended]);
+
+    render_sessions(&state, session, &user, "sessions/sessions.html.tera").await
+}
+
+/// Ends every session of the user but the one making the request.
+#[handler]
+pub async fn revoke_others(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+
+    let ended = MutationCore::end_user_sessions_of_user(&state.conn, user.id, current_session_id(session))
+        .await
+        .map_err(InternalServerError)?;
+    revoke_provider_tokens(&providers, ended);
+
+    render_sessions(&state, session, &user, "sessions/sessions.html.tera").await
+}
+
+/// Logs another user out everywhere.
+#[handler]
+pub async fn force_logout(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    Path(user_id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    if user_id == user.id {
+        return Err(Error::from_string(
+            "Use the buttons next to your own sessions to end them",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let ended = MutationCore::end_user_sessions_of_user(&state.conn, user_id, None)
+        .await
+        .map_err(InternalServerError)?;
+    revoke_provider_tokens(&providers, ended);
+
+    render_sessions(&state, session, &user, "sessions/sessions.html.tera").await
+}
+
+pub fn session_routes() -> Route {
+    Route::new()
+        .at("/", get(list).delete(revoke_others).around(login_required_middleware))
+        .at("/:id", delete(revoke).around(login_required_middleware))
+        .at(
+            "/users/:user_id",
+            delete(force_logout).with(RequirePermission::new(Permission::UserManage)),
+        )
+}
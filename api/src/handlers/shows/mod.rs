@@ -0,0 +1,199 @@
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::login_required_middleware::login_required_middleware;
+use crate::handlers::auth::permissions::user_permissions;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
+use crate::handlers::feed::show_feed;
+use crate::AppState;
+use entities::{show, show::Model as Show};
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path};
+use poem::{delete, get, handler, post, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use service::permissions::{Permission, DEFAULT_SHOW_ROLE};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[derive(Deserialize)]
+pub struct GrantForm {
+    email: String,
+    role: Option<String>,
+}
+
+#[handler]
+pub async fn create(state: Data<&AppState>, form: Form<Show>) -> poem::Result<impl IntoResponse> {
+    let form = form.0;
+    let conn = &state.conn;
+
+    MutationCore::create_show(conn, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/shows"))
+}
+
+#[handler]
+pub async fn list(state: Data<&AppState>, session: &Session) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let user = current_user(session)?;
+
+    let shows = QueryCore::find_shows_for_user(conn, &user)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("shows", &shows);
+    ctx.insert("permissions", &user_permissions(&state, &user).await?);
+
+    let body = state
+        .templates
+        .render("shows/list.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn new(state: Data<&AppState>) -> poem::Result<impl IntoResponse> {
+    let ctx = tera::Context::new();
+    let body = state
+        .templates
+        .render("shows/new.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn edit(state: Data<&AppState>, Path(id): Path<Uuid>) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    let show: show::Model = QueryCore::find_show_by_id(conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+    let users = QueryCore::find_show_users(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    let roles = QueryCore::find_roles(conn)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("show", &show);
+    ctx.insert("users", &users);
+    ctx.insert("roles", roles.all());
+    ctx.insert("default_role", DEFAULT_SHOW_ROLE);
+
+    let body = state
+        .templates
+        .render("shows/edit.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn update(
+    state: Data<&AppState>,
+    Path(id): Path<Uuid>,
+    form: Form<show::Model>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let form = form.0;
+
+    let show = MutationCore::update_show_by_id(conn, id, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("show", &show);
+
+    let body = state
+        .templates
+        .render("shows/show_row.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    MutationCore::delete_show(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/shows"))
+}
+
+#[handler]
+pub async fn grant(
+    state: Data<&AppState>,
+    Path(id): Path<Uuid>,
+    form: Form<GrantForm>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let role = form.role.as_deref().unwrap_or(DEFAULT_SHOW_ROLE);
+
+    let roles = QueryCore::find_roles(conn)
+        .await
+        .map_err(InternalServerError)?;
+    if !roles.contains(role) {
+        return Err(Error::from_string("Unknown role", StatusCode::BAD_REQUEST));
+    }
+    let user = QueryCore::find_user_by_email(conn, form.email.trim())
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_string("No user with that email", StatusCode::NOT_FOUND))?;
+
+    MutationCore::grant_show_access(conn, id, user.id, role)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", format!("/shows/{id}")))
+}
+
+#[handler]
+pub async fn revoke(
+    state: Data<&AppState>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    MutationCore::revoke_show_access(conn, id, user_id)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", format!("/shows/{id}")))
+}
+
+pub fn show_routes() -> Route {
+    Route::new()
+        .at("/", get(list).around(login_required_middleware))
+        .at(
+            "/create",
+            post(create).with(RequirePermission::new(Permission::ShowManage)),
+        )
+        .at(
+            "/new",
+            get(new).with(RequirePermission::new(Permission::ShowManage)),
+        )
+        .at(
+            "/:id",
+            get(edit)
+                .patch(update)
+                .delete(destroy)
+                .with(RequirePermission::new(Permission::ShowManage)),
+        )
+        .at(
+            "/:id/users",
+            post(grant).with(RequirePermission::new(Permission::ShowGrant)),
+        )
+        .at(
+            "/:id/users/:user_id",
+            delete(revoke).with(RequirePermission::new(Permission::ShowGrant)),
+        )
+        .at("/:id/feed.xml", get(show_feed))
+}
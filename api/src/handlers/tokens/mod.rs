@@ -0,0 +1,141 @@
+//! Lets users mint and revoke their personal API tokens.
+
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::login_required_middleware::login_required_middleware;
+use crate::AppState;
+use chrono::{Duration, Utc};
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path};
+use poem::{delete, get, handler, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use entities::api_token;
+use serde::{Deserialize, Serialize};
+use service::api_tokens::TokenScope;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+/// Expiry choices offered in the form, in days.
+const EXPIRY_DAYS: [i64; 3] = [30, 90, 365];
+
+#[derive(Deserialize)]
+pub struct TokenForm {
+    name: String,
+    /// Checkboxes, present when ticked.
+    read: Option<String>,
+    write: Option<String>,
+    /// Empty for tokens that never expire.
+    expires_in_days: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenRow {
+    #[serde(flatten)]
+    token: api_token::Model,
+    expired: bool,
+}
+
+/// Renders the token list, along with the secret of a token that was just created.
+async fn render_tokens(
+    state: &AppState,
+    user: &User,
+    template: &str,
+    new_token: Option<&str>,
+) -> poem::Result<Html<String>> {
+    let tokens = QueryCore::find_api_tokens_by_user(&state.conn, user.id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let now = Utc::now();
+    let tokens: Vec<TokenRow> = tokens
+        .into_iter()
+        .map(|token| TokenRow {
+            expired: token.expires_at.is_some_and(|expires_at| expires_at <= now),
+            token,
+        })
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("tokens", &tokens);
+    ctx.insert("new_token", &new_token);
+    ctx.insert("expiry_days", &EXPIRY_DAYS);
+
+    let body = state
+        .templates
+        .render(template, &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn list(state: Data<&AppState>, session: &Session) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    render_tokens(&state, &user, "tokens/list.html.tera", None).await
+}
+
+#[handler]
+pub async fn create(
+    state: Data<&AppState>,
+    session: &Session,
+    form: Form<TokenForm>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let form = form.0;
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::from_string("Give the token a name", StatusCode::BAD_REQUEST));
+    }
+    let scopes: Vec<TokenScope> = [(TokenScope::Read, &form.read), (TokenScope::Write, &form.write)]
+        .into_iter()
+        .filter(|(_, ticked)| ticked.is_some())
+        .map(|(scope, _)| scope)
+        .collect();
+    if scopes.is_empty() {
+        return Err(Error::from_string("Pick at least one scope", StatusCode::BAD_REQUEST));
+    }
+    let now = Utc::now();
+    let expires_at = match form.expires_in_days.as_deref().filter(|days| !days.is_empty()) {
+        Some(days) => {
+            let days: i64 = days
+                .parse()
+                .ok()
+                .filter(|days| EXPIRY_DAYS.contains(days))
+                .ok_or_else(|| Error::from_string("Pick one of the offered expiry times", StatusCode::BAD_REQUEST))?;
+            Some(now + Duration::days(days))
+        }
+        None => None,
+    };
+
+    let (_, secret) = MutationCore::create_api_token(&state.conn, user.id, name, &scopes, expires_at, now)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_tokens(&state, &user, "tokens/tokens.html.tera", Some(&secret)).await
+}
+
+#[handler]
+pub async fn revoke(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+
+    let result = MutationCore::revoke_api_token(&state.conn, user.id, id)
+        .await
+        .map_err(InternalServerError)?;
+    if result.rows_affected == 0 {
+        return Err(Error::from_status(StatusCode::NOT_FOUND));
+    }
+
+    render_tokens(&state, &user, "tokens/tokens.html.tera", None).await
+}
+
+// Everybody manages their own tokens only, so logging in is all it takes.
+pub fn token_routes() -> Route {
+    Route::new()
+        .at("/", get(list).post(create).around(login_required_middleware))
+        .at("/:id", delete(revoke).around(login_required_middleware))
+}
@@ -0,0 +1,175 @@
+use crate::handlers::auth::current_user;
+use crate::handlers::episodes::find_accessible_episode;
+use crate::AppState;
+use entities::episode;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::transcript;
+use poem::error::InternalServerError;
+use poem::http::{header, StatusCode};
+use poem::session::Session;
+use poem::web::{Data, Html, Multipart, Path};
+use poem::{handler, Error, IntoResponse};
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use service::chapters::format_timestamp;
+use service::transcripts::{parse_upload, render, Cue, TranscriptFormat};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+/// Accepts tags such as `en`, `de-CH` or `zh-Hant`.
+fn valid_language(language: &str) -> bool {
+    (2..=16).contains(&language.len())
+        && language.split('-').all(|part| {
+            !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
+
+/// A cue as listed on the public episode page.
+#[derive(Serialize)]
+pub(crate) struct CueRow {
+    start: String,
+    start_seconds: i64,
+    speaker: Option<String>,
+    text: String,
+}
+
+pub(crate) fn cue_rows(cues: Vec<Cue>) -> Vec<CueRow> {
+    cues.into_iter()
+        .map(|cue| CueRow {
+            // Whole seconds are precise enough to find your way around.
+            start: format_timestamp(cue.start_ms / 1000 * 1000),
+            start_seconds: cue.start_ms / 1000,
+            speaker: cue.speaker,
+            text: cue.text,
+        })
+        .collect()
+}
+
+#[allow(clippy::result_large_err)]
+pub(crate) fn stored_cues(transcript: &transcript::Model) -> poem::Result<Vec<Cue>> {
+    serde_json::from_str(&transcript.cues).map_err(InternalServerError)
+}
+
+/// Loads an episode for the public pages, which only ever show published episodes.
+async fn find_published_episode(state: &AppState, id: Uuid) -> poem::Result<episode::Model> {
+    QueryCore::find_episode_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .filter(|episode| episode.status == EpisodeStatus::Published)
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))
+}
+
+/// Re-renders the transcript list of an episode's edit screen.
+async fn render_transcripts(state: &AppState, episode: &episode::Model) -> poem::Result<Html<String>> {
+    let transcripts = QueryCore::find_transcripts_by_episode(&state.conn, episode.id)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("episode", episode);
+    ctx.insert("transcripts", &transcripts);
+
+    let body = state
+        .templates
+        .render("episodes/transcripts.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+/// Takes an SRT or WebVTT upload and stores it as the transcript for its language.
+#[handler]
+pub async fn upload(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+
+    let mut language = String::new();
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "language" => language = field.text().await?.trim().to_string(),
+            "transcript" => {
+                let file_name = field.file_name().unwrap_or_default().to_string();
+                file = Some((file_name, field.bytes().await?));
+            }
+            _ => {}
+        }
+    }
+
+    if !valid_language(&language) {
+        return Err(Error::from_string(
+            "Give the transcript's language as a tag such as en or de-CH",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let (file_name, bytes) = file
+        .filter(|(_, bytes)| !bytes.is_empty())
+        .ok_or_else(|| Error::from_string("Choose a transcript file", StatusCode::BAD_REQUEST))?;
+    let content = String::from_utf8(bytes).map_err(|_| {
+        Error::from_string(
+            format!("{file_name} is not UTF-8 text"),
+            StatusCode::BAD_REQUEST,
+        )
+    })?;
+    let (source_format, cues) = parse_upload(&file_name, &content).map_err(|err| {
+        Error::from_string(format!("{file_name}: {err}"), StatusCode::BAD_REQUEST)
+    })?;
+
+    MutationCore::save_transcript(&state.conn, id, &language, source_format, &cues)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_transcripts(&state, &episode).await
+}
+
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    session: &Session,
+    Path((id, transcript_id)): Path<(Uuid, Uuid)>,
+) -> poem::Result<impl IntoResponse> {
+    let user = current_user(session)?;
+    let episode = find_accessible_episode(&state, &user, id).await?;
+
+    let transcripts = QueryCore::find_transcripts_by_episode(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    if !transcripts.iter().any(|transcript| transcript.id == transcript_id) {
+        return Err(Error::from_status(StatusCode::NOT_FOUND));
+    }
+    MutationCore::delete_transcript(&state.conn, transcript_id)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_transcripts(&state, &episode).await
+}
+
+/// Serves a transcript in whichever format the extension asks for, e.g. `en.vtt`.
+#[handler]
+pub async fn download(
+    state: Data<&AppState>,
+    Path((id, file)): Path<(Uuid, String)>,
+) -> poem::Result<impl IntoResponse> {
+    let (language, format) = file
+        .rsplit_once('.')
+        .and_then(|(language, extension)| Some((language, TranscriptFormat::from_extension(extension)?)))
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+
+    find_published_episode(&state, id).await?;
+    let transcripts = QueryCore::find_transcripts_by_episode(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    let transcript = transcripts
+        .iter()
+        .find(|transcript| transcript.language == language)
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+
+    let body = render(&stored_cues(transcript)?, format);
+    Ok(body
+        .with_header(header::CONTENT_TYPE, format!("{}; charset=utf-8", format.mime_type()))
+        // Web players fetch transcripts from other origins.
+        .with_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+}
@@ -0,0 +1,475 @@
+//! Lets users allowed to manage users see everybody's account, invite people, change
+//! their roles, and deactivate or delete accounts.
+
+use crate::handlers::auth::current_user;
+use crate::handlers::auth::local::send_email;
+use crate::handlers::auth::providers::OidcProviders;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
+use crate::handlers::auth::revoke_provider_tokens;
+use crate::AppState;
+use chrono::Utc;
+use entities::user::Model as User;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path, Query};
+use poem::{delete, get, handler, patch, post, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use service::accounts::{looks_like_email, normalize_email};
+use service::invitations::INVITATION_LIFETIME_DAYS;
+use service::permissions::{Permission, Roles, DEFAULT_ROLE};
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+const USERS_PER_PAGE: u64 = 20;
+
+#[derive(Deserialize)]
+pub struct UsersParams {
+    page: Option<u64>,
+    /// Matched against the name and the email address.
+    q: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct InviteForm {
+    email: String,
+    role: String,
+}
+
+#[derive(Deserialize)]
+pub struct RoleForm {
+    role: String,
+}
+
+pub(crate) async fn find_roles(state: &AppState) -> poem::Result<Roles> {
+    QueryCore::find_roles(&state.conn)
+        .await
+        .map_err(InternalServerError)
+}
+
+async fn find_user(state: &AppState, id: Uuid) -> poem::Result<User> {
+    QueryCore::find_user_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))
+}
+
+/// Admins cannot lock themselves out; somebody else has to do that.
+#[allow(clippy::result_large_err)]
+fn ensure_not_self(user: &User, id: Uuid) -> poem::Result<()> {
+    if user.id == id {
+        Err(Error::from_string(
+            "You cannot do this to your own account",
+            StatusCode::BAD_REQUEST,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Nobody may hand out a role that is not below their own, or change the role of
+/// somebody at or above them. Super admins are the exception.
+#[allow(clippy::result_large_err)]
+pub(crate) fn ensure_may_grant(roles: &Roles, me: &User, role: &str) -> poem::Result<()> {
+    if roles.may_grant(&me.role, role) {
+        Ok(())
+    } else {
+        Err(Error::from_string(
+            format!("You cannot grant the role {role}"),
+            StatusCode::FORBIDDEN,
+        ))
+    }
+}
+
+/// Renders the pending invitations with the invite form, which offers the roles `me` may
+/// grant. `ctx` carries what the form shows, e.g. an error.
+async fn render_invitations(
+    state: &AppState,
+    me: &User,
+    template: &str,
+    mut ctx: tera::Context,
+) -> poem::Result<Html<String>> {
+    let invitations = QueryCore::find_pending_invitations(&state.conn, Utc::now())
+        .await
+        .map_err(InternalServerError)?;
+
+    ctx.insert("invitations", &invitations);
+    ctx.insert("roles", &find_roles(state).await?.grantable_by(&me.role));
+    ctx.insert("default_role", DEFAULT_ROLE);
+
+    let body = state
+        .templates
+        .render(template, &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+async fn render_user_row(state: &AppState, me: &User, user: &User) -> poem::Result<Html<String>> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("user", user);
+    ctx.insert("me", &me.id);
+    ctx.insert("roles", &find_roles(state).await?.grantable_by(&me.role));
+
+    let body = state
+        .templates
+        .render("users/user_row.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn list(
+    state: Data<&AppState>,
+    session: &Session,
+    Query(params): Query<UsersParams>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    let search = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let users = QueryCore::find_users_page(
+        &state.conn,
+        None,
+        search,
+        params.page.unwrap_or(1),
+        USERS_PER_PAGE,
+    )
+    .await
+    .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("users", &users.items);
+    ctx.insert("page", &users.page);
+    ctx.insert("num_pages", &users.total_pages.max(1));
+    ctx.insert("q", search.unwrap_or_default());
+    ctx.insert("me", &me.id);
+    render_invitations(&state, &me, "users/list.html.tera", ctx).await
+}
+
+/// Mails `email` a link to sign up with.
+#[handler]
+pub async fn invite(
+    state: Data<&AppState>,
+    session: &Session,
+    Form(form): Form<InviteForm>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    let email = normalize_email(&form.email);
+
+    let existing = QueryCore::find_user_by_email(&state.conn, &email)
+        .await
+        .map_err(InternalServerError)?;
+    let roles = find_roles(&state).await?;
+    let error = if !looks_like_email(&email) {
+        Some("That does not look like an email address".to_string())
+    } else if !roles.contains(&form.role) {
+        Some("Pick one of the roles".to_string())
+    } else if !roles.may_grant(&me.role, &form.role) {
+        Some("You can only invite people with a role below your own".to_string())
+    } else if existing.is_some() {
+        Some(format!("{email} already has an account"))
+    } else {
+        None
+    };
+    let mut ctx = tera::Context::new();
+    if let Some(error) = error {
+        ctx.insert("error", &error);
+        ctx.insert("email", &email);
+        return render_invitations(&state, &me, "users/invitations.html.tera", ctx).await;
+    }
+
+    let (_, token) = MutationCore::create_invitation(&state.conn, &email, &form.role, me.id, Utc::now())
+        .await
+        .map_err(InternalServerError)?;
+    let mut mail = tera::Context::new();
+    mail.insert("inviter", &me.name);
+    mail.insert("link", &format!("{}/auth/invitations/{token}", state.public_url()));
+    mail.insert("valid_days", &INVITATION_LIFETIME_DAYS);
+    send_email(&state, &email, "You are invited to Pod Crab", "invitation", mail)?;
+
+    ctx.insert("notice", &format!("Invitation sent to {email}"));
+    render_invitations(&state, &me, "users/invitations.html.tera", ctx).await
+}
+
+#[handler]
+pub async fn revoke_invitation(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    let invitation = QueryCore::find_invitation_by_id(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    if let Some(invitation) = invitation {
+        ensure_may_grant(&find_roles(&state).await?, &me, &invitation.role)?;
+    }
+    MutationCore::revoke_invitation(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    render_invitations(&state, &me, "users/invitations.html.tera", tera::Context::new()).await
+}
+
+#[handler]
+pub async fn change_role(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+    Form(form): Form<RoleForm>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    ensure_not_self(&me, id)?;
+    let roles = find_roles(&state).await?;
+    if !roles.contains(&form.role) {
+        return Err(Error::from_string("Unknown role", StatusCode::BAD_REQUEST));
+    }
+    ensure_may_grant(&roles, &me, &form.role)?;
+
+    let user = find_user(&state, id).await?;
+    ensure_may_grant(&roles, &me, &user.role)?;
+    let user = MutationCore::change_user_role(&state.conn, user, &form.role)
+        .await
+        .map_err(InternalServerError)?;
+    render_user_row(&state, &me, &user).await
+}
+
+/// Locks the user out and ends their sessions. Their API tokens stop working too.
+#[handler]
+pub async fn deactivate(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    ensure_not_self(&me, id)?;
+
+    let user = find_user(&state, id).await?;
+    ensure_may_grant(&find_roles(&state).await?, &me, &user.role)?;
+    let user = MutationCore::set_user_deactivated(&state.conn, user, Some(Utc::now()))
+        .await
+        .map_err(InternalServerError)?;
+    let ended = MutationCore::end_user_sessions_of_user(&state.conn, id, None)
+        .await
+        .map_err(InternalServerError)?;
+    revoke_provider_tokens(&providers, ended);
+
+    render_user_row(&state, &me, &user).await
+}
+
+#[handler]
+pub async fn activate(
+    state: Data<&AppState>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+
+    let user = find_user(&state, id).await?;
+    ensure_may_grant(&find_roles(&state).await?, &me, &user.role)?;
+    let user = MutationCore::set_user_deactivated(&state.conn, user, None)
+        .await
+        .map_err(InternalServerError)?;
+    render_user_row(&state, &me, &user).await
+}
+
+/// Deletes the user. Users who created episodes can only be deactivated, as their
+/// episodes would go with them.
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    providers: Data<&OidcProviders>,
+    session: &Session,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let me = current_user(session)?;
+    ensure_not_self(&me, id)?;
+    let user = find_user(&state, id).await?;
+    ensure_may_grant(&find_roles(&state).await?, &me, &user.role)?;
+
+    let episodes = QueryCore::count_episodes_by_user(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+    if episodes > 0 {
+        return Err(Error::from_string(
+            format!("This user created {episodes} episode(s). Deactivate them instead."),
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let ended = MutationCore::end_user_sessions_of_user(&state.conn, id, None)
+        .await
+        .map_err(InternalServerError)?;
+    revoke_provider_tokens(&providers, ended);
+    MutationCore::delete_user(&state.conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/users"))
+}
+
+pub fn user_routes() -> Route {
+    Route::new()
+        .at(
+            "/",
+            get(list).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/invitations",
+            post(invite).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/invitations/:id",
+            delete(revoke_invitation).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/:id",
+            delete(destroy).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/:id/role",
+            patch(change_role).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/:id/deactivate",
+            post(deactivate).with(RequirePermission::new(Permission::UserManage)),
+        )
+        .at(
+            "/:id/activate",
+            post(activate).with(RequirePermission::new(Permission::UserManage)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{body_text, TestApp};
+    use chrono::Utc;
+    use entities::role_permission;
+    use poem::http::{Method, StatusCode};
+    use poem::Request;
+    use sea_orm::prelude::Uuid;
+    use sea_orm::{ActiveModelTrait, Set};
+    use service::permissions::{Permission, SUPER_ADMIN_ROLE};
+    use service::{Mutation as MutationCore, Query as QueryCore};
+
+    /// An app where admins may manage users too, with one of them logged in.
+    async fn app_with_admin() -> TestApp {
+        let app = TestApp::new().await;
+        role_permission::ActiveModel {
+            role: Set("admin".to_string()),
+            permission: Set(Permission::UserManage.as_str().to_string()),
+        }
+        .insert(&app.state.conn)
+        .await
+        .unwrap();
+        let admin = app.create_user("admin@example.com", "admin").await;
+        app.log_in(&admin).await;
+        app
+    }
+
+    async fn invite(app: &TestApp, email: &str, role: &str) -> String {
+        let response = app
+            .send_form(Method::POST, "/users/invitations", &[("email", email), ("role", role)])
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        body_text(response).await
+    }
+
+    async fn pending_roles(app: &TestApp) -> Vec<String> {
+        QueryCore::find_pending_invitations(&app.state.conn, chrono::Utc::now())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|invitation| invitation.role)
+            .collect()
+    }
+
+    async fn change(app: &TestApp, id: Uuid, role: &str) -> StatusCode {
+        let uri = format!("/users/{id}/role");
+        app.send_form(Method::PATCH, &uri, &[("role", role)]).await.status()
+    }
+
+    #[tokio::test]
+    async fn admins_invite_only_below_their_role() {
+        let app = app_with_admin().await;
+
+        for role in ["admin", SUPER_ADMIN_ROLE] {
+            let body = invite(&app, "eve@example.com", role).await;
+            assert!(body.contains("only invite people with a role below your own"), "{role}");
+        }
+        assert!(pending_roles(&app).await.is_empty());
+
+        let body = invite(&app, "hal@example.com", "host").await;
+        assert!(body.contains("Invitation sent to hal@example.com"));
+        assert_eq!(pending_roles(&app).await, ["host"]);
+        assert!(!body.contains("value=\"admin\""), "the form only offers grantable roles");
+    }
+
+    #[tokio::test]
+    async fn admins_change_roles_only_below_their_own() {
+        let app = app_with_admin().await;
+        let user = app.create_user("user@example.com", "user").await;
+        let boss = app.create_user("boss@example.com", SUPER_ADMIN_ROLE).await;
+        assert_eq!(change(&app, user.id, SUPER_ADMIN_ROLE).await, StatusCode::FORBIDDEN);
+        assert_eq!(change(&app, user.id, "admin").await, StatusCode::FORBIDDEN);
+        assert_eq!(change(&app, boss.id, "user").await, StatusCode::FORBIDDEN, "demoting a super admin");
+        assert_eq!(change(&app, user.id, "host").await, StatusCode::OK);
+
+        let stored = |id| QueryCore::find_user_by_id(&app.state.conn, id);
+        assert_eq!(stored(user.id).await.unwrap().unwrap().role, "host");
+        assert_eq!(stored(boss.id).await.unwrap().unwrap().role, SUPER_ADMIN_ROLE);
+    }
+
+    async fn act(app: &TestApp, method: Method, uri: String) -> StatusCode {
+        app.send(Request::builder().method(method).uri_str(&uri).finish()).await.status()
+    }
+
+    #[tokio::test]
+    async fn admins_manage_only_accounts_below_their_own() {
+        let app = app_with_admin().await;
+        let boss = app.create_user("boss@example.com", SUPER_ADMIN_ROLE).await;
+        let peer = app.create_user("peer@example.com", "admin").await;
+        let user = app.create_user("user@example.com", "user").await;
+
+        for target in [&boss, &peer] {
+            let id = target.id;
+            let forbidden = StatusCode::FORBIDDEN;
+            assert_eq!(act(&app, Method::POST, format!("/users/{id}/deactivate")).await, forbidden);
+            assert_eq!(act(&app, Method::POST, format!("/users/{id}/activate")).await, forbidden);
+            assert_eq!(act(&app, Method::DELETE, format!("/users/{id}")).await, forbidden);
+            assert_eq!(act(&app, Method::DELETE, format!("/api/v1/users/{id}/sessions")).await, forbidden);
+            let stored = QueryCore::find_user_by_id(&app.state.conn, id).await.unwrap().unwrap();
+            assert!(stored.deactivated_at.is_none(), "{}", stored.email);
+        }
+
+        let (invitation, _) =
+            MutationCore::create_invitation(&app.state.conn, "next@example.com", "admin", boss.id, Utc::now())
+                .await
+                .unwrap();
+        let uri = format!("/users/invitations/{}", invitation.id);
+        assert_eq!(act(&app, Method::DELETE, uri).await, StatusCode::FORBIDDEN);
+        assert_eq!(pending_roles(&app).await, ["admin"]);
+
+        let id = user.id;
+        let log_out = format!("/api/v1/users/{id}/sessions");
+        assert_eq!(act(&app, Method::DELETE, log_out).await, StatusCode::NO_CONTENT);
+        assert_eq!(act(&app, Method::POST, format!("/users/{id}/deactivate")).await, StatusCode::OK);
+        assert_eq!(act(&app, Method::POST, format!("/users/{id}/activate")).await, StatusCode::OK);
+        assert_eq!(act(&app, Method::DELETE, format!("/users/{id}")).await, StatusCode::ACCEPTED);
+        assert!(QueryCore::find_user_by_id(&app.state.conn, id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn super_admins_grant_any_role() {
+        let app = TestApp::new().await;
+        let root = app.create_user("root@example.com", SUPER_ADMIN_ROLE).await;
+        let user = app.create_user("user@example.com", "user").await;
+        app.log_in(&root).await;
+
+        let body = invite(&app, "next@example.com", SUPER_ADMIN_ROLE).await;
+        assert!(body.contains("Invitation sent"));
+        let uri = format!("/users/{}/role", user.id);
+        let response = app.send_form(Method::PATCH, &uri, &[("role", SUPER_ADMIN_ROLE)]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
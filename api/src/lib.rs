@@ -1,28 +1,55 @@
-use crate::handlers::auth::{setup_openid_client};
-use crate::handlers::{auth, index, members, posts};
+use crate::handlers::auth::bearer_token_middleware::bearer_token_middleware;
+use crate::handlers::auth::browser_only_middleware::browser_only_middleware;
+use crate::handlers::auth::session_tracking_middleware::session_tracking_middleware;
+use crate::config::{Config, SessionBackend};
+use crate::handlers::auth::providers::OidcProviders;
+use crate::handlers::{api_v1, auth, episodes, feed, imports, index, media, members, posts, public, sessions, shows, tokens, users};
 use migration::{Migrator, MigratorTrait};
 use poem::endpoint::StaticFilesEndpoint;
 use poem::listener::TcpListener;
-use poem::{get, EndpointExt, Route, Server};
-use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, EntityTrait};
+use poem::{get, Endpoint, EndpointExt, Route, Server};
+use sea_orm::{Database, DatabaseConnection};
 use serde::Deserialize;
 use std::env;
-use std::str::FromStr;
-use poem::session::{CookieConfig, CookieSession};
-use sea_orm::ActiveValue::Set;
-use sea_orm::prelude::Uuid;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use service::Mutation as MutationCore;
+use poem::session::ServerSession;
 use tera::Tera;
-use entities::user;
+use ::media::MediaStore;
+use mailer::Mailer;
 
+pub mod config;
 mod handlers;
 
+pub use handlers::feed::{render_show_feed, render_site_feed};
+pub use handlers::imports::EnclosureDownloader;
+mod session_store;
+#[cfg(test)]
+mod test_support;
+
+use session_store::SessionStore;
+
 
 const DEFAULT_ITEMS_PER_PAGE: u64 = 5;
+const PUBLISH_INTERVAL_SECONDS: u64 = 60;
+const SESSION_CLEANUP_INTERVAL_SECONDS: u64 = 60 * 60;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 struct AppState {
     templates: Tera,
-    conn: DatabaseConnection
+    conn: DatabaseConnection,
+    media: Arc<dyn MediaStore>,
+    mailer: Arc<dyn Mailer>,
+    config: Arc<Config>,
+}
+
+impl AppState {
+    /// Absolute base URL the site is reachable at from the outside, without a trailing slash.
+    fn public_url(&self) -> &str {
+        &self.config.server.public_url
+    }
 }
 
 #[derive(Deserialize, Default)]
@@ -31,73 +58,104 @@ struct PaginationParams {
     items_per_page: Option<u64>,
 }
 
-#[tokio::main]
-async fn start(root_path: Option<String>) -> std::io::Result<()> {
-    let root_path = if let Some(root_path) = root_path { root_path } else { env::current_dir()?.to_str().unwrap().to_string() };
+/// Runs the web server until it is stopped. Templates and static files are looked up
+/// below `root_path`.
+pub async fn serve(config: Config, root_path: &str) -> std::io::Result<()> {
     env::set_var("RUST_LOG", "debug");
     tracing_subscriber::fmt::init();
     println!("Root path: {root_path}");
 
-    // get env vars
-    dotenvy::dotenv().ok();
-    
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let host = env::var("HOST").expect("HOST is not set in .env file");
-    let port = env::var("PORT").expect("PORT is not set in .env file");
-    let server_url = format!("{host}:{port}");
+    let server_url = format!("{}:{}", config.server.host, config.server.port);
 
     // create post table if not exists
-    let conn = Database::connect(&db_url).await.unwrap();
+    let conn = Database::connect(&config.database.url).await.unwrap();
     Migrator::up(&conn, None).await.unwrap();
     
-    ensure_super_admin(&conn).await;
-    let template_path = format!("{}/frontend/templates/**/*", &root_path);
-    println!("{}", template_path);
-    let templates = Tera::new(&template_path).unwrap();
-    let google_client = setup_openid_client().await.unwrap();
-    let state = AppState { templates, conn };
+    let templates = load_templates(root_path);
+    let providers = OidcProviders::discover(config.auth.providers.clone()).await;
+    tokio::spawn(publish_scheduled_episodes(conn.clone()));
+
+    let media = ::media::from_config(&config.media).expect("Failed to set up media storage");
+    let mailer = mailer::from_config(&config.mail).expect("Failed to set up mailer");
+    let session_store = session_store::from_config(&config.sessions, &conn)
+        .await
+        .expect("Failed to set up session storage");
+    if matches!(config.sessions.backend, SessionBackend::Database) {
+        tokio::spawn(delete_expired_sessions(conn.clone()));
+    }
+    let state = AppState { templates, conn, media, mailer, config: Arc::new(config) };
     
     println!("Starting server at {server_url}");
-    let app = Route::new()
+    let server = Server::new(TcpListener::bind(server_url));
+    server.run(app(state, providers, session_store, root_path)).await
+}
+
+fn load_templates(root_path: &str) -> Tera {
+    let template_path = format!("{root_path}/frontend/templates/**/*");
+    println!("{}", template_path);
+    let mut templates = Tera::new(&template_path).unwrap();
+    // Public pages show whatever users typed in, so escape by default.
+    templates.autoescape_on(vec![".html.tera"]);
+    templates.register_tester("granted", auth::permissions::granted);
+    templates
+}
+
+/// Every route of the site, with sessions and bearer tokens set up.
+fn app(state: AppState, providers: OidcProviders, session_store: SessionStore, root_path: &str) -> impl Endpoint {
+    let cookie_config = session_store::cookie_config(&state.config.sessions);
+    let api_routes = api_v1::api_routes(state.public_url());
+    Route::new()
         .at("/", get(index::index))
+        .at("/feed.xml", get(feed::feed))
+        .at("/media/*key", get(media::serve))
         .nest("/posts", posts::post_routes())
         .nest("/members", members::member_routes())
+        .nest("/episodes", episodes::episode_routes())
+        .nest("/shows", shows::show_routes())
+        .nest("/tokens", tokens::token_routes().around(browser_only_middleware))
+        .nest("/sessions", sessions::session_routes().around(browser_only_middleware))
+        .nest("/users", users::user_routes().around(browser_only_middleware))
+        .nest("/imports", imports::import_routes())
+        .nest("/podcasts", public::public_routes())
         .nest("/auth", auth::routes())
+        .nest("/api", api_routes)
         .nest(
             "/static",
-            StaticFilesEndpoint::new(format!("{}/static", &root_path)),
+            StaticFilesEndpoint::new(format!("{root_path}/static")),
         )
         .nest(
             "/dist",
-            StaticFilesEndpoint::new(format!("{}/frontend/dist", &root_path)),
+            StaticFilesEndpoint::new(format!("{root_path}/frontend/dist")),
         )
-        .with(CookieSession::new(CookieConfig::default())) //.secure(true)
+        .around(bearer_token_middleware)
+        .around(session_tracking_middleware)
+        .with(ServerSession::new(cookie_config, session_store))
         .data(state)
-        .data(google_client);
-    let server = Server::new(TcpListener::bind(format!("{host}:{port}")));
-    server.run(app).await
+        .data(providers)
 }
 
-async fn ensure_super_admin(database_connection: &DatabaseConnection) {
-    let user_id = Uuid::from_str("920b2fc5-d127-4003-b3f9-43bb685558d4").unwrap();
-    if let Ok(Some(_user)) = user::Entity::find_by_id(user_id.clone()).one(database_connection).await {
-        return;
-    }
-    
-    let _u = user::ActiveModel {
-        id: Set(user_id),
-        email: Set("tommie.nygren@gmail.com".to_string()),
-        name: Set("Tommie Nygren".to_string()),
-        role: Set("super_admin".to_string()),
+/// Flips scheduled episodes to published once they are due.
+async fn publish_scheduled_episodes(conn: DatabaseConnection) {
+    let mut interval = tokio::time::interval(Duration::from_secs(PUBLISH_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        match MutationCore::publish_due_episodes(&conn, Utc::now()).await {
+            Ok(0) => {}
+            Ok(count) => println!("Published {count} scheduled episode(s)"),
+            Err(err) => eprintln!("Failed to publish scheduled episodes: {err}"),
+        }
     }
-        .insert(database_connection)
-        .await;
 }
 
-pub fn main(root_path: Option<String>) {
-    let result = start(root_path);
-
-    if let Some(err) = result.err() {
-        println!("Error: {err}");
+/// Deletes sessions from the database once they have expired.
+async fn delete_expired_sessions(conn: DatabaseConnection) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SESSION_CLEANUP_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        match MutationCore::delete_expired_web_sessions(&conn, Utc::now()).await {
+            Ok(0) => {}
+            Ok(count) => println!("Deleted {count} expired session(s)"),
+            Err(err) => eprintln!("Failed to delete expired sessions: {err}"),
+        }
     }
 }
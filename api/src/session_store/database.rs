@@ -0,0 +1,52 @@
+use chrono::Utc;
+use poem::error::InternalServerError;
+use poem::session::SessionStorage;
+use sea_orm::DatabaseConnection;
+use serde_json::Value;
+use service::{Mutation as MutationCore, Query as QueryCore};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Keeps sessions in the `web_session` table. Expired sessions are ignored when loading
+/// and deleted from time to time by a background task started with the server.
+pub struct DatabaseStorage {
+    conn: DatabaseConnection,
+}
+
+impl DatabaseStorage {
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self { conn }
+    }
+}
+
+impl SessionStorage for DatabaseStorage {
+    async fn load_session<'a>(&'a self, session_id: &'a str) -> poem::Result<Option<BTreeMap<String, Value>>> {
+        let session = QueryCore::find_web_session(&self.conn, session_id, Utc::now())
+            .await
+            .map_err(InternalServerError)?;
+        // Sessions that no longer parse are as good as gone.
+        Ok(session.and_then(|session| serde_json::from_str(&session.entries).ok()))
+    }
+
+    async fn update_session<'a>(
+        &'a self,
+        session_id: &'a str,
+        entries: &'a BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> poem::Result<()> {
+        let now = Utc::now();
+        let expires_at = expires
+            .and_then(|expires| chrono::Duration::from_std(expires).ok())
+            .map(|expires| now + expires);
+        let entries = serde_json::to_string(entries).map_err(InternalServerError)?;
+        MutationCore::save_web_session(&self.conn, session_id, entries, expires_at, now)
+            .await
+            .map_err(InternalServerError)
+    }
+
+    async fn remove_session<'a>(&'a self, session_id: &'a str) -> poem::Result<()> {
+        MutationCore::delete_web_session(&self.conn, session_id)
+            .await
+            .map_err(InternalServerError)
+    }
+}
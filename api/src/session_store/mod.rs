@@ -0,0 +1,74 @@
+//! Where sessions are kept. The browser only holds a random session id in its cookie,
+//! everything else stays on the server, so sessions can be ended from the server side.
+//!
+//! The `[sessions]` settings pick the backend, how long a session lives and whether the
+//! cookie is only sent over HTTPS.
+
+mod database;
+mod redis;
+
+use crate::config::{SessionBackend, SessionConfig};
+use poem::session::{CookieConfig, SessionStorage};
+use poem::web::cookie::SameSite;
+use sea_orm::DatabaseConnection;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub use self::database::DatabaseStorage;
+pub use self::redis::RedisStorage;
+
+const COOKIE_NAME: &str = "pod-crab-session";
+
+pub enum SessionStore {
+    Database(DatabaseStorage),
+    Redis(RedisStorage),
+}
+
+impl SessionStorage for SessionStore {
+    async fn load_session<'a>(&'a self, session_id: &'a str) -> poem::Result<Option<BTreeMap<String, Value>>> {
+        match self {
+            SessionStore::Database(storage) => storage.load_session(session_id).await,
+            SessionStore::Redis(storage) => storage.load_session(session_id).await,
+        }
+    }
+
+    async fn update_session<'a>(
+        &'a self,
+        session_id: &'a str,
+        entries: &'a BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> poem::Result<()> {
+        match self {
+            SessionStore::Database(storage) => storage.update_session(session_id, entries, expires).await,
+            SessionStore::Redis(storage) => storage.update_session(session_id, entries, expires).await,
+        }
+    }
+
+    async fn remove_session<'a>(&'a self, session_id: &'a str) -> poem::Result<()> {
+        match self {
+            SessionStore::Database(storage) => storage.remove_session(session_id).await,
+            SessionStore::Redis(storage) => storage.remove_session(session_id).await,
+        }
+    }
+}
+
+/// Sets up the store `config` names.
+pub async fn from_config(config: &SessionConfig, conn: &DatabaseConnection) -> anyhow::Result<SessionStore> {
+    match &config.backend {
+        SessionBackend::Database => Ok(SessionStore::Database(DatabaseStorage::new(conn.clone()))),
+        SessionBackend::Redis { url } => Ok(SessionStore::Redis(RedisStorage::connect(url).await?)),
+    }
+}
+
+/// The session cookie: unreadable by scripts and, with `SameSite=Lax`, not sent along
+/// with requests other sites make, except for plain links such as provider callbacks.
+pub fn cookie_config(config: &SessionConfig) -> CookieConfig {
+    CookieConfig::default()
+        .name(COOKIE_NAME)
+        .path("/")
+        .http_only(true)
+        .secure(config.cookie_secure)
+        .same_site(SameSite::Lax)
+        .max_age(config.ttl)
+}
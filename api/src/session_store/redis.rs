@@ -0,0 +1,61 @@
+use poem::error::InternalServerError;
+use poem::session::SessionStorage;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Keeps every session under its own key, leaving expiry to Redis.
+pub struct RedisStorage {
+    connection: ConnectionManager,
+}
+
+fn key(session_id: &str) -> String {
+    format!("pod-crab:session:{session_id}")
+}
+
+impl RedisStorage {
+    /// Connects to `url`, e.g. `redis://127.0.0.1:6379/0`. The connection is re-established
+    /// by itself when it drops later on.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let client = redis::Client::open(url)?;
+        let connection = client.get_connection_manager().await?;
+        Ok(Self { connection })
+    }
+}
+
+impl SessionStorage for RedisStorage {
+    async fn load_session<'a>(&'a self, session_id: &'a str) -> poem::Result<Option<BTreeMap<String, Value>>> {
+        let entries: Option<String> = self
+            .connection
+            .clone()
+            .get(key(session_id))
+            .await
+            .map_err(InternalServerError)?;
+        Ok(entries.and_then(|entries| serde_json::from_str(&entries).ok()))
+    }
+
+    async fn update_session<'a>(
+        &'a self,
+        session_id: &'a str,
+        entries: &'a BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> poem::Result<()> {
+        let entries = serde_json::to_string(entries).map_err(InternalServerError)?;
+        let mut connection = self.connection.clone();
+        match expires {
+            Some(expires) => connection.set_ex::<_, _, ()>(key(session_id), entries, expires.as_secs()).await,
+            None => connection.set::<_, _, ()>(key(session_id), entries).await,
+        }
+        .map_err(InternalServerError)
+    }
+
+    async fn remove_session<'a>(&'a self, session_id: &'a str) -> poem::Result<()> {
+        self.connection
+            .clone()
+            .del::<_, ()>(key(session_id))
+            .await
+            .map_err(InternalServerError)
+    }
+}
@@ -0,0 +1,153 @@
+//! A stand-in OpenID Connect provider on a random local port. It has no login page: tests
+//! play the browser and ask it for the code it would send the browser back with.
+
+use crate::handlers::auth::providers::ProviderConfig;
+use chrono::{Duration, Utc};
+use openidconnect::core::{
+    CoreIdToken, CoreIdTokenClaims, CoreJwsSigningAlgorithm, CoreRsaPrivateSigningKey,
+};
+use openidconnect::{
+    Audience, EmptyAdditionalClaims, EndUserEmail, IssuerUrl, JsonWebKeyId, Nonce,
+    PkceCodeChallenge, PkceCodeVerifier, PrivateSigningKey, StandardClaims, SubjectIdentifier,
+};
+use poem::http::StatusCode;
+use poem::listener::{Acceptor, Listener, TcpListener};
+use poem::web::{Data, Form, Json};
+use poem::{get, handler, post, EndpointExt, IntoResponse, Response, Route, Server};
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const CLIENT_ID: &str = "pod-crab-test";
+const SIGNING_KEY: &str = include_str!("issuer_key.pem");
+
+/// Who logs in at the provider, the nonce the provider puts into their ID token and the
+/// PKCE challenge the app sent along, which the code verifier must match.
+#[derive(Clone)]
+pub(crate) struct StubLogin {
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub nonce: String,
+    pub code_challenge: String,
+}
+
+struct Issuer {
+    url: String,
+    key: CoreRsaPrivateSigningKey,
+    /// Logins by the code that finishes them. Codes work once.
+    codes: Mutex<HashMap<String, StubLogin>>,
+}
+
+pub(crate) struct StubIssuer {
+    issuer: Arc<Issuer>,
+}
+
+impl StubIssuer {
+    pub async fn start() -> Self {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().copied().unwrap();
+        let key = CoreRsaPrivateSigningKey::from_pem(SIGNING_KEY, Some(JsonWebKeyId::new("test".to_string())))
+            .unwrap();
+        let issuer = Arc::new(Issuer {
+            url: format!("http://{addr}"),
+            key,
+            codes: Mutex::new(HashMap::new()),
+        });
+        let routes = Route::new()
+            .at("/.well-known/openid-configuration", get(discovery))
+            .at("/jwks", get(jwks))
+            .at("/token", post(token))
+            .data(issuer.clone());
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(routes));
+        Self { issuer }
+    }
+
+    /// How the app reaches this provider as `name`, with its public URL `public_url`.
+    pub fn provider_config(&self, name: &str, public_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            name: name.to_string(),
+            label: "Stub".to_string(),
+            issuer: self.issuer.url.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: format!("{public_url}/auth/{name}/callback"),
+            scopes: vec!["email".to_string()],
+        }
+    }
+
+    /// Logs somebody in at the provider and returns the authorization code for them.
+    pub fn issue_code(&self, login: StubLogin) -> String {
+        let code = format!("code-{}", Uuid::new_v4().simple());
+        self.issuer.codes.lock().unwrap().insert(code.clone(), login);
+        code
+    }
+}
+
+#[handler]
+fn discovery(issuer: Data<&Arc<Issuer>>) -> Json<Value> {
+    let url = &issuer.url;
+    Json(json!({
+        "issuer": url,
+        "authorization_endpoint": format!("{url}/authorize"),
+        "token_endpoint": format!("{url}/token"),
+        "jwks_uri": format!("{url}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+    }))
+}
+
+#[handler]
+fn jwks(issuer: Data<&Arc<Issuer>>) -> Json<Value> {
+    Json(json!({ "keys": [issuer.key.as_verification_key()] }))
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    code: String,
+    code_verifier: Option<String>,
+}
+
+fn invalid_grant(description: &str) -> Response {
+    Json(json!({ "error": "invalid_grant", "error_description": description }))
+        .with_status(StatusCode::BAD_REQUEST)
+        .into_response()
+}
+
+#[handler]
+fn token(issuer: Data<&Arc<Issuer>>, Form(request): Form<TokenRequest>) -> Response {
+    // Like real providers, a code is used up by the first attempt, even a failed one.
+    let Some(login) = issuer.codes.lock().unwrap().remove(&request.code) else {
+        return invalid_grant("The code is unknown or was used before");
+    };
+    let challenge = request
+        .code_verifier
+        .map(|verifier| PkceCodeChallenge::from_code_verifier_sha256(&PkceCodeVerifier::new(verifier)));
+    if challenge.as_ref().map(PkceCodeChallenge::as_str) != Some(login.code_challenge.as_str()) {
+        return invalid_grant("The code verifier does not match the code challenge");
+    }
+    let now = Utc::now();
+    let claims = CoreIdTokenClaims::new(
+        IssuerUrl::new(issuer.url.clone()).unwrap(),
+        vec![Audience::new(CLIENT_ID.to_string())],
+        now + Duration::minutes(5),
+        now,
+        StandardClaims::new(SubjectIdentifier::new(login.subject))
+            .set_email(Some(EndUserEmail::new(login.email)))
+            .set_email_verified(Some(login.email_verified)),
+        EmptyAdditionalClaims {},
+    )
+    .set_nonce(Some(Nonce::new(login.nonce)));
+    let id_token = CoreIdToken::new(claims, &issuer.key, CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256, None, None)
+        .unwrap();
+    Json(json!({
+        "access_token": format!("access-{}", Uuid::new_v4().simple()),
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    }))
+    .into_response()
+}
@@ -0,0 +1,154 @@
+//! The whole app on a throwaway database, for tests that go through the routes.
+
+use crate::config::{Config, Profile};
+use crate::handlers::auth::providers::OidcProviders;
+use crate::session_store::{DatabaseStorage, SessionStore};
+use crate::{app, load_templates, AppState};
+use chrono::Utc;
+use entities::user::Model as User;
+use mailer::MailBackend;
+use migration::{Migrator, MigratorTrait};
+use poem::http::{header, Method};
+use poem::session::SessionStorage;
+use poem::{Endpoint, EndpointExt, Request, Response};
+use sea_orm::Database;
+use service::{Mutation as MutationCore, SessionClient};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+
+pub(crate) mod issuer;
+
+const COOKIE_PREFIX: &str = "pod-crab-session=";
+
+pub(crate) struct TestApp {
+    pub state: AppState,
+    endpoint: poem::endpoint::BoxEndpoint<'static>,
+    /// The session cookie, as a browser would keep it.
+    cookie: Mutex<Option<String>>,
+    /// Holds the database, media and mail; removed when the app is dropped.
+    dir: TempDir,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    /// The app with the checked in `test` profile, changed by `configure` before anything is
+    /// set up. Neither the environment nor `.env` or `config/local.toml` get a say.
+    pub async fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let dir = TempDir::new().unwrap();
+        let mut config = Config::load_isolated(&root, Profile::Test, |_| None).unwrap();
+        config.database.url = format!("sqlite://{}?mode=rwc", dir.path().join("test.db").display());
+        config.media = ::media::MediaConfig::Local {
+            root: dir.path().join("media").display().to_string(),
+            base_url: format!("{}/media/", config.server.public_url),
+        };
+        config.mail.backend = MailBackend::File {
+            dir: dir.path().join("mail").display().to_string(),
+        };
+        configure(&mut config);
+
+        let conn = Database::connect(&config.database.url).await.unwrap();
+        Migrator::up(&conn, None).await.unwrap();
+        let providers = OidcProviders::discover(config.auth.providers.clone()).await;
+        let state = AppState {
+            templates: load_templates(root.to_str().unwrap()),
+            media: ::media::from_config(&config.media).unwrap(),
+            mailer: mailer::from_config(&config.mail).unwrap(),
+            conn: conn.clone(),
+            config: Arc::new(config),
+        };
+        let session_store = SessionStore::Database(DatabaseStorage::new(conn));
+        let endpoint = app(state.clone(), providers, session_store, root.to_str().unwrap())
+            .map_to_response()
+            .boxed();
+        Self { state, endpoint, cookie: Mutex::new(None), dir }
+    }
+
+    /// Sends `req` along with the session cookie and keeps the cookie the response sets.
+    pub async fn send(&self, mut req: Request) -> Response {
+        if let Some(cookie) = self.cookie.lock().unwrap().as_ref() {
+            req.headers_mut().insert(header::COOKIE, cookie.parse().unwrap());
+        }
+        let response = self.endpoint.get_response(req).await;
+        for value in response.headers().get_all(header::SET_COOKIE) {
+            let value = value.to_str().unwrap();
+            if let Some(cookie) = value.split(';').next().filter(|cookie| cookie.starts_with(COOKIE_PREFIX)) {
+                *self.cookie.lock().unwrap() = Some(cookie.to_string());
+            }
+        }
+        response
+    }
+
+    pub async fn get(&self, uri: &str) -> Response {
+        self.send(Request::builder().uri_str(uri).finish()).await
+    }
+
+    /// Sends `form` URL encoded.
+    pub async fn send_form(&self, method: Method, uri: &str, form: &[(&str, &str)]) -> Response {
+        let req = Request::builder()
+            .method(method)
+            .uri_str(uri)
+            .content_type("application/x-www-form-urlencoded")
+            .body(serde_urlencoded::to_string(form).unwrap());
+        self.send(req).await
+    }
+
+    /// Creates an account with `role`.
+    pub async fn create_user(&self, email: &str, role: &str) -> User {
+        let user = MutationCore::create_user(&self.state.conn, email, email).await.unwrap();
+        MutationCore::change_user_role(&self.state.conn, user, role).await.unwrap()
+    }
+
+    /// Logs the browser in as `user`, like a finished login would.
+    pub async fn log_in(&self, user: &User) {
+        let client = SessionClient { ip_address: None, user_agent: None };
+        let user_session = MutationCore::start_user_session(&self.state.conn, user.id, client, None, Utc::now())
+            .await
+            .unwrap();
+        let entries = BTreeMap::from([
+            ("current_user".to_string(), serde_json::to_value(user).unwrap()),
+            ("session_id".to_string(), serde_json::to_value(user_session.id).unwrap()),
+        ]);
+        let session_id = user_session.id.simple().to_string();
+        DatabaseStorage::new(self.state.conn.clone())
+            .update_session(&session_id, &entries, None)
+            .await
+            .unwrap();
+        *self.cookie.lock().unwrap() = Some(format!("{COOKIE_PREFIX}{session_id}"));
+    }
+
+    /// Forgets the session cookie.
+    pub fn log_out(&self) {
+        *self.cookie.lock().unwrap() = None;
+    }
+
+    /// The mail sent so far, oldest first, once there are `count` messages. Mail goes out
+    /// in the background, so this waits a while for it.
+    pub async fn sent_mail(&self, count: usize) -> Vec<String> {
+        let dir = self.dir.path().join("mail");
+        for _ in 0..100 {
+            let mut names: Vec<_> = std::fs::read_dir(&dir)
+                .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+                .unwrap_or_default();
+            names.sort();
+            let mail: Vec<String> = names.iter().map(|name| std::fs::read_to_string(name).unwrap()).collect();
+            // A message still being written reads as empty.
+            if mail.len() >= count && mail.iter().all(|mail| !mail.is_empty()) {
+                return mail;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("expected {count} messages in {}", dir.display());
+    }
+}
+
+/// The body of `response` as text.
+pub(crate) async fn body_text(response: Response) -> String {
+    response.into_body().into_string().await.unwrap()
+}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+use super::sea_orm_active_enums::EpisodeStatus;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -7,16 +8,52 @@ use serde::{Deserialize, Serialize};
 #[sea_orm(table_name = "episode")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
+    #[serde(skip_deserializing)]
     pub id: Uuid,
     pub title: String,
     pub summary: String,
     pub tags: String,
     pub url: Option<String>,
+    #[serde(skip_deserializing)]
     pub user_id: Uuid,
+    #[serde(skip_deserializing)]
+    pub media_key: Option<String>,
+    #[serde(skip_deserializing)]
+    pub media_size: Option<i64>,
+    #[serde(skip_deserializing)]
+    pub media_type: Option<String>,
+    pub show_id: Option<Uuid>,
+    #[serde(skip_deserializing)]
+    pub status: EpisodeStatus,
+    #[serde(skip_deserializing)]
+    pub published_at: Option<DateTimeUtc>,
+    #[serde(skip_deserializing)]
+    pub scheduled_for: Option<DateTimeUtc>,
+    #[serde(skip_deserializing)]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_deserializing)]
+    pub bitrate: Option<i32>,
+    #[serde(skip_deserializing)]
+    pub sample_rate: Option<i32>,
+    #[serde(skip_deserializing)]
+    pub channels: Option<i16>,
+    #[serde(skip_deserializing)]
+    pub codec: Option<String>,
+    #[serde(skip_deserializing)]
+    pub artwork_url: Option<String>,
+    /// Chapters found in the uploaded file, as JSON.
+    #[sea_orm(column_type = "Text", nullable)]
+    #[serde(skip_deserializing)]
+    pub embedded_chapters: Option<String>,
+    /// The GUID the episode had in the feed it was imported from. Feeds use the id otherwise.
+    #[serde(skip_deserializing)]
+    pub guid: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::chapter::Entity")]
+    Chapter,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -25,6 +62,34 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     User,
+    #[sea_orm(
+        belongs_to = "super::show::Entity",
+        from = "Column::ShowId",
+        to = "super::show::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Show,
+    #[sea_orm(has_many = "super::transcript::Entity")]
+    Transcript,
+}
+
+impl Related<super::chapter::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Chapter.def()
+    }
+}
+
+impl Related<super::show::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Show.def()
+    }
+}
+
+impl Related<super::transcript::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transcript.def()
+    }
 }
 
 impl Related<super::user::Entity> for Entity {
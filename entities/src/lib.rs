@@ -2,7 +2,20 @@
 
 pub mod prelude;
 
+pub mod api_token;
+pub mod chapter;
 pub mod episode;
+pub mod invitation;
 pub mod member;
+pub mod one_time_token;
 pub mod post;
+pub mod role;
+pub mod role_permission;
+pub mod sea_orm_active_enums;
+pub mod show;
+pub mod show_user;
+pub mod transcript;
 pub mod user;
+pub mod user_identity;
+pub mod user_session;
+pub mod web_session;
@@ -1,6 +1,18 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub use super::api_token::Entity as ApiToken;
+pub use super::chapter::Entity as Chapter;
 pub use super::episode::Entity as Episode;
+pub use super::invitation::Entity as Invitation;
 pub use super::member::Entity as Member;
+pub use super::one_time_token::Entity as OneTimeToken;
 pub use super::post::Entity as Post;
+pub use super::role::Entity as Role;
+pub use super::role_permission::Entity as RolePermission;
+pub use super::show::Entity as Show;
+pub use super::show_user::Entity as ShowUser;
+pub use super::transcript::Entity as Transcript;
 pub use super::user::Entity as User;
+pub use super::user_identity::Entity as UserIdentity;
+pub use super::user_session::Entity as UserSession;
+pub use super::web_session::Entity as WebSession;
@@ -11,12 +11,37 @@ pub struct Model {
     pub email: String,
     pub name: String,
     pub role: String,
+    /// Argon2id hash of the local password. Users who only log in through a provider or
+    /// with magic links have none.
+    #[serde(skip_serializing)]
+    pub password_hash: Option<String>,
+    pub email_verified_at: Option<DateTimeUtc>,
+    /// Set while the user may not log in.
+    pub deactivated_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_token::Entity")]
+    ApiToken,
     #[sea_orm(has_many = "super::episode::Entity")]
     Episode,
+    #[sea_orm(has_many = "super::invitation::Entity")]
+    Invitation,
+    #[sea_orm(has_many = "super::one_time_token::Entity")]
+    OneTimeToken,
+    #[sea_orm(has_many = "super::show_user::Entity")]
+    ShowUser,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentity,
+    #[sea_orm(has_many = "super::user_session::Entity")]
+    UserSession,
+}
+
+impl Related<super::api_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiToken.def()
+    }
 }
 
 impl Related<super::episode::Entity> for Entity {
@@ -25,4 +50,44 @@ impl Related<super::episode::Entity> for Entity {
     }
 }
 
+impl Related<super::invitation::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Invitation.def()
+    }
+}
+
+impl Related<super::one_time_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OneTimeToken.def()
+    }
+}
+
+impl Related<super::show_user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ShowUser.def()
+    }
+}
+
+impl Related<super::user_identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentity.def()
+    }
+}
+
+impl Related<super::user_session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSession.def()
+    }
+}
+
+impl Related<super::show::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::show_user::Relation::Show.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::show_user::Relation::User.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,44 @@
+use crate::{build_message, Email, MailError, Mailer};
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::message::Mailbox;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Writes every message into a directory, for tests and for looking at mail without a mail
+/// server. Files hold the headers that matter and the body as plain text, without the
+/// transfer encoding a real message would get, so links can be read straight from them.
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: Mailbox) -> Self {
+        Self {
+            dir: dir.into(),
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        // Build the message anyway so bad addresses fail the same way as in production.
+        build_message(&self.from, &email)?;
+        let contents = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}",
+            self.from, email.to, email.subject, email.body
+        );
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // Sortable by the time they were sent.
+        let file_name = format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%dT%H%M%S%.6fZ"),
+            Uuid::new_v4()
+        );
+        tokio::fs::write(self.dir.join(file_name), contents).await?;
+        Ok(())
+    }
+}
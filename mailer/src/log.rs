@@ -0,0 +1,28 @@
+use crate::{build_message, Email, MailError, Mailer};
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+
+/// Prints mail to stdout instead of sending it. Meant for development: the links in the
+/// messages let anybody who can read the log in as the recipient.
+pub struct LogMailer {
+    from: Mailbox,
+}
+
+impl LogMailer {
+    pub fn new(from: Mailbox) -> Self {
+        Self { from }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        // Build the message anyway so bad addresses fail the same way as in production.
+        build_message(&self.from, &email)?;
+        println!(
+            "Mail from {} to {}: {}\n\n{}\n",
+            self.from, email.to, email.subject, email.body
+        );
+        Ok(())
+    }
+}
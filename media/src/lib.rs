@@ -0,0 +1,129 @@
+//! Storage for uploaded episode media.
+//!
+//! Handlers only talk to the [`MediaStore`] trait; which backend is used is decided once at
+//! startup by [`from_config`].
+
+mod local;
+pub mod probe;
+mod s3_store;
+
+pub use local::LocalMediaStore;
+pub use s3_store::S3MediaStore;
+
+use async_trait::async_trait;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MediaError {
+    #[error("invalid media key: {0}")]
+    InvalidKey(String),
+    #[error("media not found: {0}")]
+    NotFound(String),
+    #[error("media store misconfigured: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    S3(#[from] s3::error::S3Error),
+}
+
+/// What a backend knows about an object after it has been stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredMedia {
+    pub key: String,
+    pub size: u64,
+    pub mime_type: String,
+}
+
+/// Part of an object, from `start` to `end` inclusive, like in an HTTP `Range` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Object contents, read as they come in from the backend.
+pub type MediaReader = Pin<Box<dyn AsyncRead + Send>>;
+
+#[async_trait]
+pub trait MediaStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8], mime_type: &str) -> Result<StoredMedia, MediaError>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MediaError>;
+
+    /// Size of the object in bytes.
+    async fn size(&self, key: &str) -> Result<u64, MediaError>;
+
+    /// Reads `range` of the object, or all of it, without holding it in memory.
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<MediaReader, MediaError>;
+
+    async fn delete(&self, key: &str) -> Result<(), MediaError>;
+
+    /// Absolute URL listeners can download the object from.
+    fn public_url(&self, key: &str) -> String;
+}
+
+/// Builds the object key an episode's audio file is stored under.
+pub fn episode_media_key(episode_id: &str, file_name: &str) -> String {
+    let file_name: String = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let file_name = file_name.trim_start_matches('.');
+    let file_name = if file_name.is_empty() { "audio" } else { file_name };
+    format!("episodes/{episode_id}/{file_name}")
+}
+
+/// Rejects keys that are empty, absolute or try to climb out of the store.
+pub(crate) fn validate_key(key: &str) -> Result<(), MediaError> {
+    if key.is_empty()
+        || key.starts_with('/')
+        || key.contains('\\')
+        || key.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(MediaError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+/// Which backend keeps the media, with its settings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MediaConfig {
+    /// Files under `root`, served by the app itself below `base_url`.
+    Local { root: String, base_url: String },
+    /// An S3 compatible bucket. Objects are linked at `public_url` when it is set, e.g. for
+    /// a CDN in front of the bucket.
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+        access_key: String,
+        secret_key: String,
+        public_url: Option<String>,
+    },
+}
+
+/// Creates the store `config` describes.
+pub fn from_config(config: &MediaConfig) -> Result<Arc<dyn MediaStore>, MediaError> {
+    match config {
+        MediaConfig::Local { root, base_url } => {
+            Ok(Arc::new(LocalMediaStore::new(root, base_url.as_str())))
+        }
+        MediaConfig::S3 { bucket, region, endpoint, access_key, secret_key, public_url } => {
+            let store = S3MediaStore::new(
+                bucket,
+                region,
+                endpoint.as_deref(),
+                access_key,
+                secret_key,
+                public_url.clone(),
+            )?;
+            Ok(Arc::new(store))
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{validate_key, ByteRange, MediaError, MediaReader, MediaStore, StoredMedia};
+use async_trait::async_trait;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Keeps media as plain files below a root directory.
+pub struct LocalMediaStore {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalMediaStore {
+    pub fn new(root: impl Into<PathBuf>, base_url: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, MediaError> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+fn not_found(key: &str) -> impl FnOnce(std::io::Error) -> MediaError + '_ {
+    move |err| match err.kind() {
+        ErrorKind::NotFound => MediaError::NotFound(key.to_string()),
+        _ => err.into(),
+    }
+}
+
+#[async_trait]
+impl MediaStore for LocalMediaStore {
+    async fn put(&self, key: &str, bytes: &[u8], mime_type: &str) -> Result<StoredMedia, MediaError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, bytes).await?;
+
+        Ok(StoredMedia {
+            key: key.to_string(),
+            size: bytes.len() as u64,
+            mime_type: mime_type.to_string(),
+        })
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MediaError> {
+        let path = self.path_for(key)?;
+        tokio::fs::read(&path).await.map_err(not_found(key))
+    }
+
+    async fn size(&self, key: &str) -> Result<u64, MediaError> {
+        let path = self.path_for(key)?;
+        let metadata = tokio::fs::metadata(&path).await.map_err(not_found(key))?;
+        Ok(metadata.len())
+    }
+
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<MediaReader, MediaError> {
+        let path = self.path_for(key)?;
+        let mut file = tokio::fs::File::open(&path).await.map_err(not_found(key))?;
+        match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start)).await?;
+                Ok(Box::pin(file.take(range.end - range.start + 1)))
+            }
+            None => Ok(Box::pin(file)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), MediaError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{key}", self.base_url)
+    }
+}
@@ -0,0 +1,110 @@
+"""Writes the probe test fixtures: valid container headers around silent payloads.
+
+Run from this directory with `python3 generate.py`.
+"""
+import struct
+
+
+def syncsafe(n):
+    return bytes([(n >> 21) & 0x7F, (n >> 14) & 0x7F, (n >> 7) & 0x7F, n & 0x7F])
+
+
+def id3_frame(id, data):
+    return id.encode() + struct.pack(">I", len(data)) + b"\0\0" + data
+
+
+def tit2(title):
+    return id3_frame("TIT2", b"\x03" + title.encode("utf8"))
+
+
+# MP3: an ID3v2.3 tag with artwork and two chapters (stored out of order), then
+# MPEG-1 layer III frames at 128 kbps, 44.1 kHz, stereo. Each frame is 417 bytes.
+apic = id3_frame("APIC", b"\x00image/png\x00\x03cover\x00" + b"\x89PNG" + b"x" * 60)
+intro = id3_frame(
+    "CHAP",
+    b"ch1\x00" + struct.pack(">IIII", 0, 60000, 0xFFFFFFFF, 0xFFFFFFFF)
+    + tit2("Intro")
+    + id3_frame("WXXX", b"\x00\x00https://example.com/intro"),
+)
+second = id3_frame(
+    "CHAP",
+    b"ch2\x00" + struct.pack(">IIII", 60000, 120000, 0xFFFFFFFF, 0xFFFFFFFF)
+    + id3_frame("TIT2", b"\x01\xff\xfe" + "Zwei".encode("utf-16-le") + b"\0\0"),
+)
+body = tit2("Episode") + apic + second + intro + b"\0" * 16
+tag = b"ID3\x03\x00\x00" + syncsafe(len(body)) + body
+header = b"\xff\xfb\x90\x00"
+frame = header + b"\0" * (417 - 4)
+with open("cbr.mp3", "wb") as f:
+    f.write(tag + frame * 40)
+
+# MP3 with a Xing header in the first frame claiming 2000 frames.
+xing = bytearray(frame)
+xing[36:40] = b"Xing"
+xing[40:44] = struct.pack(">I", 3)
+xing[44:48] = struct.pack(">I", 2000)
+xing[48:52] = struct.pack(">I", 2000 * 417)
+with open("vbr.mp3", "wb") as f:
+    f.write(bytes(xing) + frame * 10)
+
+
+# M4A: 90 seconds of AAC per mdhd, Nero chapters and cover art.
+def atom(kind, body):
+    return struct.pack(">I", 8 + len(body)) + kind + body
+
+
+mvhd = atom(b"mvhd", b"\0" * 4 + b"\0" * 8 + struct.pack(">II", 1000, 95000) + b"\0" * 80)
+mdhd = atom(b"mdhd", b"\0" * 4 + b"\0" * 8 + struct.pack(">II", 44100, 44100 * 90) + b"\0" * 4)
+hdlr = atom(b"hdlr", b"\0" * 8 + b"soun" + b"\0" * 13)
+mp4a = atom(
+    b"mp4a",
+    b"\0" * 6 + struct.pack(">H", 1) + b"\0" * 8 + struct.pack(">HHHH", 2, 16, 0, 0)
+    + struct.pack(">I", 44100 << 16),
+)
+stsd = atom(b"stsd", b"\0" * 4 + struct.pack(">I", 1) + mp4a)
+trak = atom(b"trak", atom(b"mdia", mdhd + hdlr + atom(b"minf", atom(b"stbl", stsd))))
+chpl = atom(
+    b"chpl",
+    b"\x01\0\0\0" + b"\0" * 4 + bytes([2])
+    + struct.pack(">Q", 0) + bytes([5]) + b"Start"
+    + struct.pack(">Q", 30 * 10_000_000) + bytes([3]) + b"Mid",
+)
+covr = atom(b"covr", atom(b"data", struct.pack(">II", 13, 0) + b"\xff\xd8" + b"j" * 40))
+udta = atom(b"udta", chpl + atom(b"meta", b"\0" * 4 + atom(b"ilst", covr)))
+moov = atom(b"moov", mvhd + trak + udta)
+with open("a.m4a", "wb") as f:
+    f.write(atom(b"ftyp", b"M4A \0\0\0\0") + moov + atom(b"mdat", b"\0" * 4000))
+
+
+def page(granule, serial, sequence, packet, header_type=0):
+    segments = []
+    n = len(packet)
+    while n >= 255:
+        segments.append(255)
+        n -= 255
+    segments.append(n)
+    return (
+        b"OggS" + bytes([0, header_type])
+        + struct.pack("<qIII", granule, serial, sequence, 0)
+        + bytes([len(segments)]) + bytes(segments) + packet
+    )
+
+
+# Opus: 61 seconds after the 312 sample pre-skip, from a 44.1 kHz source.
+opus_head = b"OpusHead" + bytes([1, 2]) + struct.pack("<HIhB", 312, 44100, 0, 0)
+with open("a.opus", "wb") as f:
+    f.write(
+        page(0, 7, 0, opus_head, 2)
+        + page(0, 7, 1, b"OpusTags" + b"\0" * 8)
+        + page(48000 * 30, 7, 2, b"\0" * 300)
+        + page(48000 * 61 + 312, 7, 3, b"\0" * 300, 4)
+    )
+
+# Vorbis: 10 seconds of mono 22.05 kHz.
+vorbis_id = b"\x01vorbis" + struct.pack("<IBIiiiBB", 0, 1, 22050, 0, 64000, 0, 0xB8, 1)
+with open("a.ogg", "wb") as f:
+    f.write(
+        page(0, 9, 0, vorbis_id, 2)
+        + page(22050 * 4, 9, 1, b"\0" * 300)
+        + page(22050 * 10, 9, 2, b"\0" * 300, 4)
+    )
@@ -0,0 +1,204 @@
+//! ID3v2.2 to v2.4 tags, as far as podcasts care about them: artwork and chapters.
+
+use super::{EmbeddedChapter, EmbeddedImage, Reader};
+
+#[derive(Debug, Default)]
+pub(super) struct Id3Tag {
+    /// Bytes taken up by the tag, header and footer included.
+    pub size: usize,
+    pub artwork: Option<EmbeddedImage>,
+    pub chapters: Vec<EmbeddedChapter>,
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, b| (acc << 7) | (*b as usize & 0x7f))
+}
+
+/// Parses the tag at the start of `bytes`, if there is one.
+pub(super) fn parse(bytes: &[u8]) -> Option<Id3Tag> {
+    if bytes.len() < 10 || !bytes.starts_with(b"ID3") {
+        return None;
+    }
+    let version = bytes[3];
+    let flags = bytes[5];
+    let body_size = syncsafe(&bytes[6..10]);
+    let footer = if version >= 4 && flags & 0x10 != 0 { 10 } else { 0 };
+    let mut tag = Id3Tag {
+        size: 10 + body_size + footer,
+        ..Default::default()
+    };
+
+    let end = (10 + body_size).min(bytes.len());
+    let mut body = &bytes[10..end];
+    if flags & 0x40 != 0 && version >= 3 {
+        let mut reader = Reader::new(body);
+        let extended = match version {
+            3 => reader.u32_be()? as usize + 4,
+            _ => syncsafe(reader.take(4)?),
+        };
+        body = body.get(extended..)?;
+    }
+
+    for frame in frames(body, version) {
+        match frame.id {
+            "APIC" | "PIC" if tag.artwork.is_none() => {
+                tag.artwork = picture(frame.data, frame.id == "PIC");
+            }
+            "CHAP" => {
+                if let Some(chapter) = chapter(frame.data, version) {
+                    tag.chapters.push(chapter);
+                }
+            }
+            _ => {}
+        }
+    }
+    tag.chapters.sort_by_key(|chapter| chapter.start_ms);
+    Some(tag)
+}
+
+struct Frame<'a> {
+    id: &'a str,
+    data: &'a [u8],
+}
+
+fn frames(body: &[u8], version: u8) -> Vec<Frame<'_>> {
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut frames = Vec::new();
+    let mut reader = Reader::new(body);
+
+    while reader.remaining() >= header_len {
+        let header = reader.take(header_len).unwrap_or_default();
+        // Padding, or garbage: either way there are no more frames.
+        let Ok(id) = std::str::from_utf8(&header[..id_len]) else {
+            break;
+        };
+        if !id.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+            break;
+        }
+        let size = match version {
+            2 => u32::from_be_bytes([0, header[3], header[4], header[5]]) as usize,
+            3 => u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize,
+            _ => syncsafe(&header[4..8]),
+        };
+        let Some(data) = reader.take(size) else {
+            break;
+        };
+        frames.push(Frame { id, data });
+    }
+    frames
+}
+
+/// Splits off a string terminated according to `encoding`.
+fn terminated(data: &[u8], encoding: u8) -> (&[u8], &[u8]) {
+    if encoding == 1 || encoding == 2 {
+        let mut i = 0;
+        while i + 1 < data.len() {
+            if data[i] == 0 && data[i + 1] == 0 {
+                return (&data[..i], &data[i + 2..]);
+            }
+            i += 2;
+        }
+        (data, &[])
+    } else {
+        match data.iter().position(|b| *b == 0) {
+            Some(i) => (&data[..i], &data[i + 1..]),
+            None => (data, &[]),
+        }
+    }
+}
+
+fn decode_text(data: &[u8], encoding: u8) -> String {
+    let text = match encoding {
+        0 => data.iter().map(|b| *b as char).collect(),
+        1 | 2 => {
+            let (big_endian, data) = match data {
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                _ => (encoding == 2, data),
+            };
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| {
+                    if big_endian {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data).into_owned(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+fn text_frame(data: &[u8]) -> Option<String> {
+    let (encoding, text) = data.split_first()?;
+    Some(decode_text(terminated(text, *encoding).0, *encoding))
+}
+
+fn user_url_frame(data: &[u8]) -> Option<String> {
+    let (encoding, rest) = data.split_first()?;
+    let (_description, url) = terminated(rest, *encoding);
+    let url = decode_text(terminated(url, 0).0, 0);
+    (!url.is_empty()).then_some(url)
+}
+
+fn picture(data: &[u8], v2_2: bool) -> Option<EmbeddedImage> {
+    let (encoding, rest) = data.split_first()?;
+    let (mime_type, rest) = if v2_2 {
+        let format = rest.get(..3)?;
+        let mime_type = match &format.to_ascii_uppercase()[..] {
+            b"PNG" => "image/png",
+            _ => "image/jpeg",
+        };
+        (mime_type.to_string(), &rest[3..])
+    } else {
+        let (mime_type, rest) = terminated(rest, 0);
+        (decode_text(mime_type, 0), rest)
+    };
+    let (_picture_type, rest) = rest.split_first()?;
+    let (_description, image) = terminated(rest, *encoding);
+    if image.is_empty() {
+        return None;
+    }
+
+    let mime_type = match mime_type.as_str() {
+        "" | "image/jpg" => "image/jpeg".to_string(),
+        mime_type if !mime_type.contains('/') => format!("image/{}", mime_type.to_ascii_lowercase()),
+        _ => mime_type,
+    };
+    Some(EmbeddedImage {
+        mime_type,
+        data: image.to_vec(),
+    })
+}
+
+fn chapter(data: &[u8], version: u8) -> Option<EmbeddedChapter> {
+    let (element_id, rest) = terminated(data, 0);
+    let mut reader = Reader::new(rest);
+    let start_ms = reader.u32_be()? as u64;
+    let end_ms = reader.u32_be()? as u64;
+    reader.skip(8)?;
+
+    let mut chapter = EmbeddedChapter {
+        start_ms,
+        end_ms: (end_ms > start_ms).then_some(end_ms),
+        title: String::new(),
+        url: None,
+        image: None,
+    };
+    for frame in frames(reader.rest(), version) {
+        match frame.id {
+            "TIT2" => chapter.title = text_frame(frame.data).unwrap_or_default(),
+            "WXXX" => chapter.url = user_url_frame(frame.data),
+            "APIC" => chapter.image = picture(frame.data, false),
+            _ => {}
+        }
+    }
+    if chapter.title.is_empty() {
+        chapter.title = decode_text(element_id, 0);
+    }
+    Some(chapter)
+}
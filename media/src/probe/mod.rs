@@ -0,0 +1,141 @@
+//! Reads technical metadata out of uploaded audio files.
+//!
+//! Only the container headers are parsed, nothing is decoded, so probing an hour long
+//! episode is cheap. Supported are MP3 (with ID3v2 tags), MP4/M4A and Ogg (Opus, Vorbis).
+
+mod id3;
+mod mp3;
+mod mp4;
+mod ogg;
+#[cfg(test)]
+mod tests;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ProbeError {
+    #[error("not a supported audio file")]
+    UnsupportedFormat,
+    #[error("corrupt {0} file: {1}")]
+    Corrupt(&'static str, &'static str),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddedImage {
+    pub mime_type: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+/// A chapter as found in the file, e.g. an ID3 `CHAP` frame.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddedChapter {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+    pub title: String,
+    pub url: Option<String>,
+    #[serde(skip)]
+    pub image: Option<EmbeddedImage>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioMetadata {
+    /// Short codec name such as `mp3`, `aac`, `opus` or `vorbis`.
+    pub codec: String,
+    pub mime_type: &'static str,
+    pub duration_ms: u64,
+    /// Average bitrate in bits per second.
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub chapters: Vec<EmbeddedChapter>,
+    pub artwork: Option<EmbeddedImage>,
+}
+
+impl AudioMetadata {
+    pub fn duration_seconds(&self) -> u64 {
+        (self.duration_ms + 500) / 1000
+    }
+}
+
+/// Identifies the container from its magic bytes and parses it.
+pub fn probe(bytes: &[u8]) -> Result<AudioMetadata, ProbeError> {
+    if bytes.starts_with(b"OggS") {
+        ogg::probe(bytes)
+    } else if bytes.len() >= 8 && &bytes[4..8] == b"ftyp" {
+        mp4::probe(bytes)
+    } else if bytes.starts_with(b"ID3") || mp3::find_first_frame(bytes, 0).is_some() {
+        mp3::probe(bytes)
+    } else {
+        Err(ProbeError::UnsupportedFormat)
+    }
+}
+
+/// Average bitrate of `audio_bytes` bytes played over `duration_ms`.
+fn average_bitrate(audio_bytes: u64, duration_ms: u64) -> u32 {
+    if duration_ms == 0 {
+        return 0;
+    }
+    (audio_bytes * 8 * 1000 / duration_ms).min(u32::MAX as u64) as u32
+}
+
+/// Bounds checked big and little endian reads over a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.position)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.position.min(self.bytes.len())..]
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let slice = self.bytes.get(self.position..end)?;
+        self.position = end;
+        Some(slice)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16_be(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32_be(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64_be(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn u16_le(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32_le(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i64_le(&mut self) -> Option<i64> {
+        self.take(8)
+            .map(|b| i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+}
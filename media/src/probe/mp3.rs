@@ -0,0 +1,173 @@
+//! MPEG audio (layers I to III) behind an optional ID3v2 tag.
+
+use super::{average_bitrate, id3, AudioMetadata, ProbeError, Reader};
+
+const BITRATES_V1: [[u32; 15]; 3] = [
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+];
+const BITRATES_V2: [[u32; 15]; 3] = [
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// How far past the tag we look for the first frame before giving up.
+const SYNC_SEARCH_LIMIT: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct FrameHeader {
+    mpeg1: bool,
+    layer: u8,
+    /// Kilobits per second.
+    bitrate: u32,
+    sample_rate: u32,
+    mono: bool,
+    padding: bool,
+}
+
+impl FrameHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let header = bytes.get(..4)?;
+        if header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+        let version = (header[1] >> 3) & 0b11;
+        let layer = match (header[1] >> 1) & 0b11 {
+            0b11 => 1,
+            0b10 => 2,
+            0b01 => 3,
+            _ => return None,
+        };
+        let bitrate_index = (header[2] >> 4) as usize;
+        let sample_rate_index = ((header[2] >> 2) & 0b11) as usize;
+        if version == 0b01 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+            return None;
+        }
+
+        let mpeg1 = version == 0b11;
+        let bitrate = if mpeg1 {
+            BITRATES_V1[layer as usize - 1][bitrate_index]
+        } else {
+            BITRATES_V2[layer as usize - 1][bitrate_index]
+        };
+        let sample_rate = [44100, 48000, 32000][sample_rate_index]
+            >> match version {
+                0b11 => 0,
+                0b10 => 1,
+                _ => 2,
+            };
+
+        Some(FrameHeader {
+            mpeg1,
+            layer,
+            bitrate,
+            sample_rate,
+            mono: header[3] >> 6 == 0b11,
+            padding: header[2] & 0b10 != 0,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.mpeg1) {
+            (1, _) => 384,
+            (3, false) => 576,
+            _ => 1152,
+        }
+    }
+
+    fn frame_len(&self) -> usize {
+        let padding = match (self.padding, self.layer) {
+            (false, _) => 0,
+            (true, 1) => 4,
+            (true, _) => 1,
+        };
+        (self.samples_per_frame() / 8 * self.bitrate * 1000 / self.sample_rate) as usize + padding
+    }
+
+    /// Where a Xing/Info header would sit, counted from the start of the frame.
+    fn side_info_end(&self) -> usize {
+        4 + match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        }
+    }
+}
+
+/// Finds the first frame header at or after `from` that is followed by another valid one.
+pub(super) fn find_first_frame(bytes: &[u8], from: usize) -> Option<(usize, FrameHeader)> {
+    let end = bytes.len().min(from.saturating_add(SYNC_SEARCH_LIMIT));
+    (from..end).find_map(|offset| {
+        let header = FrameHeader::parse(&bytes[offset..])?;
+        let next = offset + header.frame_len();
+        // A file holding a single frame has nothing to confirm the sync against.
+        let confirmed = FrameHeader::parse(bytes.get(next..).unwrap_or_default()).is_some()
+            || (offset == from && next == bytes.len());
+        confirmed.then_some((offset, header))
+    })
+}
+
+/// Frame count from a Xing/Info or VBRI header, which VBR encoders put in the first frame.
+fn vbr_frame_count(frame: &[u8], header: &FrameHeader) -> Option<(u64, Option<u64>)> {
+    let xing_offset = header.side_info_end();
+    if let Some(tag) = frame.get(xing_offset..xing_offset + 4) {
+        if tag == b"Xing" || tag == b"Info" {
+            let mut reader = Reader::new(&frame[xing_offset + 4..]);
+            let flags = reader.u32_be()?;
+            let frames = if flags & 1 != 0 { reader.u32_be()? as u64 } else { return None };
+            let bytes = if flags & 2 != 0 { reader.u32_be().map(u64::from) } else { None };
+            return Some((frames, bytes));
+        }
+    }
+    if frame.get(36..40) == Some(b"VBRI") {
+        let mut reader = Reader::new(&frame[40..]);
+        reader.skip(6)?;
+        let bytes = reader.u32_be()? as u64;
+        let frames = reader.u32_be()? as u64;
+        return Some((frames, Some(bytes)));
+    }
+    None
+}
+
+pub(super) fn probe(bytes: &[u8]) -> Result<AudioMetadata, ProbeError> {
+    let tag = id3::parse(bytes).unwrap_or_default();
+    let (offset, header) = find_first_frame(bytes, tag.size)
+        .ok_or(ProbeError::Corrupt("MP3", "no MPEG audio frames found"))?;
+
+    let mut audio_end = bytes.len();
+    if audio_end >= 128 && &bytes[audio_end - 128..audio_end - 125] == b"TAG" {
+        audio_end -= 128;
+    }
+    let audio_bytes = audio_end.saturating_sub(offset) as u64;
+
+    let (duration_ms, bitrate) = match vbr_frame_count(&bytes[offset..], &header) {
+        Some((frames, stream_bytes)) if frames > 0 => {
+            let samples = frames * header.samples_per_frame() as u64;
+            let duration_ms = samples * 1000 / header.sample_rate as u64;
+            let bitrate = average_bitrate(stream_bytes.unwrap_or(audio_bytes), duration_ms);
+            (duration_ms, bitrate)
+        }
+        _ => {
+            let bitrate = header.bitrate * 1000;
+            (audio_bytes * 8 * 1000 / bitrate as u64, bitrate)
+        }
+    };
+
+    Ok(AudioMetadata {
+        codec: match header.layer {
+            1 => "mp1",
+            2 => "mp2",
+            _ => "mp3",
+        }
+        .to_string(),
+        mime_type: "audio/mpeg",
+        duration_ms,
+        bitrate,
+        sample_rate: header.sample_rate,
+        channels: if header.mono { 1 } else { 2 },
+        chapters: tag.chapters,
+        artwork: tag.artwork,
+    })
+}
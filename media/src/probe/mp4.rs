@@ -0,0 +1,224 @@
+//! ISO base media files (MP4, M4A, M4B).
+
+use super::{average_bitrate, AudioMetadata, EmbeddedChapter, EmbeddedImage, ProbeError, Reader};
+
+struct Atom<'a> {
+    kind: [u8; 4],
+    body: &'a [u8],
+}
+
+/// Splits a run of sibling atoms.
+fn atoms(bytes: &[u8]) -> Vec<Atom<'_>> {
+    let mut atoms = Vec::new();
+    let mut reader = Reader::new(bytes);
+
+    while reader.remaining() >= 8 {
+        let start = reader.position;
+        let (Some(size), Some(kind)) = (reader.u32_be(), reader.take(4)) else {
+            break;
+        };
+        let kind = [kind[0], kind[1], kind[2], kind[3]];
+        let size = match size {
+            0 => bytes.len() - start,
+            1 => match reader.u64_be() {
+                Some(size) => size.min(usize::MAX as u64) as usize,
+                None => break,
+            },
+            size => size as usize,
+        };
+        let header_len = reader.position - start;
+        if size < header_len {
+            break;
+        }
+        let Some(body) = reader.take(size - header_len) else {
+            // Truncated uploads still carry useful headers in the part we have.
+            atoms.push(Atom { kind, body: reader.rest() });
+            break;
+        };
+        atoms.push(Atom { kind, body });
+    }
+    atoms
+}
+
+fn child<'a>(bytes: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    atoms(bytes)
+        .into_iter()
+        .find(|atom| &atom.kind == kind)
+        .map(|atom| atom.body)
+}
+
+/// Follows a path of nested atoms, e.g. `[b"udta", b"meta"]`.
+fn path<'a>(bytes: &'a [u8], kinds: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    kinds.iter().try_fold(bytes, |bytes, kind| {
+        let body = child(bytes, kind)?;
+        // `meta` is a full box: version and flags come before its children.
+        Some(if *kind == b"meta" { body.get(4..)? } else { body })
+    })
+}
+
+/// Returns (timescale, duration) from an `mvhd` or `mdhd` atom.
+fn timing(header: &[u8]) -> Option<(u64, u64)> {
+    let mut reader = Reader::new(header);
+    let version = reader.u8()?;
+    reader.skip(3)?;
+    if version == 1 {
+        reader.skip(16)?;
+        let timescale = reader.u32_be()? as u64;
+        Some((timescale, reader.u64_be()?))
+    } else {
+        reader.skip(8)?;
+        let timescale = reader.u32_be()? as u64;
+        Some((timescale, reader.u32_be()? as u64))
+    }
+}
+
+/// Converts a duration in `timescale` units to milliseconds. Version 1 headers have room
+/// for durations no real file has, so overflowing means the file is broken.
+fn timing_ms((timescale, duration): (u64, u64)) -> Result<u64, ProbeError> {
+    duration
+        .checked_mul(1000)
+        .map(|duration| duration / timescale)
+        .ok_or(ProbeError::Corrupt("MP4", "duration out of range"))
+}
+
+struct SoundTrack {
+    codec: String,
+    channels: u8,
+    sample_rate: u32,
+    /// (timescale, duration) of the track.
+    timing: Option<(u64, u64)>,
+}
+
+fn sound_track(trak: &[u8]) -> Option<SoundTrack> {
+    let mdia = child(trak, b"mdia")?;
+    let handler = child(mdia, b"hdlr")?;
+    if handler.get(8..12)? != b"soun" {
+        return None;
+    }
+    let timing = child(mdia, b"mdhd")
+        .and_then(timing)
+        .filter(|(timescale, _)| *timescale > 0);
+
+    let stsd = path(mdia, &[b"minf", b"stbl", b"stsd"])?;
+    let mut reader = Reader::new(stsd.get(8..)?);
+    reader.skip(4)?;
+    let format = reader.take(4)?;
+    reader.skip(6 + 2 + 8)?;
+    let channels = reader.u16_be()?;
+    reader.skip(6)?;
+    let sample_rate = reader.u32_be()? >> 16;
+
+    let codec = match format {
+        b"mp4a" => "aac".to_string(),
+        b"alac" => "alac".to_string(),
+        b"Opus" => "opus".to_string(),
+        b"fLaC" => "flac".to_string(),
+        b".mp3" => "mp3".to_string(),
+        other => String::from_utf8_lossy(other).trim().to_ascii_lowercase(),
+    };
+    Some(SoundTrack {
+        codec,
+        channels: channels.min(u8::MAX as u16) as u8,
+        sample_rate,
+        timing,
+    })
+}
+
+fn cover_art(moov: &[u8]) -> Option<EmbeddedImage> {
+    let covr = path(moov, &[b"udta", b"meta", b"ilst", b"covr"])?;
+    let data = child(covr, b"data")?;
+    let mut reader = Reader::new(data);
+    let data_type = reader.u32_be()?;
+    reader.skip(4)?;
+    let image = reader.rest();
+    if image.is_empty() {
+        return None;
+    }
+    Some(EmbeddedImage {
+        mime_type: if data_type == 14 { "image/png" } else { "image/jpeg" }.to_string(),
+        data: image.to_vec(),
+    })
+}
+
+/// Nero style `chpl` chapters, as written by ffmpeg and most podcast tools.
+fn chapters(moov: &[u8], duration_ms: u64) -> Vec<EmbeddedChapter> {
+    let Some(chpl) = path(moov, &[b"udta", b"chpl"]) else {
+        return Vec::new();
+    };
+    let mut reader = Reader::new(chpl);
+    let mut chapters = Vec::new();
+    let Some(version) = reader.u8() else {
+        return chapters;
+    };
+    if reader.skip(3).is_none() || (version > 0 && reader.skip(4).is_none()) {
+        return chapters;
+    }
+    let count = reader.u8().unwrap_or_default();
+    for _ in 0..count {
+        let (Some(start), Some(len)) = (reader.u64_be(), reader.u8()) else {
+            break;
+        };
+        let Some(title) = reader.take(len as usize) else {
+            break;
+        };
+        chapters.push(EmbeddedChapter {
+            // Stored in units of 100 nanoseconds.
+            start_ms: start / 10_000,
+            end_ms: None,
+            title: String::from_utf8_lossy(title).into_owned(),
+            url: None,
+            image: None,
+        });
+    }
+
+    let ends: Vec<u64> = chapters
+        .iter()
+        .skip(1)
+        .map(|chapter| chapter.start_ms)
+        .chain(std::iter::once(duration_ms))
+        .collect();
+    for (chapter, end_ms) in chapters.iter_mut().zip(ends) {
+        chapter.end_ms = (end_ms > chapter.start_ms).then_some(end_ms);
+    }
+    chapters
+}
+
+pub(super) fn probe(bytes: &[u8]) -> Result<AudioMetadata, ProbeError> {
+    let top_level = atoms(bytes);
+    let moov = top_level
+        .iter()
+        .find(|atom| &atom.kind == b"moov")
+        .ok_or(ProbeError::Corrupt("MP4", "no moov atom"))?
+        .body;
+    let track = atoms(moov)
+        .iter()
+        .filter(|atom| &atom.kind == b"trak")
+        .find_map(|atom| sound_track(atom.body))
+        .ok_or(ProbeError::Corrupt("MP4", "no audio track"))?;
+
+    let duration_ms = track
+        .timing
+        .or_else(|| {
+            child(moov, b"mvhd")
+                .and_then(timing)
+                .filter(|(timescale, _)| *timescale > 0)
+        })
+        .ok_or(ProbeError::Corrupt("MP4", "no duration"))
+        .and_then(timing_ms)?;
+    let media_bytes: usize = top_level
+        .iter()
+        .filter(|atom| &atom.kind == b"mdat")
+        .map(|atom| atom.body.len())
+        .sum();
+
+    Ok(AudioMetadata {
+        codec: track.codec,
+        mime_type: "audio/mp4",
+        duration_ms,
+        bitrate: average_bitrate(media_bytes as u64, duration_ms),
+        sample_rate: track.sample_rate,
+        channels: track.channels,
+        chapters: chapters(moov, duration_ms),
+        artwork: cover_art(moov),
+    })
+}
@@ -0,0 +1,89 @@
+//! Ogg streams carrying Opus or Vorbis.
+
+use super::{average_bitrate, AudioMetadata, ProbeError, Reader};
+
+struct Page<'a> {
+    granule_position: i64,
+    serial: u32,
+    body: &'a [u8],
+}
+
+fn page_at(bytes: &[u8], offset: usize) -> Option<Page<'_>> {
+    let mut reader = Reader::new(bytes.get(offset..)?);
+    if reader.take(4)? != b"OggS" {
+        return None;
+    }
+    reader.skip(2)?;
+    let granule_position = reader.i64_le()?;
+    let serial = reader.u32_le()?;
+    reader.skip(8)?;
+    let segment_count = reader.u8()? as usize;
+    let body_len: usize = reader.take(segment_count)?.iter().map(|len| *len as usize).sum();
+    let body = reader.take(body_len)?;
+    Some(Page {
+        granule_position,
+        serial,
+        body,
+    })
+}
+
+/// The granule position of the last complete page of `serial`, found by scanning backwards.
+fn last_granule_position(bytes: &[u8], serial: u32) -> Option<i64> {
+    let mut end = bytes.len();
+    while let Some(start) = bytes[..end].windows(4).rposition(|window| window == b"OggS") {
+        if let Some(page) = page_at(bytes, start) {
+            if page.serial == serial && page.granule_position >= 0 {
+                return Some(page.granule_position);
+            }
+        }
+        end = start;
+    }
+    None
+}
+
+pub(super) fn probe(bytes: &[u8]) -> Result<AudioMetadata, ProbeError> {
+    let first = page_at(bytes, 0).ok_or(ProbeError::Corrupt("Ogg", "truncated first page"))?;
+    let mut reader = Reader::new(first.body);
+
+    let (codec, channels, sample_rate, pre_skip, granule_rate) = if first.body.starts_with(b"OpusHead") {
+        reader.skip(9).ok_or(ProbeError::Corrupt("Ogg", "short Opus header"))?;
+        let channels = reader.u8();
+        let pre_skip = reader.u16_le();
+        let input_rate = reader.u32_le();
+        let (Some(channels), Some(pre_skip), Some(input_rate)) = (channels, pre_skip, input_rate) else {
+            return Err(ProbeError::Corrupt("Ogg", "short Opus header"));
+        };
+        // Opus always runs at 48 kHz internally, whatever the source was.
+        let sample_rate = if input_rate == 0 { 48_000 } else { input_rate };
+        ("opus", channels, sample_rate, pre_skip as i64, 48_000)
+    } else if first.body.starts_with(b"\x01vorbis") {
+        reader.skip(7 + 4).ok_or(ProbeError::Corrupt("Ogg", "short Vorbis header"))?;
+        let (Some(channels), Some(sample_rate)) = (reader.u8(), reader.u32_le()) else {
+            return Err(ProbeError::Corrupt("Ogg", "short Vorbis header"));
+        };
+        ("vorbis", channels, sample_rate, 0, sample_rate as i64)
+    } else {
+        return Err(ProbeError::UnsupportedFormat);
+    };
+    if granule_rate == 0 {
+        return Err(ProbeError::Corrupt("Ogg", "zero sample rate"));
+    }
+
+    let last_granule = last_granule_position(bytes, first.serial).unwrap_or_default();
+    let samples = (last_granule - pre_skip).max(0) as u64;
+    let duration_ms = samples
+        .checked_mul(1000)
+        .map(|samples| samples / granule_rate as u64)
+        .ok_or(ProbeError::Corrupt("Ogg", "granule position out of range"))?;
+
+    Ok(AudioMetadata {
+        codec: codec.to_string(),
+        mime_type: if codec == "opus" { "audio/opus" } else { "audio/ogg" },
+        duration_ms,
+        bitrate: average_bitrate(bytes.len() as u64, duration_ms),
+        sample_rate,
+        channels,
+        chapters: Vec::new(),
+        artwork: None,
+    })
+}
@@ -0,0 +1,169 @@
+//! The fixtures are written by `fixtures/generate.py`: real container headers around
+//! silent payloads, small enough to keep in the repository.
+
+use super::*;
+
+const CBR_MP3: &[u8] = include_bytes!("fixtures/cbr.mp3");
+const VBR_MP3: &[u8] = include_bytes!("fixtures/vbr.mp3");
+const M4A: &[u8] = include_bytes!("fixtures/a.m4a");
+const OPUS: &[u8] = include_bytes!("fixtures/a.opus");
+const VORBIS: &[u8] = include_bytes!("fixtures/a.ogg");
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut atom = (8 + body.len() as u32).to_be_bytes().to_vec();
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(body);
+    atom
+}
+
+/// An M4A file whose only duration is a version 1 `mvhd` with `duration` in `timescale`.
+fn m4a_with_v1_duration(timescale: u32, duration: u64) -> Vec<u8> {
+    let mut mvhd = vec![1, 0, 0, 0];
+    mvhd.extend_from_slice(&[0; 16]);
+    mvhd.extend_from_slice(&timescale.to_be_bytes());
+    mvhd.extend_from_slice(&duration.to_be_bytes());
+    mvhd.extend_from_slice(&[0; 80]);
+
+    let mut hdlr = vec![0; 8];
+    hdlr.extend_from_slice(b"soun");
+    hdlr.extend_from_slice(&[0; 13]);
+    let mut mp4a = vec![0; 16];
+    mp4a.extend_from_slice(&[0, 2, 0, 16, 0, 0, 0, 0]);
+    mp4a.extend_from_slice(&(44100u32 << 16).to_be_bytes());
+    let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    stsd.extend(atom(b"mp4a", &mp4a));
+    let stbl = atom(b"stbl", &atom(b"stsd", &stsd));
+    let mut mdia = atom(b"hdlr", &hdlr);
+    mdia.extend(atom(b"minf", &stbl));
+    let trak = atom(b"trak", &atom(b"mdia", &mdia));
+
+    let mut moov = atom(b"mvhd", &mvhd);
+    moov.extend(trak);
+    let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+    file.extend(atom(b"moov", &moov));
+    file
+}
+
+#[test]
+fn reads_cbr_mp3_with_id3_chapters_and_artwork() {
+    let metadata = probe(CBR_MP3).unwrap();
+
+    assert_eq!(metadata.codec, "mp3");
+    assert_eq!(metadata.mime_type, "audio/mpeg");
+    // 40 frames of 417 bytes at 128 kbps.
+    assert_eq!(metadata.duration_ms, 1042);
+    assert_eq!(metadata.bitrate, 128_000);
+    assert_eq!(metadata.sample_rate, 44100);
+    assert_eq!(metadata.channels, 2);
+
+    let artwork = metadata.artwork.unwrap();
+    assert_eq!(artwork.mime_type, "image/png");
+    assert!(artwork.data.starts_with(b"\x89PNG"));
+
+    let chapters: Vec<_> = metadata
+        .chapters
+        .iter()
+        .map(|chapter| (chapter.start_ms, chapter.end_ms, chapter.title.as_str(), chapter.url.as_deref()))
+        .collect();
+    assert_eq!(
+        chapters,
+        [
+            (0, Some(60_000), "Intro", Some("https://example.com/intro")),
+            (60_000, Some(120_000), "Zwei", None),
+        ]
+    );
+}
+
+#[test]
+fn reads_duration_of_vbr_mp3_from_xing_header() {
+    let metadata = probe(VBR_MP3).unwrap();
+
+    // 2000 frames of 1152 samples at 44.1 kHz.
+    assert_eq!(metadata.duration_ms, 52_244);
+    assert_eq!(metadata.bitrate as u64, 2000 * 417 * 8 * 1000 / 52_244);
+    assert!(metadata.chapters.is_empty());
+    assert_eq!(metadata.artwork, None);
+}
+
+#[test]
+fn reads_m4a_with_nero_chapters_and_cover_art() {
+    let metadata = probe(M4A).unwrap();
+
+    assert_eq!(metadata.codec, "aac");
+    assert_eq!(metadata.mime_type, "audio/mp4");
+    // The track's own duration wins over the longer movie header.
+    assert_eq!(metadata.duration_ms, 90_000);
+    assert_eq!(metadata.sample_rate, 44100);
+    assert_eq!(metadata.channels, 2);
+    assert_eq!(metadata.artwork.unwrap().mime_type, "image/jpeg");
+
+    let chapters: Vec<_> = metadata
+        .chapters
+        .iter()
+        .map(|chapter| (chapter.start_ms, chapter.end_ms, chapter.title.as_str()))
+        .collect();
+    assert_eq!(chapters, [(0, Some(30_000), "Start"), (30_000, Some(90_000), "Mid")]);
+}
+
+#[test]
+fn reads_opus_duration_after_pre_skip() {
+    let metadata = probe(OPUS).unwrap();
+
+    assert_eq!(metadata.codec, "opus");
+    assert_eq!(metadata.mime_type, "audio/opus");
+    assert_eq!(metadata.duration_ms, 61_000);
+    assert_eq!(metadata.sample_rate, 44100);
+    assert_eq!(metadata.channels, 2);
+}
+
+#[test]
+fn reads_vorbis() {
+    let metadata = probe(VORBIS).unwrap();
+
+    assert_eq!(metadata.codec, "vorbis");
+    assert_eq!(metadata.mime_type, "audio/ogg");
+    assert_eq!(metadata.duration_ms, 10_000);
+    assert_eq!(metadata.sample_rate, 22050);
+    assert_eq!(metadata.channels, 1);
+}
+
+#[test]
+fn reads_version_1_mp4_headers() {
+    let metadata = probe(&m4a_with_v1_duration(1000, 5_400_000)).unwrap();
+    assert_eq!(metadata.duration_ms, 5_400_000);
+}
+
+#[test]
+fn rejects_mp4_durations_that_overflow() {
+    assert_eq!(
+        probe(&m4a_with_v1_duration(1000, u64::MAX)),
+        Err(ProbeError::Corrupt("MP4", "duration out of range"))
+    );
+}
+
+#[test]
+fn rejects_ogg_granule_positions_that_overflow() {
+    let mut opus = OPUS.to_vec();
+    // The granule position of the last page, which starts at the last "OggS".
+    let last_page = opus.windows(4).rposition(|window| window == b"OggS").unwrap();
+    opus[last_page + 6..last_page + 14].copy_from_slice(&i64::MAX.to_le_bytes());
+
+    assert_eq!(probe(&opus), Err(ProbeError::Corrupt("Ogg", "granule position out of range")));
+}
+
+#[test]
+fn rejects_files_that_are_not_audio() {
+    assert_eq!(probe(b"<html>not audio</html>"), Err(ProbeError::UnsupportedFormat));
+    assert_eq!(probe(&[]), Err(ProbeError::UnsupportedFormat));
+}
+
+#[test]
+fn rejects_truncated_files_without_panicking() {
+    for fixture in [CBR_MP3, VBR_MP3, M4A, OPUS, VORBIS] {
+        for len in (0..fixture.len()).step_by(7) {
+            let _ = probe(&fixture[..len]);
+        }
+    }
+    assert_eq!(probe(&M4A[..40]), Err(ProbeError::Corrupt("MP4", "no audio track")));
+    assert_eq!(probe(&OPUS[..20]), Err(ProbeError::Corrupt("Ogg", "truncated first page")));
+}
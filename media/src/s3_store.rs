@@ -0,0 +1,233 @@
+use crate::{validate_key, ByteRange, MediaError, MediaReader, MediaStore, StoredMedia};
+use async_trait::async_trait;
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+
+/// How much of an object is buffered between the bucket and whoever reads it.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Stores media in an S3 bucket, or in anything that speaks the S3 API (MinIO, Garage, R2).
+pub struct S3MediaStore {
+    bucket: Box<Bucket>,
+    public_url: String,
+}
+
+impl S3MediaStore {
+    /// With an `endpoint` the bucket is addressed path style, which is what self-hosted
+    /// S3 compatible servers expect. `public_url` overrides the URL media is served from,
+    /// e.g. a CDN in front of the bucket.
+    pub fn new(
+        bucket: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        access_key: &str,
+        secret_key: &str,
+        public_url: Option<String>,
+    ) -> Result<Self, MediaError> {
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)
+            .map_err(|err| MediaError::Config(err.to_string()))?;
+
+        let bucket = match endpoint {
+            Some(endpoint) => {
+                let region = Region::Custom {
+                    region: region.to_string(),
+                    endpoint: endpoint.trim_end_matches('/').to_string(),
+                };
+                Bucket::new(bucket, region, credentials)?.with_path_style()
+            }
+            None => {
+                let region = region
+                    .parse::<Region>()
+                    .map_err(|err| MediaError::Config(err.to_string()))?;
+                Bucket::new(bucket, region, credentials)?
+            }
+        };
+
+        let public_url = public_url
+            .unwrap_or_else(|| bucket.url())
+            .trim_end_matches('/')
+            .to_string();
+        Ok(Self { bucket, public_url })
+    }
+}
+
+#[async_trait]
+impl MediaStore for S3MediaStore {
+    async fn put(&self, key: &str, bytes: &[u8], mime_type: &str) -> Result<StoredMedia, MediaError> {
+        validate_key(key)?;
+        self.bucket
+            .put_object_with_content_type(key, bytes, mime_type)
+            .await?;
+
+        Ok(StoredMedia {
+            key: key.to_string(),
+            size: bytes.len() as u64,
+            mime_type: mime_type.to_string(),
+        })
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MediaError> {
+        validate_key(key)?;
+        match self.bucket.get_object(key).await {
+            Ok(response) => Ok(response.bytes().to_vec()),
+            Err(S3Error::HttpFailWithBody(404, _)) => Err(MediaError::NotFound(key.to_string())),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn size(&self, key: &str) -> Result<u64, MediaError> {
+        validate_key(key)?;
+        match self.bucket.head_object(key).await {
+            Ok((head, _)) => head
+                .content_length
+                .and_then(|length| u64::try_from(length).ok())
+                .ok_or_else(|| MediaError::Config(format!("the bucket reports no size for {key}"))),
+            Err(S3Error::HttpFailWithBody(404, _)) => Err(MediaError::NotFound(key.to_string())),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The object is copied through a pipe by a task of its own, so it is passed on as it
+    /// arrives. Failures after the first byte can only cut the object short; they are
+    /// logged.
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<MediaReader, MediaError> {
+        validate_key(key)?;
+        let (start, end) = match range {
+            Some(range) => (range.start, Some(range.end)),
+            None => (0, None),
+        };
+        let (reader, mut writer) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let bucket = self.bucket.clone();
+        let key = key.to_string();
+        tokio::spawn(async move {
+            if let Err(err) = bucket.get_object_range_to_writer(&key, start, end, &mut writer).await {
+                eprintln!("Failed to stream {key} from the bucket: {err}");
+            }
+        });
+        Ok(Box::pin(reader))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), MediaError> {
+        validate_key(key)?;
+        self.bucket.delete_object(key).await?;
+        Ok(())
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{key}", self.public_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poem::http::{header, StatusCode};
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::web::{Data, Path};
+    use poem::{get, handler, Body, EndpointExt, Request, Response, Route, Server};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Enough of the S3 API, addressed path style like MinIO, to store, read and delete
+    /// objects. Signatures are not checked.
+    async fn start_stub() -> (String, Objects) {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().copied().unwrap();
+        let objects = Objects::default();
+        let routes = Route::new()
+            .at(
+                "/podcasts/*key",
+                get(get_object).head(head_object).put(put_object).delete(delete_object),
+            )
+            .data(objects.clone());
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(routes));
+        (format!("http://{addr}"), objects)
+    }
+
+    fn no_such_key() -> Response {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .content_type("application/xml")
+            .body("<Error><Code>NoSuchKey</Code></Error>")
+    }
+
+    #[handler]
+    fn get_object(objects: Data<&Objects>, Path(key): Path<String>, req: &Request) -> Response {
+        let objects = objects.lock().unwrap();
+        let Some(bytes) = objects.get(&key) else {
+            return no_such_key();
+        };
+        let range = req
+            .headers()
+            .get(header::RANGE)
+            .and_then(|range| range.to_str().ok()?.strip_prefix("bytes=")?.split_once('-'));
+        match range {
+            Some((start, end)) => {
+                let start: usize = start.parse().unwrap();
+                let end = end.parse::<usize>().map_or(bytes.len() - 1, |end| end.min(bytes.len() - 1));
+                Response::builder()
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{}", bytes.len()))
+                    .body(bytes[start..=end].to_vec())
+            }
+            None => Response::builder().body(bytes.clone()),
+        }
+    }
+
+    #[handler]
+    fn head_object(objects: Data<&Objects>, Path(key): Path<String>) -> Response {
+        match objects.lock().unwrap().get(&key) {
+            Some(bytes) => Response::builder()
+                .header(header::CONTENT_LENGTH, bytes.len())
+                .header(header::ETAG, "\"stub\"")
+                .body(Body::empty()),
+            None => no_such_key(),
+        }
+    }
+
+    #[handler]
+    async fn put_object(objects: Data<&Objects>, Path(key): Path<String>, body: Vec<u8>) -> Response {
+        objects.lock().unwrap().insert(key, body);
+        Response::builder().header(header::ETAG, "\"stub\"").finish()
+    }
+
+    #[handler]
+    fn delete_object(objects: Data<&Objects>, Path(key): Path<String>) -> StatusCode {
+        objects.lock().unwrap().remove(&key);
+        StatusCode::NO_CONTENT
+    }
+
+    async fn read_all(store: &S3MediaStore, key: &str, range: Option<ByteRange>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        store.open(key, range).await.unwrap().read_to_end(&mut bytes).await.unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn stores_and_streams_objects_in_a_bucket() {
+        let (endpoint, objects) = start_stub().await;
+        let store = S3MediaStore::new("podcasts", "us-east-1", Some(&endpoint), "minio", "minio123", None).unwrap();
+        let key = "episodes/1/a.mp3";
+
+        let stored = store.put(key, b"0123456789", "audio/mpeg").await.unwrap();
+        assert_eq!(stored.size, 10);
+        assert_eq!(objects.lock().unwrap()[key], b"0123456789");
+        assert_eq!(store.public_url(key), format!("{endpoint}/podcasts/{key}"));
+
+        assert_eq!(store.get(key).await.unwrap(), b"0123456789");
+        assert_eq!(store.size(key).await.unwrap(), 10);
+        assert_eq!(read_all(&store, key, None).await, b"0123456789");
+        assert_eq!(read_all(&store, key, Some(ByteRange { start: 3, end: 6 })).await, b"3456");
+
+        assert!(matches!(store.get("episodes/1/b.mp3").await, Err(MediaError::NotFound(_))));
+        assert!(matches!(store.size("episodes/1/b.mp3").await, Err(MediaError::NotFound(_))));
+        assert!(matches!(store.size("../a.mp3").await, Err(MediaError::InvalidKey(_))));
+
+        store.delete(key).await.unwrap();
+        assert!(objects.lock().unwrap().is_empty());
+    }
+}
@@ -5,6 +5,20 @@ use std::fmt::Display;
 mod m20220120_000001_create_post_table;
 mod m20241205_170802_create_member_table;
 mod m20250108_130829_add_episode_and_user_table;
+mod m20261018_000001_add_media_to_episode;
+mod m20261018_000002_create_show_table;
+mod m20261018_000003_add_status_to_episode;
+mod m20261018_000004_add_audio_metadata_to_episode;
+mod m20261018_000005_create_chapter_table;
+mod m20261018_000006_create_transcript_table;
+mod m20261018_000007_create_api_token_table;
+mod m20261018_000008_create_user_identity_table;
+mod m20261018_000009_add_local_accounts;
+mod m20261018_000010_create_user_session_table;
+mod m20261018_000011_create_web_session_table;
+mod m20261018_000012_create_role_tables;
+mod m20261018_000013_create_invitation_table;
+mod m20261018_000014_add_guid_to_episode;
 
 pub struct Migrator;
 
@@ -15,6 +29,20 @@ impl MigratorTrait for Migrator {
             Box::new(m20220120_000001_create_post_table::Migration),
             Box::new(m20241205_170802_create_member_table::Migration),
             Box::new(m20250108_130829_add_episode_and_user_table::Migration),
+            Box::new(m20261018_000001_add_media_to_episode::Migration),
+            Box::new(m20261018_000002_create_show_table::Migration),
+            Box::new(m20261018_000003_add_status_to_episode::Migration),
+            Box::new(m20261018_000004_add_audio_metadata_to_episode::Migration),
+            Box::new(m20261018_000005_create_chapter_table::Migration),
+            Box::new(m20261018_000006_create_transcript_table::Migration),
+            Box::new(m20261018_000007_create_api_token_table::Migration),
+            Box::new(m20261018_000008_create_user_identity_table::Migration),
+            Box::new(m20261018_000009_add_local_accounts::Migration),
+            Box::new(m20261018_000010_create_user_session_table::Migration),
+            Box::new(m20261018_000011_create_web_session_table::Migration),
+            Box::new(m20261018_000012_create_role_tables::Migration),
+            Box::new(m20261018_000013_create_invitation_table::Migration),
+            Box::new(m20261018_000014_add_guid_to_episode::Migration),
         ]
     }
 }
@@ -0,0 +1,48 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only supports one column per ALTER TABLE statement.
+        for column in [
+            string_null(Episode::MediaKey),
+            big_integer_null(Episode::MediaSize),
+            string_null(Episode::MediaType),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Episode::MediaKey, Episode::MediaSize, Episode::MediaType] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    MediaKey,
+    MediaSize,
+    MediaType,
+}
@@ -0,0 +1,147 @@
+use sea_orm_migration::sea_orm::DbBackend;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Show::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Show::Id))
+                    .col(string(Show::Title))
+                    .col(text(Show::Description))
+                    .col(string_null(Show::ArtworkUrl))
+                    .col(string(Show::Language).default("en"))
+                    .col(string(Show::Category))
+                    .col(string(Show::Author))
+                    .col(string(Show::OwnerName))
+                    .col(string(Show::OwnerEmail))
+                    .col(boolean(Show::Explicit).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShowUser::Table)
+                    .if_not_exists()
+                    .col(uuid(ShowUser::ShowId))
+                    .col(uuid(ShowUser::UserId))
+                    .primary_key(Index::create().col(ShowUser::ShowId).col(ShowUser::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_show_user_show")
+                            .from(ShowUser::Table, ShowUser::ShowId)
+                            .to(Show::Table, Show::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_show_user_user")
+                            .from(ShowUser::Table, ShowUser::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing episodes predate shows, so the column has to start out nullable.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Episode::Table)
+                    .add_column(uuid_null(Episode::ShowId))
+                    .to_owned(),
+            )
+            .await?;
+
+        // SQLite cannot add a foreign key to an existing table.
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name("fk_episode_show")
+                        .from(Episode::Table, Episode::ShowId)
+                        .to(Show::Table, Show::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                        .on_update(ForeignKeyAction::Cascade)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .drop_foreign_key(
+                    ForeignKey::drop()
+                        .name("fk_episode_show")
+                        .table(Episode::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Episode::Table)
+                    .drop_column(Episode::ShowId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ShowUser::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Show::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Show {
+    Table,
+    Id,
+    Title,
+    Description,
+    ArtworkUrl,
+    Language,
+    Category,
+    Author,
+    OwnerName,
+    OwnerEmail,
+    Explicit,
+}
+
+#[derive(DeriveIden)]
+enum ShowUser {
+    Table,
+    ShowId,
+    UserId,
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    ShowId,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
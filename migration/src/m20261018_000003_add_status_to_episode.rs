@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            string_len(Episode::Status, 16).default("draft").to_owned(),
+            timestamp_with_time_zone_null(Episode::PublishedAt),
+            timestamp_with_time_zone_null(Episode::ScheduledFor),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Before statuses existed every episode was live, so keep it that way.
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Episode::Table)
+                    .value(Episode::Status, "published")
+                    .value(Episode::PublishedAt, Expr::current_timestamp())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Episode::Status, Episode::PublishedAt, Episode::ScheduledFor] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    Status,
+    PublishedAt,
+    ScheduledFor,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            big_integer_null(Episode::DurationMs),
+            integer_null(Episode::Bitrate),
+            integer_null(Episode::SampleRate),
+            small_integer_null(Episode::Channels),
+            string_null(Episode::Codec),
+            string_null(Episode::ArtworkUrl),
+            text_null(Episode::EmbeddedChapters),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Episode::DurationMs,
+            Episode::Bitrate,
+            Episode::SampleRate,
+            Episode::Channels,
+            Episode::Codec,
+            Episode::ArtworkUrl,
+            Episode::EmbeddedChapters,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Episode::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    DurationMs,
+    Bitrate,
+    SampleRate,
+    Channels,
+    Codec,
+    ArtworkUrl,
+    EmbeddedChapters,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Chapter::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Chapter::Id))
+                    .col(uuid(Chapter::EpisodeId))
+                    .col(big_integer(Chapter::StartMs))
+                    .col(string(Chapter::Title))
+                    .col(string_null(Chapter::Url))
+                    .col(string_null(Chapter::ImageUrl))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_chapter_episode")
+                            .from(Chapter::Table, Chapter::EpisodeId)
+                            .to(Episode::Table, Episode::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_chapter_episode_start")
+                    .table(Chapter::Table)
+                    .col(Chapter::EpisodeId)
+                    .col(Chapter::StartMs)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Chapter::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Chapter {
+    Table,
+    Id,
+    EpisodeId,
+    StartMs,
+    Title,
+    Url,
+    ImageUrl,
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    Id,
+}
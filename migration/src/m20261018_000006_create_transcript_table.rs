@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Transcript::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Transcript::Id))
+                    .col(uuid(Transcript::EpisodeId))
+                    .col(string_len(Transcript::Language, 16))
+                    .col(string_len(Transcript::SourceFormat, 8))
+                    .col(text(Transcript::Cues))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_transcript_episode")
+                            .from(Transcript::Table, Transcript::EpisodeId)
+                            .to(Episode::Table, Episode::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One transcript per language and episode.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_transcript_episode_language")
+                    .table(Transcript::Table)
+                    .col(Transcript::EpisodeId)
+                    .col(Transcript::Language)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Transcript::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Transcript {
+    Table,
+    Id,
+    EpisodeId,
+    Language,
+    SourceFormat,
+    Cues,
+}
+
+#[derive(DeriveIden)]
+enum Episode {
+    Table,
+    Id,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiToken::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(ApiToken::Id))
+                    .col(uuid(ApiToken::UserId))
+                    .col(string(ApiToken::Name))
+                    // SHA-256 of the secret, hex encoded. The secret itself is never stored.
+                    .col(string_len_uniq(ApiToken::TokenHash, 64))
+                    .col(string_len(ApiToken::Prefix, 16))
+                    .col(string(ApiToken::Scopes))
+                    .col(timestamp_with_time_zone_null(ApiToken::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(ApiToken::LastUsedAt))
+                    .col(timestamp_with_time_zone(ApiToken::CreatedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_api_token_user")
+                            .from(ApiToken::Table, ApiToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_api_token_user_id")
+                    .table(ApiToken::Table)
+                    .col(ApiToken::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiToken {
+    Table,
+    Id,
+    UserId,
+    Name,
+    TokenHash,
+    Prefix,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,107 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            // Argon2id PHC string, only set for users with a local password.
+            string_null(User::PasswordHash),
+            timestamp_with_time_zone_null(User::EmailVerifiedAt),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Everybody so far logged in through Google, which vouched for their address.
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(User::EmailVerifiedAt, Expr::current_timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OneTimeToken::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(OneTimeToken::Id))
+                    .col(uuid(OneTimeToken::UserId))
+                    // `verify_email`, `reset_password` or `magic_link`.
+                    .col(string_len(OneTimeToken::Purpose, 32))
+                    .col(string_len_uniq(OneTimeToken::TokenHash, 64))
+                    .col(timestamp_with_time_zone(OneTimeToken::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(OneTimeToken::UsedAt))
+                    .col(timestamp_with_time_zone(OneTimeToken::CreatedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_one_time_token_user")
+                            .from(OneTimeToken::Table, OneTimeToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_one_time_token_user_id")
+                    .table(OneTimeToken::Table)
+                    .col(OneTimeToken::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OneTimeToken::Table).to_owned())
+            .await?;
+
+        for column in [User::PasswordHash, User::EmailVerifiedAt] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+    PasswordHash,
+    EmailVerifiedAt,
+}
+
+#[derive(DeriveIden)]
+enum OneTimeToken {
+    Table,
+    Id,
+    UserId,
+    Purpose,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    CreatedAt,
+}
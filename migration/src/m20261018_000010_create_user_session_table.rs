@@ -0,0 +1,71 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserSession::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(UserSession::Id))
+                    .col(uuid(UserSession::UserId))
+                    .col(string_null(UserSession::IpAddress))
+                    .col(string_null(UserSession::UserAgent))
+                    // Set when the session was started through an OpenID Connect provider,
+                    // so logging out can revoke the provider's token.
+                    .col(string_len_null(UserSession::Provider, 64))
+                    .col(text_null(UserSession::ProviderAccessToken))
+                    .col(timestamp_with_time_zone(UserSession::CreatedAt))
+                    .col(timestamp_with_time_zone(UserSession::LastSeenAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_session_user")
+                            .from(UserSession::Table, UserSession::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_session_user_id")
+                    .table(UserSession::Table)
+                    .col(UserSession::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserSession::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserSession {
+    Table,
+    Id,
+    UserId,
+    IpAddress,
+    UserAgent,
+    Provider,
+    ProviderAccessToken,
+    CreatedAt,
+    LastSeenAt,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
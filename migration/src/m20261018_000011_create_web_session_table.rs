@@ -0,0 +1,49 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebSession::Table)
+                    .if_not_exists()
+                    // The random id in the session cookie.
+                    .col(string_len(WebSession::Id, 64).primary_key())
+                    // The session's entries as a JSON object.
+                    .col(text(WebSession::Entries))
+                    .col(timestamp_with_time_zone_null(WebSession::ExpiresAt))
+                    .col(timestamp_with_time_zone(WebSession::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_web_session_expires_at")
+                    .table(WebSession::Table)
+                    .col(WebSession::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebSession::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum WebSession {
+    Table,
+    Id,
+    Entries,
+    ExpiresAt,
+    UpdatedAt,
+}
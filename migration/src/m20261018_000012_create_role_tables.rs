@@ -0,0 +1,172 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// The roles every installation starts with: name, label, the role it inherits from and
+/// the permissions it adds on top.
+const BUILT_IN_ROLES: [(&str, &str, Option<&str>, &[&str]); 5] = [
+    ("user", "User", None, &[]),
+    ("contributor", "Contributor", Some("user"), &["episode:edit"]),
+    ("host", "Host", Some("contributor"), &["episode:publish"]),
+    (
+        "admin",
+        "Admin",
+        Some("host"),
+        &["post:edit", "member:edit", "show:manage", "show:grant"],
+    ),
+    ("super_admin", "Super Admin", Some("admin"), &["user:manage"]),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Role::Table)
+                    .if_not_exists()
+                    .col(string_len(Role::Name, 64).primary_key())
+                    .col(string(Role::Label))
+                    // Roles get every permission of the role they inherit from.
+                    .col(string_len_null(Role::Parent, 64))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_parent")
+                            .from(Role::Table, Role::Parent)
+                            .to(Role::Table, Role::Name)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RolePermission::Table)
+                    .if_not_exists()
+                    .col(string_len(RolePermission::Role, 64))
+                    // E.g. `episode:publish`.
+                    .col(string_len(RolePermission::Permission, 64))
+                    .primary_key(
+                        Index::create()
+                            .col(RolePermission::Role)
+                            .col(RolePermission::Permission),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_permission_role")
+                            .from(RolePermission::Table, RolePermission::Role)
+                            .to(Role::Table, Role::Name)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, label, parent, permissions) in BUILT_IN_ROLES {
+            manager
+                .exec_stmt(
+                    Query::insert()
+                        .into_table(Role::Table)
+                        .columns([Role::Name, Role::Label, Role::Parent])
+                        .values_panic([name.into(), label.into(), parent.into()])
+                        .to_owned(),
+                )
+                .await?;
+            for permission in permissions {
+                manager
+                    .exec_stmt(
+                        Query::insert()
+                            .into_table(RolePermission::Table)
+                            .columns([RolePermission::Role, RolePermission::Permission])
+                            .values_panic([name.into(), (*permission).into()])
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+
+        // Roles typed into `user.role` by hand keep working, without any permissions.
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Role::Table)
+                    .columns([Role::Name, Role::Label, Role::Parent])
+                    .select_from(
+                        Query::select()
+                            .distinct()
+                            .column(User::Role)
+                            .column(User::Role)
+                            .expr(Expr::val("user"))
+                            .from(User::Table)
+                            .and_where(
+                                Expr::col(User::Role).not_in_subquery(
+                                    Query::select().column(Role::Name).from(Role::Table).to_owned(),
+                                ),
+                            )
+                            .to_owned(),
+                    )
+                    .map_err(|err| DbErr::Migration(err.to_string()))?
+                    .to_owned(),
+            )
+            .await?;
+
+        // Access granted to a show so far allowed editing and publishing its episodes.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ShowUser::Table)
+                    .add_column(string_len(ShowUser::Role, 64).default("host"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ShowUser::Table)
+                    .drop_column(ShowUser::Role)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(RolePermission::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Role::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Role {
+    Table,
+    Name,
+    Label,
+    Parent,
+}
+
+#[derive(DeriveIden)]
+enum RolePermission {
+    Table,
+    Role,
+    Permission,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Role,
+}
+
+#[derive(DeriveIden)]
+enum ShowUser {
+    Table,
+    Role,
+}
@@ -0,0 +1,104 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Deactivated users keep their account and episodes but cannot log in.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(timestamp_with_time_zone_null(User::DeactivatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invitation::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Invitation::Id))
+                    .col(string(Invitation::Email))
+                    // The role the account is created with.
+                    .col(string_len(Invitation::Role, 64))
+                    .col(string_len_uniq(Invitation::TokenHash, 64))
+                    .col(uuid_null(Invitation::InvitedBy))
+                    .col(timestamp_with_time_zone(Invitation::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(Invitation::AcceptedAt))
+                    .col(timestamp_with_time_zone(Invitation::CreatedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_invitation_role")
+                            .from(Invitation::Table, Invitation::Role)
+                            .to(Role::Table, Role::Name)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_invitation_invited_by")
+                            .from(Invitation::Table, Invitation::InvitedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_invitation_email")
+                    .table(Invitation::Table)
+                    .col(Invitation::Email)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Invitation::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::DeactivatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Invitation {
+    Table,
+    Id,
+    Email,
+    Role,
+    TokenHash,
+    InvitedBy,
+    ExpiresAt,
+    AcceptedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Role {
+    Table,
+    Name,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+    DeactivatedAt,
+}
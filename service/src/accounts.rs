@@ -0,0 +1,111 @@
+//! Local accounts: passwords and the one-time tokens sent by email.
+//!
+//! Passwords are hashed with Argon2id. One-time tokens verify an email address, reset a
+//! password or log in without one. Like API tokens, only their SHA-256 is stored and each
+//! can be used once.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Duration;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::sync::OnceLock;
+
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+/// Argon2 gets slow on purpose, so do not let anybody make it hash megabytes.
+pub const MAX_PASSWORD_LENGTH: usize = 256;
+
+/// What a one-time token may be used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    MagicLink,
+}
+
+impl TokenPurpose {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+            TokenPurpose::MagicLink => "magic_link",
+        }
+    }
+
+    /// How long a token stays usable after it was sent.
+    pub fn lifetime(self) -> Duration {
+        match self {
+            TokenPurpose::VerifyEmail => Duration::days(2),
+            TokenPurpose::ResetPassword => Duration::hours(1),
+            TokenPurpose::MagicLink => Duration::minutes(15),
+        }
+    }
+}
+
+/// Why a password was rejected, ready to be shown next to the form.
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        Err(format!("Use at least {MIN_PASSWORD_LENGTH} characters"))
+    } else if length > MAX_PASSWORD_LENGTH {
+        Err(format!("Use at most {MAX_PASSWORD_LENGTH} characters"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The PHC string of a fresh Argon2id hash of `password`.
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password_hash: &str, password: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .and_then(|hash| Argon2::default().verify_password(password.as_bytes(), &hash))
+        .is_ok()
+}
+
+/// Hashes a password nobody has, so looking up an unknown user takes as long as checking
+/// the password of a known one.
+pub fn waste_password_check(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let dummy_hash = DUMMY_HASH.get_or_init(|| {
+        hash_password(&generate_one_time_token()).expect("Failed to hash a random password")
+    });
+    verify_password(dummy_hash, password);
+}
+
+/// A fresh random one-time token, safe to put in a URL.
+pub fn generate_one_time_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Email addresses are compared and stored without surrounding space and in lower case.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// What to call somebody who did not tell their name: the part of their address before
+/// the `@`.
+pub fn name_from_email(email: &str) -> &str {
+    email.split_once('@').map_or(email, |(local, _)| local)
+}
+
+/// Cheap sanity check; whether an address really works is found out by mailing it.
+pub fn looks_like_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
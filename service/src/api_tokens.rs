@@ -0,0 +1,74 @@
+//! Personal API tokens for scripts and other machine clients.
+//!
+//! A token is `pc_` followed by 40 random hex digits. Only its SHA-256 is stored, so a
+//! token can be shown to its owner exactly once, right after it was created.
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+pub const TOKEN_PREFIX: &str = "pc_";
+
+/// How many characters of a token are kept to tell tokens apart.
+const DISPLAY_PREFIX_LENGTH: usize = 11;
+
+/// What a token may be used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenScope {
+    /// Safe requests: `GET`, `HEAD` and `OPTIONS`.
+    Read,
+    /// Everything that changes data.
+    Write,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 2] = [TokenScope::Read, TokenScope::Write];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == scope)
+    }
+
+    /// The scope a request with `method` needs.
+    pub fn for_method(method: &str) -> Self {
+        match method {
+            "GET" | "HEAD" | "OPTIONS" => TokenScope::Read,
+            _ => TokenScope::Write,
+        }
+    }
+}
+
+/// Scopes as stored on a token, e.g. `read write`.
+pub fn format_scopes(scopes: &[TokenScope]) -> String {
+    TokenScope::ALL
+        .into_iter()
+        .filter(|scope| scopes.contains(scope))
+        .map(TokenScope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn has_scope(scopes: &str, scope: TokenScope) -> bool {
+    scopes.split_whitespace().any(|granted| granted == scope.as_str())
+}
+
+/// A fresh random token.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{TOKEN_PREFIX}{}", hex::encode(bytes))
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The part of a token that is safe to show again later.
+pub fn display_prefix(token: &str) -> String {
+    token.chars().take(DISPLAY_PREFIX_LENGTH).collect()
+}
@@ -0,0 +1,500 @@
+//! A show with everything that belongs to it as one `.tar.gz` file, for keeping offsite
+//! backups and moving shows between deployments.
+//!
+//! The archive starts with `manifest.json`, a [`ShowBackup`], followed by the media files
+//! the show's episodes use, each under `media/` and its media store key. Ids are kept, so
+//! feed URLs and episode GUIDs stay the same after restoring.
+
+use crate::chapters::ImportedChapter;
+use crate::exports::ExportedEpisode;
+use crate::transcripts::Cue;
+use entities::{chapter, episode, show, transcript};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sea_orm::prelude::{DateTimeUtc, Uuid};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// Bumped whenever the format changes in a way older readers cannot handle.
+pub const SHOW_BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+const MEDIA_DIRECTORY: &str = "media/";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("cannot read the archive: {0}")]
+    Io(#[from] io::Error),
+    #[error("the manifest is invalid: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("the archive does not start with {MANIFEST_PATH}")]
+    MissingManifest,
+    #[error("backup version {0} is newer than the supported version {SHOW_BACKUP_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("the archive contains {0}, which is not part of a backup")]
+    UnexpectedEntry(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShowBackup {
+    pub version: u32,
+    pub created_at: DateTimeUtc,
+    /// What the URLs of stored media started with, so they can be pointed at the media
+    /// store of the deployment the backup is restored into.
+    pub media_url: String,
+    pub show: BackedUpShow,
+    /// Everybody who owns an episode or was granted access to the show.
+    pub users: Vec<BackedUpUser>,
+    pub members: Vec<BackedUpMember>,
+    pub episodes: Vec<BackedUpEpisode>,
+}
+
+impl ShowBackup {
+    /// Every media file the archive should contain.
+    pub fn media_keys(&self) -> impl Iterator<Item = &str> {
+        self.episodes
+            .iter()
+            .flat_map(|episode| episode.media.iter().map(String::as_str))
+    }
+
+    /// Whether `key` is one of [`ShowBackup::media_keys`]. Nothing else in an archive may
+    /// end up in the media store.
+    pub fn lists_media(&self, key: &str) -> bool {
+        self.media_keys().any(|known| known == key)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackedUpShow {
+    pub id: Uuid,
+    pub title: String,
+    pub description: String,
+    pub artwork_url: Option<String>,
+    pub language: String,
+    pub category: String,
+    pub author: String,
+    pub owner_name: String,
+    pub owner_email: String,
+    pub explicit: bool,
+}
+
+impl From<&show::Model> for BackedUpShow {
+    fn from(show: &show::Model) -> Self {
+        Self {
+            id: show.id,
+            title: show.title.clone(),
+            description: show.description.clone(),
+            artwork_url: show.artwork_url.clone(),
+            language: show.language.clone(),
+            category: show.category.clone(),
+            author: show.author.clone(),
+            owner_name: show.owner_name.clone(),
+            owner_email: show.owner_email.clone(),
+            explicit: show.explicit,
+        }
+    }
+}
+
+/// An account, without anything that lets anyone log in as it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackedUpUser {
+    pub email: String,
+    pub name: String,
+    pub role: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackedUpMember {
+    pub email: String,
+    /// The role granted on the show.
+    pub role: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackedUpEpisode {
+    pub id: Uuid,
+    /// Address of the user who owns the episode.
+    pub owner: String,
+    #[serde(flatten)]
+    pub episode: ExportedEpisode,
+    pub media_key: Option<String>,
+    pub embedded_chapters: Option<String>,
+    #[serde(default)]
+    pub transcripts: Vec<BackedUpTranscript>,
+    /// Keys of the files in the archive that belong to the episode: its audio and the
+    /// images taken from it.
+    #[serde(default)]
+    pub media: Vec<String>,
+}
+
+impl BackedUpEpisode {
+    /// Fails if a stored transcript is not valid JSON.
+    pub fn new(
+        episode: &episode::Model,
+        owner: &str,
+        chapters: &[chapter::Model],
+        transcripts: &[transcript::Model],
+        media_url: &str,
+    ) -> Result<Self, serde_json::Error> {
+        let exported = ExportedEpisode::new(episode, chapters);
+        let embedded_chapters: Vec<ImportedChapter> = episode
+            .embedded_chapters
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?
+            .unwrap_or_default();
+
+        let mut media: Vec<String> = episode.media_key.iter().cloned().collect();
+        let urls = exported
+            .artwork_url
+            .iter()
+            .chain(exported.chapters.iter().filter_map(|chapter| chapter.image_url.as_ref()))
+            .chain(embedded_chapters.iter().filter_map(|chapter| chapter.image_url.as_ref()));
+        for key in urls.filter_map(|url| url.strip_prefix(media_url)) {
+            if !media.iter().any(|known| known == key) {
+                media.push(key.to_string());
+            }
+        }
+
+        let transcripts = transcripts
+            .iter()
+            .map(|transcript| {
+                Ok(BackedUpTranscript {
+                    language: transcript.language.clone(),
+                    source_format: transcript.source_format.clone(),
+                    cues: serde_json::from_str(&transcript.cues)?,
+                })
+            })
+            .collect::<Result<_, serde_json::Error>>()?;
+
+        Ok(Self {
+            id: episode.id,
+            owner: owner.to_string(),
+            episode: exported,
+            media_key: episode.media_key.clone(),
+            embedded_chapters: episode.embedded_chapters.clone(),
+            transcripts,
+            media,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackedUpTranscript {
+    pub language: String,
+    pub source_format: String,
+    pub cues: Vec<Cue>,
+}
+
+/// What restoring a backup did.
+#[derive(Clone, Debug)]
+pub struct RestoredShow {
+    pub show_id: Uuid,
+    pub episodes: usize,
+    /// Addresses of the accounts that had to be created. They log in through a provider
+    /// or with a magic link, as passwords are not backed up.
+    pub created_users: Vec<String>,
+}
+
+/// Swaps the `from` prefix of a stored media URL for `to`. Other URLs are left alone.
+pub fn rebase_url(url: &str, from: &str, to: &str) -> String {
+    match url.strip_prefix(from) {
+        Some(key) => format!("{to}{key}"),
+        None => url.to_string(),
+    }
+}
+
+/// Writes a backup. Media files are added one at a time, so a show never has to fit into
+/// memory as a whole.
+pub struct ArchiveWriter<W: Write> {
+    builder: tar::Builder<GzEncoder<W>>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(writer: W, backup: &ShowBackup) -> io::Result<Self> {
+        let mut archive = Self {
+            builder: tar::Builder::new(GzEncoder::new(writer, Compression::default())),
+        };
+        let manifest = serde_json::to_vec_pretty(backup)?;
+        archive.append(MANIFEST_PATH, &manifest)?;
+        Ok(archive)
+    }
+
+    pub fn add_media(&mut self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        self.append(&format!("{MEDIA_DIRECTORY}{key}"), bytes)
+    }
+
+    fn append(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        self.builder.append_data(&mut header, path, bytes)
+    }
+
+    /// Completes the archive and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.builder.into_inner()?.finish()
+    }
+}
+
+/// Reads a backup written by [`ArchiveWriter`].
+pub struct ArchiveReader<R: Read> {
+    archive: tar::Archive<GzDecoder<R>>,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            archive: tar::Archive::new(GzDecoder::new(reader)),
+        }
+    }
+
+    /// Reads the manifest, refusing versions this build does not know, and returns it
+    /// together with the media files that follow it.
+    pub fn read(&mut self) -> Result<(ShowBackup, MediaFiles<'_, R>), BackupError> {
+        let mut entries = self.archive.entries()?;
+        let mut manifest = entries.next().ok_or(BackupError::MissingManifest)??;
+        if manifest.path()?.to_str() != Some(MANIFEST_PATH) {
+            return Err(BackupError::MissingManifest);
+        }
+        let mut json = Vec::new();
+        manifest.read_to_end(&mut json)?;
+
+        // Only the version is looked at first, so newer backups get a useful error.
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let Version { version } = serde_json::from_slice(&json)?;
+        if version > SHOW_BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion(version));
+        }
+        let backup = serde_json::from_slice(&json)?;
+        Ok((backup, MediaFiles { entries }))
+    }
+}
+
+/// The media files in a backup, as their key and contents.
+pub struct MediaFiles<'a, R: 'a + Read> {
+    entries: tar::Entries<'a, GzDecoder<R>>,
+}
+
+impl<'a, R: 'a + Read> Iterator for MediaFiles<'a, R> {
+    type Item = Result<(String, Vec<u8>), BackupError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = match self.entries.next()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err.into())),
+        };
+        let path = match entry.path() {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(err) => return Some(Err(err.into())),
+        };
+        let Some(key) = path.strip_prefix(MEDIA_DIRECTORY).map(str::to_string) else {
+            return Some(Err(BackupError::UnexpectedEntry(path)));
+        };
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        if let Err(err) = entry.read_to_end(&mut bytes) {
+            return Some(Err(err.into()));
+        }
+        Some(Ok((key, bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+    use crate::permissions::{DEFAULT_ROLE, SUPER_ADMIN_ROLE};
+    use crate::{Mutation, Query};
+    use entities::sea_orm_active_enums::EpisodeStatus;
+    use entities::show_user;
+    use sea_orm::{ActiveModelTrait, DbConn, Set};
+
+    const OLD_MEDIA_URL: &str = "https://old.example.com/media/";
+    const NEW_MEDIA_URL: &str = "https://new.example.com/media/";
+
+    async fn seed_show(db: &DbConn) -> Uuid {
+        let host = Mutation::create_user(db, "host@example.com", "Host").await.unwrap();
+        let show_id = Uuid::new_v4();
+        show::ActiveModel {
+            id: Set(show_id),
+            title: Set("Crab Talk".to_owned()),
+            description: Set("All about crabs".to_owned()),
+            artwork_url: Set(Some(format!("{OLD_MEDIA_URL}shows/cover.png"))),
+            language: Set("en".to_owned()),
+            category: Set("Science".to_owned()),
+            author: Set("Host".to_owned()),
+            owner_name: Set("Host".to_owned()),
+            owner_email: Set("host@example.com".to_owned()),
+            explicit: Set(false),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        show_user::ActiveModel {
+            show_id: Set(show_id),
+            user_id: Set(host.id),
+            role: Set("host".to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        let episode_id = Uuid::new_v4();
+        let media_key = format!("episodes/{episode_id}/one.mp3");
+        episode::ActiveModel {
+            id: Set(episode_id),
+            title: Set("One".to_owned()),
+            summary: Set("The first one".to_owned()),
+            tags: Set("crabs".to_owned()),
+            url: Set(Some(format!("{OLD_MEDIA_URL}{media_key}"))),
+            user_id: Set(host.id),
+            media_key: Set(Some(media_key)),
+            media_size: Set(Some(5)),
+            media_type: Set(Some("audio/mpeg".to_owned())),
+            show_id: Set(Some(show_id)),
+            status: Set(EpisodeStatus::Published),
+            published_at: Set(Some("2026-01-02T03:04:05Z".parse().unwrap())),
+            scheduled_for: Set(None),
+            duration_ms: Set(Some(61_000)),
+            bitrate: Set(Some(128_000)),
+            sample_rate: Set(Some(44_100)),
+            channels: Set(Some(2)),
+            codec: Set(Some("mp3".to_owned())),
+            artwork_url: Set(Some(format!("{OLD_MEDIA_URL}episodes/{episode_id}/art.jpg"))),
+            embedded_chapters: Set(None),
+            guid: Set(Some("crab-talk-1".to_owned())),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        chapter::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            episode_id: Set(episode_id),
+            start_ms: Set(0),
+            title: Set("Hello".to_owned()),
+            url: Set(None),
+            image_url: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        transcript::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            episode_id: Set(episode_id),
+            language: Set("en".to_owned()),
+            source_format: Set("srt".to_owned()),
+            cues: Set(r#"[{"start_ms":0,"end_ms":1000,"speaker":null,"text":"Hi"}]"#.to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        show_id
+    }
+
+    #[tokio::test]
+    async fn backups_restore_into_another_deployment() {
+        let source = test_db().await;
+        let show_id = seed_show(&source).await;
+        let now = chrono::Utc::now();
+        let backup = Query::find_show_backup(&source, show_id, OLD_MEDIA_URL, now)
+            .await
+            .unwrap()
+            .unwrap();
+        let keys: Vec<String> = backup.media_keys().map(str::to_string).collect();
+        assert_eq!(keys.len(), 2, "{keys:?}");
+
+        let mut writer = ArchiveWriter::new(Vec::new(), &backup).unwrap();
+        for key in &keys {
+            writer.add_media(key, key.as_bytes()).unwrap();
+        }
+        writer.add_media("episodes/elsewhere/stray.mp3", b"stray").unwrap();
+        let archive = writer.finish().unwrap();
+
+        let mut reader = ArchiveReader::new(archive.as_slice());
+        let (mut restored_backup, files) = reader.read().unwrap();
+        let files: Vec<(String, Vec<u8>)> = files.collect::<Result<_, _>>().unwrap();
+        let listed: Vec<&String> = files
+            .iter()
+            .filter(|(key, _)| restored_backup.lists_media(key))
+            .map(|(key, bytes)| {
+                assert_eq!(key.as_bytes(), bytes.as_slice());
+                key
+            })
+            .collect();
+        assert_eq!(listed, keys.iter().collect::<Vec<_>>());
+        assert!(!restored_backup.lists_media("episodes/elsewhere/stray.mp3"));
+
+        let target = test_db().await;
+        restored_backup.users[0].role = SUPER_ADMIN_ROLE.to_owned();
+        let restored = Mutation::restore_show(&target, &restored_backup, NEW_MEDIA_URL, now)
+            .await
+            .unwrap();
+        assert_eq!(restored.show_id, show_id);
+        assert_eq!(restored.episodes, 1);
+        assert_eq!(restored.created_users, ["host@example.com"]);
+        let host = Query::find_user_by_email(&target, "host@example.com").await.unwrap().unwrap();
+        assert_eq!(host.role, DEFAULT_ROLE, "archives must not hand out site-wide roles");
+
+        let again = Query::find_show_backup(&target, show_id, NEW_MEDIA_URL, now)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.show.title, "Crab Talk");
+        assert_eq!(again.members.len(), 1);
+        assert_eq!(again.members[0].role, "host");
+        let [episode] = again.episodes.as_slice() else {
+            panic!("expected one episode, got {:?}", again.episodes);
+        };
+        let original = &backup.episodes[0];
+        assert_eq!(episode.id, original.id);
+        assert_eq!(episode.episode.guid.as_deref(), Some("crab-talk-1"));
+        assert_eq!(episode.episode.chapters.len(), 1);
+        assert_eq!(episode.transcripts[0].cues[0].text, "Hi");
+        assert_eq!(episode.media, original.media);
+        let url = episode.episode.url.as_deref().unwrap();
+        assert!(url.starts_with(NEW_MEDIA_URL), "{url}");
+
+        let err = Mutation::restore_show(&target, &restored_backup, NEW_MEDIA_URL, now).await;
+        assert!(err.is_err(), "restoring twice must fail");
+    }
+
+    #[test]
+    fn rejects_newer_versions_and_stray_entries() {
+        let backup = ShowBackup {
+            version: SHOW_BACKUP_VERSION + 1,
+            created_at: chrono::Utc::now(),
+            media_url: OLD_MEDIA_URL.to_owned(),
+            show: BackedUpShow {
+                id: Uuid::new_v4(),
+                title: "Later".to_owned(),
+                description: String::new(),
+                artwork_url: None,
+                language: "en".to_owned(),
+                category: String::new(),
+                author: String::new(),
+                owner_name: String::new(),
+                owner_email: String::new(),
+                explicit: false,
+            },
+            users: Vec::new(),
+            members: Vec::new(),
+            episodes: Vec::new(),
+        };
+        let archive = ArchiveWriter::new(Vec::new(), &backup).unwrap().finish().unwrap();
+        let err = ArchiveReader::new(archive.as_slice()).read().err().unwrap();
+        assert!(matches!(err, BackupError::UnsupportedVersion(version) if version == SHOW_BACKUP_VERSION + 1));
+
+        let backup = ShowBackup { version: SHOW_BACKUP_VERSION, ..backup };
+        let mut writer = ArchiveWriter::new(Vec::new(), &backup).unwrap();
+        writer.append("notes.txt", b"hi").unwrap();
+        let archive = writer.finish().unwrap();
+        let mut reader = ArchiveReader::new(archive.as_slice());
+        let (_, mut files) = reader.read().unwrap();
+        assert!(matches!(files.next(), Some(Err(BackupError::UnexpectedEntry(path))) if path == "notes.txt"));
+    }
+}
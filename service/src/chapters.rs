@@ -0,0 +1,156 @@
+//! Podcasting 2.0 chapters: the JSON chapters format and chapter timestamps.
+//!
+//! See <https://github.com/Podcastindex-org/podcast-namespace/blob/main/chapters/jsonChapters.md>.
+
+use entities::chapter;
+use serde::{Deserialize, Serialize};
+
+/// Content type of a JSON chapters document.
+pub const CHAPTERS_MIME_TYPE: &str = "application/json+chapters";
+
+/// A chapter found in an uploaded audio file, kept on the episode until it is imported.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedChapter {
+    pub start_ms: i64,
+    pub title: String,
+    pub url: Option<String>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChaptersDocument<'a> {
+    version: &'static str,
+    chapters: Vec<ChapterEntry<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChapterEntry<'a> {
+    start_time: f64,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    img: Option<&'a str>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+/// Renders chapters, which must already be ordered by start time, as a JSON chapters document.
+pub fn render_chapters_json(chapters: &[chapter::Model]) -> String {
+    let document = ChaptersDocument {
+        version: "1.2.0",
+        chapters: chapters
+            .iter()
+            .map(|chapter| ChapterEntry {
+                start_time: chapter.start_ms as f64 / 1000.0,
+                title: &chapter.title,
+                url: non_empty(&chapter.url),
+                img: non_empty(&chapter.image_url),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+/// Parses `HH:MM:SS`, `MM:SS` or plain seconds, each optionally with a fraction, into milliseconds.
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (seconds, minutes_and_hours) = parts.split_last()?;
+    let seconds: f64 = seconds.parse().ok().filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)?;
+    if !minutes_and_hours.is_empty() && seconds >= 60.0 {
+        return None;
+    }
+
+    let mut whole_minutes = 0i64;
+    for (i, part) in minutes_and_hours.iter().enumerate() {
+        let value: i64 = part.parse().ok().filter(|value| *value >= 0)?;
+        // Only the leading field may run past its usual range.
+        if i > 0 && value >= 60 {
+            return None;
+        }
+        whole_minutes = whole_minutes * 60 + value;
+    }
+    Some(whole_minutes * 60_000 + (seconds * 1000.0).round() as i64)
+}
+
+/// Formats milliseconds as `HH:MM:SS`, adding `.mmm` only when there is a fraction.
+pub fn format_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    let seconds = ms / 1000;
+    let base = format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    match ms % 1000 {
+        0 => base,
+        fraction => format!("{base}.{fraction:03}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::prelude::Uuid;
+
+    fn chapter(start_ms: i64, title: &str, url: Option<&str>, image_url: Option<&str>) -> chapter::Model {
+        chapter::Model {
+            id: Uuid::new_v4(),
+            episode_id: Uuid::nil(),
+            start_ms,
+            title: title.to_owned(),
+            url: url.map(str::to_owned),
+            image_url: image_url.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("90"), Some(90_000));
+        assert_eq!(parse_timestamp(" 1.5 "), Some(1500));
+        assert_eq!(parse_timestamp("01:35.5"), Some(95_500));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3_723_000));
+        assert_eq!(parse_timestamp("75:00"), Some(4_500_000), "minutes may lead past an hour");
+        assert_eq!(parse_timestamp("0:00:00.0005"), Some(1));
+    }
+
+    #[test]
+    fn rejects_malformed_timestamps() {
+        for text in ["", "  ", "abc", "-5", "1:-5", "1:60", "1:60:00", "1:2:3:4", "1::2", "inf", "NaN"] {
+            assert_eq!(parse_timestamp(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn formats_timestamps_that_parse_back() {
+        assert_eq!(format_timestamp(0), "00:00:00");
+        assert_eq!(format_timestamp(95_500), "00:01:35.500");
+        assert_eq!(format_timestamp(3_723_000), "01:02:03");
+        assert_eq!(format_timestamp(-20), "00:00:00");
+        for ms in [0, 999, 95_500, 3_723_000, 36_000_001] {
+            assert_eq!(parse_timestamp(&format_timestamp(ms)), Some(ms));
+        }
+    }
+
+    #[test]
+    fn renders_json_chapters() {
+        let chapters = [
+            chapter(0, "Intro", Some(""), None),
+            chapter(
+                95_500,
+                "Crabs & \"lobsters\"",
+                Some("https://example.com/lobsters"),
+                Some("https://example.com/lobster.jpg"),
+            ),
+            chapter(3_723_000, "Outro", None, Some("")),
+        ];
+        assert_eq!(render_chapters_json(&chapters), include_str!("fixtures/chapters.json"));
+        assert_eq!(render_chapters_json(&[]), "{\n  \"version\": \"1.2.0\",\n  \"chapters\": []\n}");
+    }
+}
@@ -0,0 +1,101 @@
+//! Episodes as portable JSON, for moving them between deployments or scripting bulk
+//! changes. Media files stay where they are; episodes keep pointing at them by URL.
+
+use crate::chapters::ImportedChapter;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{chapter, episode};
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the format changes in a way older readers cannot handle.
+pub const EPISODE_EXPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EpisodeExport {
+    pub version: u32,
+    pub episodes: Vec<ExportedEpisode>,
+}
+
+impl EpisodeExport {
+    pub fn new(episodes: Vec<ExportedEpisode>) -> Self {
+        Self {
+            version: EPISODE_EXPORT_VERSION,
+            episodes,
+        }
+    }
+
+    /// Reads an export, refusing versions this build does not know.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let export: Self = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        if export.version > EPISODE_EXPORT_VERSION {
+            return Err(format!(
+                "Export version {} is newer than the supported version {EPISODE_EXPORT_VERSION}",
+                export.version
+            ));
+        }
+        Ok(export)
+    }
+}
+
+/// An episode without anything that only makes sense in the deployment it came from,
+/// such as ids, its owner and media store keys.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedEpisode {
+    pub title: String,
+    /// The GUID the episode has in feeds: the one from the feed it was imported from, or
+    /// else the id it had where it was exported. Imports use it to skip known episodes.
+    #[serde(default)]
+    pub guid: Option<String>,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub tags: String,
+    /// Where the audio can be downloaded.
+    pub url: Option<String>,
+    #[serde(default)]
+    pub status: EpisodeStatus,
+    pub published_at: Option<DateTimeUtc>,
+    pub scheduled_for: Option<DateTimeUtc>,
+    pub media_size: Option<i64>,
+    pub media_type: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub bitrate: Option<i32>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i16>,
+    pub codec: Option<String>,
+    pub artwork_url: Option<String>,
+    #[serde(default)]
+    pub chapters: Vec<ImportedChapter>,
+}
+
+impl ExportedEpisode {
+    pub fn new(episode: &episode::Model, chapters: &[chapter::Model]) -> Self {
+        Self {
+            title: episode.title.clone(),
+            guid: Some(episode.guid.clone().unwrap_or_else(|| episode.id.to_string())),
+            summary: episode.summary.clone(),
+            tags: episode.tags.clone(),
+            url: episode.url.clone(),
+            status: episode.status,
+            published_at: episode.published_at,
+            scheduled_for: episode.scheduled_for,
+            media_size: episode.media_size,
+            media_type: episode.media_type.clone(),
+            duration_ms: episode.duration_ms,
+            bitrate: episode.bitrate,
+            sample_rate: episode.sample_rate,
+            channels: episode.channels,
+            codec: episode.codec.clone(),
+            artwork_url: episode.artwork_url.clone(),
+            chapters: chapters
+                .iter()
+                .map(|chapter| ImportedChapter {
+                    start_ms: chapter.start_ms,
+                    title: chapter.title.clone(),
+                    url: chapter.url.clone(),
+                    image_url: chapter.image_url.clone(),
+                })
+                .collect(),
+        }
+    }
+}
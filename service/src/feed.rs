@@ -0,0 +1,473 @@
+//! RSS 2.0 podcast feed generation.
+//!
+//! The builder is kept free of any web-server types so feeds can be rendered from
+//! handlers, the command line or tests alike.
+
+use crate::chapters::CHAPTERS_MIME_TYPE;
+use entities::{episode, show};
+use std::fmt::Write;
+
+const ITUNES_NS: &str = "http://www.itunes.com/dtds/podcast-1.0.dtd";
+const PODCAST_NS: &str = "https://podcastindex.org/namespace/1.0";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+
+/// Channel level metadata for a feed.
+#[derive(Clone, Debug, Default)]
+pub struct FeedChannel {
+    pub title: String,
+    /// Public homepage of the podcast.
+    pub link: String,
+    /// Absolute URL the feed itself is served from.
+    pub feed_url: String,
+    pub description: String,
+    pub language: String,
+    pub author: String,
+    pub owner_name: String,
+    pub owner_email: String,
+    pub image_url: Option<String>,
+    /// iTunes categories, optionally as `"Parent > Child"`.
+    pub categories: Vec<String>,
+    pub explicit: bool,
+}
+
+impl FeedChannel {
+    /// Channel metadata for a show whose public pages live below `public_url`.
+    pub fn for_show(show: &show::Model, public_url: &str) -> Self {
+        let public_url = public_url.trim_end_matches('/');
+        FeedChannel {
+            title: show.title.clone(),
+            feed_url: format!("{public_url}/shows/{}/feed.xml", show.id),
+            link: format!("{public_url}/podcasts/{}", show.id),
+            description: show.description.clone(),
+            language: show.language.clone(),
+            author: show.author.clone(),
+            owner_name: show.owner_name.clone(),
+            owner_email: show.owner_email.clone(),
+            image_url: show.artwork_url.clone().filter(|url| !url.is_empty()),
+            categories: split_tags(&show.category),
+            explicit: show.explicit,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FeedEnclosure {
+    pub url: String,
+    pub length: u64,
+    pub mime_type: String,
+}
+
+/// A `podcast:transcript` link.
+#[derive(Clone, Debug)]
+pub struct FeedTranscript {
+    pub url: String,
+    pub mime_type: String,
+    pub language: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FeedItem {
+    pub guid: String,
+    pub title: String,
+    pub summary: String,
+    pub link: Option<String>,
+    pub enclosure: Option<FeedEnclosure>,
+    pub duration_seconds: Option<u64>,
+    pub explicit: Option<bool>,
+    /// Already formatted as RFC 2822.
+    pub pub_date: Option<String>,
+    pub image_url: Option<String>,
+    pub keywords: Vec<String>,
+    /// Where the episode's JSON chapters are served, if it has any.
+    pub chapters_url: Option<String>,
+    pub transcripts: Vec<FeedTranscript>,
+}
+
+impl From<&episode::Model> for FeedItem {
+    fn from(episode: &episode::Model) -> Self {
+        FeedItem {
+            guid: episode.guid.clone().unwrap_or_else(|| episode.id.to_string()),
+            title: episode.title.clone(),
+            summary: episode.summary.clone(),
+            enclosure: episode
+                .url
+                .as_deref()
+                .filter(|url| !url.is_empty())
+                .map(|url| FeedEnclosure {
+                    url: url.to_string(),
+                    length: episode.media_size.unwrap_or_default().max(0) as u64,
+                    mime_type: episode
+                        .media_type
+                        .clone()
+                        .unwrap_or_else(|| mime_type_for(url).to_string()),
+                }),
+            pub_date: episode.published_at.map(|published_at| published_at.to_rfc2822()),
+            duration_seconds: episode
+                .duration_ms
+                .map(|duration_ms| (duration_ms.max(0) as u64 + 500) / 1000),
+            image_url: episode.artwork_url.clone().filter(|url| !url.is_empty()),
+            keywords: split_tags(&episode.tags),
+            ..Default::default()
+        }
+    }
+}
+
+/// Splits a comma separated tag string into trimmed, non-empty tags.
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Guesses a MIME type for audio or artwork from the extension of a file name or URL.
+pub fn mime_type_for(path: &str) -> &'static str {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let extension = path.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" | "mp4" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Formats a number of seconds as the `HH:MM:SS` form expected by `itunes:duration`.
+pub fn format_duration(seconds: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Escapes text for use in XML element content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn bool_text(value: bool) -> &'static str {
+    if value { "true" } else { "false" }
+}
+
+fn element(out: &mut String, indent: &str, name: &str, text: &str) {
+    let _ = writeln!(out, "{indent}<{name}>{}</{name}>", escape_xml(text));
+}
+
+fn write_category(out: &mut String, category: &str) {
+    let mut parts = category.split('>').map(str::trim).filter(|p| !p.is_empty());
+    let Some(parent) = parts.next() else {
+        return;
+    };
+    match parts.next() {
+        Some(child) => {
+            let _ = writeln!(
+                out,
+                "    <itunes:category text=\"{}\">\n      <itunes:category text=\"{}\"/>\n    </itunes:category>",
+                escape_xml(parent),
+                escape_xml(child)
+            );
+        }
+        None => {
+            let _ = writeln!(out, "    <itunes:category text=\"{}\"/>", escape_xml(parent));
+        }
+    }
+}
+
+fn write_item(out: &mut String, item: &FeedItem) {
+    out.push_str("    <item>\n");
+    let indent = "      ";
+    element(out, indent, "title", &item.title);
+    element(out, indent, "description", &item.summary);
+    element(out, indent, "itunes:summary", &item.summary);
+    let _ = writeln!(
+        out,
+        "{indent}<guid isPermaLink=\"false\">{}</guid>",
+        escape_xml(&item.guid)
+    );
+    if let Some(link) = &item.link {
+        element(out, indent, "link", link);
+    }
+    if let Some(pub_date) = &item.pub_date {
+        element(out, indent, "pubDate", pub_date);
+    }
+    if let Some(enclosure) = &item.enclosure {
+        let _ = writeln!(
+            out,
+            "{indent}<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>",
+            escape_xml(&enclosure.url),
+            enclosure.length,
+            escape_xml(&enclosure.mime_type)
+        );
+    }
+    if let Some(duration) = item.duration_seconds {
+        element(out, indent, "itunes:duration", &format_duration(duration));
+    }
+    if let Some(explicit) = item.explicit {
+        element(out, indent, "itunes:explicit", bool_text(explicit));
+    }
+    if let Some(image_url) = &item.image_url {
+        let _ = writeln!(out, "{indent}<itunes:image href=\"{}\"/>", escape_xml(image_url));
+    }
+    if !item.keywords.is_empty() {
+        element(out, indent, "itunes:keywords", &item.keywords.join(","));
+    }
+    if let Some(chapters_url) = &item.chapters_url {
+        let _ = writeln!(
+            out,
+            "{indent}<podcast:chapters url=\"{}\" type=\"{CHAPTERS_MIME_TYPE}\"/>",
+            escape_xml(chapters_url)
+        );
+    }
+    for transcript in &item.transcripts {
+        let _ = writeln!(
+            out,
+            "{indent}<podcast:transcript url=\"{}\" type=\"{}\" language=\"{}\"/>",
+            escape_xml(&transcript.url),
+            escape_xml(&transcript.mime_type),
+            escape_xml(&transcript.language)
+        );
+    }
+    out.push_str("    </item>\n");
+}
+
+/// Renders a complete RSS 2.0 document with the `itunes:` and `podcast:` namespaces.
+pub fn render_feed(channel: &FeedChannel, items: &[FeedItem]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<rss version=\"2.0\" xmlns:itunes=\"{ITUNES_NS}\" xmlns:podcast=\"{PODCAST_NS}\" xmlns:atom=\"{ATOM_NS}\">"
+    );
+    out.push_str("  <channel>\n");
+    let indent = "    ";
+    element(&mut out, indent, "title", &channel.title);
+    element(&mut out, indent, "link", &channel.link);
+    let _ = writeln!(
+        out,
+        "{indent}<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+        escape_xml(&channel.feed_url)
+    );
+    element(&mut out, indent, "description", &channel.description);
+    element(&mut out, indent, "language", &channel.language);
+    element(&mut out, indent, "itunes:author", &channel.author);
+    element(&mut out, indent, "itunes:summary", &channel.description);
+    element(&mut out, indent, "itunes:explicit", bool_text(channel.explicit));
+    let _ = writeln!(
+        out,
+        "{indent}<itunes:owner>\n{indent}  <itunes:name>{}</itunes:name>\n{indent}  <itunes:email>{}</itunes:email>\n{indent}</itunes:owner>",
+        escape_xml(&channel.owner_name),
+        escape_xml(&channel.owner_email)
+    );
+    if let Some(image_url) = &channel.image_url {
+        let _ = writeln!(out, "{indent}<itunes:image href=\"{}\"/>", escape_xml(image_url));
+        let _ = writeln!(
+            out,
+            "{indent}<image>\n{indent}  <url>{}</url>\n{indent}  <title>{}</title>\n{indent}  <link>{}</link>\n{indent}</image>",
+            escape_xml(image_url),
+            escape_xml(&channel.title),
+            escape_xml(&channel.link)
+        );
+    }
+    for category in &channel.categories {
+        write_category(&mut out, category);
+    }
+    element(&mut out, indent, "podcast:locked", "no");
+    for item in items {
+        write_item(&mut out, item);
+    }
+    out.push_str("  </channel>\n</rss>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed_import::parse_feed;
+    use entities::sea_orm_active_enums::EpisodeStatus;
+    use sea_orm::prelude::Uuid;
+
+    fn channel() -> FeedChannel {
+        FeedChannel {
+            title: "Crabs & <Claws>".to_string(),
+            link: "https://pods.example.com/podcasts/1".to_string(),
+            feed_url: "https://pods.example.com/shows/1/feed.xml?a=1&b=2".to_string(),
+            description: "\"Sideways\" isn't a direction".to_string(),
+            language: "en".to_string(),
+            author: "Ann".to_string(),
+            owner_name: "Ann & Bob".to_string(),
+            owner_email: "ann@example.com".to_string(),
+            image_url: Some("https://pods.example.com/cover.png".to_string()),
+            categories: vec!["Science > Natural Sciences".to_string(), "Comedy".to_string()],
+            explicit: false,
+        }
+    }
+
+    fn episode() -> episode::Model {
+        episode::Model {
+            id: Uuid::nil(),
+            title: "One <b>bold</b> claim".to_string(),
+            summary: "Fish & chips".to_string(),
+            tags: "crabs, , shells ".to_string(),
+            url: Some("https://cdn.example.com/one.m4a?sig=a&exp=1".to_string()),
+            user_id: Uuid::nil(),
+            media_key: None,
+            media_size: Some(12_345),
+            media_type: None,
+            show_id: None,
+            status: EpisodeStatus::Published,
+            published_at: Some("2026-01-02T03:04:05Z".parse().unwrap()),
+            scheduled_for: None,
+            duration_ms: Some(3_723_500),
+            bitrate: None,
+            sample_rate: None,
+            channels: None,
+            codec: None,
+            artwork_url: Some(String::new()),
+            embedded_chapters: None,
+            guid: Some("old-host-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+        assert_eq!(escape_xml("plain ünïcode"), "plain ünïcode");
+
+        let feed = render_feed(&channel(), &[FeedItem::from(&episode())]);
+        assert!(feed.contains("<title>Crabs &amp; &lt;Claws&gt;</title>"));
+        assert!(feed.contains("href=\"https://pods.example.com/shows/1/feed.xml?a=1&amp;b=2\""));
+        assert!(feed.contains("<itunes:name>Ann &amp; Bob</itunes:name>"));
+        assert!(feed.contains("<title>One &lt;b&gt;bold&lt;/b&gt; claim</title>"));
+        assert!(!feed.contains("<b>"));
+
+        // What is escaped comes back unchanged.
+        let parsed = parse_feed(&feed).unwrap();
+        assert_eq!(parsed.show.title, "Crabs & <Claws>");
+        assert_eq!(parsed.show.description, "\"Sideways\" isn't a direction");
+        assert_eq!(parsed.episodes[0].title, "One <b>bold</b> claim");
+        assert_eq!(parsed.episodes[0].summary, "Fish & chips");
+    }
+
+    #[test]
+    fn describes_enclosures() {
+        let item = FeedItem::from(&episode());
+        let enclosure = item.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.length, 12_345);
+        assert_eq!(enclosure.mime_type, "audio/mp4", "guessed from the URL without its query");
+        assert_eq!(item.guid, "old-host-1");
+        assert_eq!(item.image_url, None);
+        assert_eq!(item.keywords, ["crabs", "shells"]);
+
+        let feed = render_feed(&channel(), &[item]);
+        assert!(feed.contains(
+            r#"<enclosure url="https://cdn.example.com/one.m4a?sig=a&amp;exp=1" length="12345" type="audio/mp4"/>"#
+        ));
+
+        let stored = episode::Model {
+            media_size: Some(-1),
+            media_type: Some("audio/mpeg".to_string()),
+            guid: None,
+            ..episode()
+        };
+        let item = FeedItem::from(&stored);
+        let enclosure = item.enclosure.as_ref().unwrap();
+        assert_eq!((enclosure.length, enclosure.mime_type.as_str()), (0, "audio/mpeg"));
+        assert_eq!(item.guid, Uuid::nil().to_string());
+
+        let without_audio = episode::Model { url: Some(String::new()), ..episode() };
+        let item = FeedItem::from(&without_audio);
+        assert!(item.enclosure.is_none());
+        assert!(!render_feed(&channel(), &[item]).contains("<enclosure"));
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0), "00:00:00");
+        assert_eq!(format_duration(59), "00:00:59");
+        assert_eq!(format_duration(3_723), "01:02:03");
+        assert_eq!(format_duration(360_000), "100:00:00");
+
+        // Milliseconds are rounded to the nearest second.
+        let item = FeedItem::from(&episode());
+        assert_eq!(item.duration_seconds, Some(3_724));
+        let feed = render_feed(&channel(), &[item]);
+        assert!(feed.contains("<itunes:duration>01:02:04</itunes:duration>"));
+    }
+
+    #[test]
+    fn nests_categories() {
+        let feed = render_feed(&channel(), &[]);
+        assert!(feed.contains(
+            "    <itunes:category text=\"Science\">\n      <itunes:category text=\"Natural Sciences\"/>\n    </itunes:category>\n"
+        ));
+        assert!(feed.contains("    <itunes:category text=\"Comedy\"/>\n"));
+
+        let odd = FeedChannel {
+            categories: vec![" > ".to_string(), "Arts >".to_string(), "Kids & Family".to_string()],
+            ..channel()
+        };
+        let feed = render_feed(&odd, &[]);
+        assert_eq!(feed.matches("<itunes:category").count(), 2);
+        assert!(feed.contains("<itunes:category text=\"Arts\"/>"));
+        assert!(feed.contains("<itunes:category text=\"Kids &amp; Family\"/>"));
+    }
+
+    #[test]
+    fn links_chapters_and_transcripts() {
+        let item = FeedItem {
+            chapters_url: Some("https://pods.example.com/episodes/1/chapters.json".to_string()),
+            transcripts: vec![
+                FeedTranscript {
+                    url: "https://pods.example.com/episodes/1/transcripts/en.vtt".to_string(),
+                    mime_type: "text/vtt".to_string(),
+                    language: "en".to_string(),
+                },
+                FeedTranscript {
+                    url: "https://pods.example.com/episodes/1/transcripts/de.srt?v=1&x=2".to_string(),
+                    mime_type: "application/x-subrip".to_string(),
+                    language: "de".to_string(),
+                },
+            ],
+            ..FeedItem::from(&episode())
+        };
+        let feed = render_feed(&channel(), &[item]);
+        assert!(feed.contains(&format!(
+            "<podcast:chapters url=\"https://pods.example.com/episodes/1/chapters.json\" type=\"{CHAPTERS_MIME_TYPE}\"/>"
+        )));
+        assert!(feed.contains(
+            r#"<podcast:transcript url="https://pods.example.com/episodes/1/transcripts/en.vtt" type="text/vtt" language="en"/>"#
+        ));
+        assert!(feed.contains(
+            r#"<podcast:transcript url="https://pods.example.com/episodes/1/transcripts/de.srt?v=1&amp;x=2" type="application/x-subrip" language="de"/>"#
+        ));
+        assert!(feed.contains(&format!("xmlns:podcast=\"{PODCAST_NS}\"")));
+
+        let plain = render_feed(&channel(), &[FeedItem::from(&episode())]);
+        assert!(!plain.contains("podcast:chapters"));
+        assert!(!plain.contains("podcast:transcript"));
+    }
+}
@@ -0,0 +1,699 @@
+//! Importing podcasts from the RSS feed of another host, such as Anchor, Libsyn or
+//! Buzzsprout.
+//!
+//! A feed file is parsed into an [`ImportedFeed`] and compared with what is already stored
+//! in a [`FeedImportPlan`], which doubles as the dry-run report. Only an accepted plan is
+//! written, with `Mutation::apply_feed_import`. Episodes are matched by their GUID, so
+//! importing a newer copy of the same feed only adds and updates what changed.
+
+use crate::feed::split_tags;
+use chrono::DateTime;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{episode, show};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use sea_orm::prelude::{DateTimeUtc, Uuid};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum FeedImportError {
+    #[error("the file is not valid XML: {0}")]
+    Xml(String),
+    #[error("the file is not an RSS feed: it has no rss channel")]
+    NoChannel,
+}
+
+/// The channel of a feed, as a show.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ImportedShow {
+    pub title: String,
+    pub description: String,
+    pub language: String,
+    pub author: String,
+    pub owner_name: String,
+    pub owner_email: String,
+    pub artwork_url: Option<String>,
+    /// iTunes categories, comma separated, with subcategories as `"Parent > Child"`.
+    pub category: String,
+    pub explicit: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ImportedEnclosure {
+    pub url: String,
+    pub length: Option<i64>,
+    pub mime_type: Option<String>,
+}
+
+/// An item of a feed, as an episode.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportedFeedEpisode {
+    /// The item's `guid`, or its enclosure URL for feeds without GUIDs.
+    pub guid: String,
+    pub title: String,
+    pub summary: String,
+    /// `itunes:keywords` and categories, comma separated.
+    pub tags: String,
+    pub published_at: Option<DateTimeUtc>,
+    pub enclosure: Option<ImportedEnclosure>,
+    pub duration_ms: Option<i64>,
+    pub artwork_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportedFeed {
+    pub show: ImportedShow,
+    pub episodes: Vec<ImportedFeedEpisode>,
+    /// Items that were skipped or only partly understood.
+    pub warnings: Vec<String>,
+}
+
+/// A field of a stored episode that the feed would change.
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// What importing an item would do.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", content = "changes", rename_all = "snake_case")]
+pub enum EpisodeChange {
+    New,
+    Changed(Vec<FieldChange>),
+    Unchanged,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlannedEpisode {
+    pub episode: ImportedFeedEpisode,
+    /// The stored episode with the same GUID.
+    pub existing_id: Option<Uuid>,
+    pub change: EpisodeChange,
+}
+
+/// Everything an import would do, for showing before it is done.
+#[derive(Clone, Debug, Serialize)]
+pub struct FeedImportPlan {
+    pub show: ImportedShow,
+    /// The show the episodes go into, or `None` to create one from the feed.
+    pub show_id: Option<Uuid>,
+    pub episodes: Vec<PlannedEpisode>,
+    pub warnings: Vec<String>,
+    pub new_count: usize,
+    pub changed_count: usize,
+    pub unchanged_count: usize,
+}
+
+/// What an applied import did.
+#[derive(Clone, Debug)]
+pub struct FeedImportResult {
+    pub show_id: Uuid,
+    /// Ids of the created episodes, with the enclosure URL each was created with.
+    pub created: Vec<(Uuid, Option<String>)>,
+    pub updated: usize,
+}
+
+impl ImportedShow {
+    /// The show to create from the channel.
+    pub fn to_model(&self) -> show::Model {
+        show::Model {
+            id: Uuid::nil(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            artwork_url: self.artwork_url.clone(),
+            language: self.language.clone(),
+            category: self.category.clone(),
+            author: self.author.clone(),
+            owner_name: self.owner_name.clone(),
+            owner_email: self.owner_email.clone(),
+            explicit: self.explicit,
+        }
+    }
+}
+
+impl FeedImportPlan {
+    /// Compares `feed` with `existing`, the episodes of the show with id `show_id`, or
+    /// with nothing for a new show.
+    pub fn new(feed: ImportedFeed, show_id: Option<Uuid>, existing: &[episode::Model]) -> Self {
+        let episodes: Vec<PlannedEpisode> = feed
+            .episodes
+            .into_iter()
+            .map(|episode| {
+                let stored = existing
+                    .iter()
+                    .find(|stored| stored.guid.as_deref() == Some(episode.guid.as_str()));
+                let change = match stored {
+                    None => EpisodeChange::New,
+                    Some(stored) => {
+                        let changes = field_changes(stored, &episode);
+                        if changes.is_empty() {
+                            EpisodeChange::Unchanged
+                        } else {
+                            EpisodeChange::Changed(changes)
+                        }
+                    }
+                };
+                PlannedEpisode {
+                    existing_id: stored.map(|stored| stored.id),
+                    episode,
+                    change,
+                }
+            })
+            .collect();
+        let count = |wanted: fn(&EpisodeChange) -> bool| {
+            episodes.iter().filter(|planned| wanted(&planned.change)).count()
+        };
+
+        FeedImportPlan {
+            new_count: count(|change| matches!(change, EpisodeChange::New)),
+            changed_count: count(|change| matches!(change, EpisodeChange::Changed(_))),
+            unchanged_count: count(|change| matches!(change, EpisodeChange::Unchanged)),
+            show: feed.show,
+            show_id,
+            episodes,
+            warnings: feed.warnings,
+        }
+    }
+
+    /// The plan as plain text, one line per new or changed episode.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let target = if self.show_id.is_some() { "existing show" } else { "new show" };
+        let _ = writeln!(report, "Show: {} ({target})", self.show.title);
+        for planned in &self.episodes {
+            let episode = &planned.episode;
+            let date = episode
+                .published_at
+                .map(|published_at| published_at.format(" (%Y-%m-%d)").to_string())
+                .unwrap_or_default();
+            match &planned.change {
+                EpisodeChange::New => {
+                    let _ = writeln!(report, "  + {}{date}", episode.title);
+                }
+                EpisodeChange::Changed(changes) => {
+                    let _ = writeln!(report, "  ~ {}{date}", episode.title);
+                    for change in changes {
+                        let _ = writeln!(
+                            report,
+                            "      {}: {:?} -> {:?}",
+                            change.field,
+                            shorten(&change.old),
+                            shorten(&change.new)
+                        );
+                    }
+                }
+                EpisodeChange::Unchanged => {}
+            }
+        }
+        for warning in &self.warnings {
+            let _ = writeln!(report, "  ! {warning}");
+        }
+        let _ = write!(
+            report,
+            "{} new, {} changed, {} unchanged",
+            self.new_count, self.changed_count, self.unchanged_count
+        );
+        report
+    }
+}
+
+/// Differences between a stored episode and the item with its GUID, as far as importing
+/// would apply them. The audio URL of an episode whose file was copied into the media
+/// store is expected to differ.
+fn field_changes(stored: &episode::Model, imported: &ImportedFeedEpisode) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &'static str, old: String, new: String| {
+        if old != new {
+            changes.push(FieldChange { field, old, new });
+        }
+    };
+    let date = |date: Option<DateTimeUtc>| date.map(|date| date.to_rfc3339()).unwrap_or_default();
+
+    compare("title", stored.title.clone(), imported.title.clone());
+    compare("summary", stored.summary.clone(), imported.summary.clone());
+    compare("tags", stored.tags.clone(), imported.tags.clone());
+    // Only a published episode takes its date from the feed; others go through the workflow.
+    if stored.status == EpisodeStatus::Published && imported.published_at.is_some() {
+        compare("published", date(stored.published_at), date(imported.published_at));
+    }
+    if stored.media_key.is_none() {
+        let url = imported.enclosure.as_ref().map(|enclosure| enclosure.url.clone());
+        compare("audio", stored.url.clone().unwrap_or_default(), url.unwrap_or_default());
+    }
+    changes
+}
+
+fn shorten(text: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    if text.chars().count() <= MAX_CHARS {
+        text.to_string()
+    } else {
+        text.chars().take(MAX_CHARS).collect::<String>() + "…"
+    }
+}
+
+/// An XML element with everything below it.
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The trimmed text of the first child called `name`, unless it is empty.
+    fn text_of(&self, name: &str) -> Option<String> {
+        let text = self.child(name)?.text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+fn xml_error(err: impl std::fmt::Display) -> FeedImportError {
+    FeedImportError::Xml(err.to_string())
+}
+
+fn start_node(start: &BytesStart) -> Result<Node, FeedImportError> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(xml_error)?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute.unescape_value().map_err(xml_error)?.into_owned();
+        attributes.push((key, value));
+    }
+    Ok(Node {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes,
+        ..Default::default()
+    })
+}
+
+/// Reads the whole document into a tree under a nameless root.
+fn parse_tree(xml: &str) -> Result<Node, FeedImportError> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack = vec![Node::default()];
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(start) => stack.push(start_node(&start)?),
+            Event::Empty(start) => {
+                let node = start_node(&start)?;
+                stack.last_mut().expect("the root is never popped").children.push(node);
+            }
+            Event::End(_) => {
+                if stack.len() < 2 {
+                    return Err(FeedImportError::Xml("unexpected closing tag".to_string()));
+                }
+                let node = stack.pop().expect("checked above");
+                stack.last_mut().expect("checked above").children.push(node);
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(xml_error)?;
+                stack.last_mut().expect("the root is never popped").text.push_str(&text);
+            }
+            Event::CData(data) => {
+                let data = data.into_inner();
+                stack
+                    .last_mut()
+                    .expect("the root is never popped")
+                    .text
+                    .push_str(&String::from_utf8_lossy(&data));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if stack.len() != 1 {
+        return Err(FeedImportError::Xml("the document ends inside an element".to_string()));
+    }
+    Ok(stack.pop().expect("checked above"))
+}
+
+/// `itunes:duration` as seconds, `MM:SS` or `HH:MM:SS`, in milliseconds.
+fn parse_duration(duration: &str) -> Option<i64> {
+    let mut seconds = 0.0;
+    for part in duration.split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    (seconds >= 0.0).then(|| (seconds * 1000.0).round() as i64)
+}
+
+fn parse_explicit(explicit: &str) -> bool {
+    matches!(explicit.to_ascii_lowercase().as_str(), "yes" | "true" | "explicit")
+}
+
+/// `itunes:category` elements as `"Parent > Child"`, comma separated.
+fn categories(channel: &Node) -> String {
+    let mut categories = Vec::new();
+    for category in channel.children("itunes:category") {
+        let Some(parent) = category.attribute("text") else {
+            continue;
+        };
+        let children: Vec<&str> = category
+            .children("itunes:category")
+            .filter_map(|child| child.attribute("text"))
+            .collect();
+        if children.is_empty() {
+            categories.push(parent.to_string());
+        }
+        for child in children {
+            categories.push(format!("{parent} > {child}"));
+        }
+    }
+    categories.join(", ")
+}
+
+fn image_url(node: &Node) -> Option<String> {
+    node.child("itunes:image")
+        .and_then(|image| image.attribute("href"))
+        .map(str::to_string)
+        .or_else(|| node.child("image").and_then(|image| image.text_of("url")))
+}
+
+fn parse_item(item: &Node, number: usize, warnings: &mut Vec<String>) -> Option<ImportedFeedEpisode> {
+    let enclosure = item.child("enclosure").and_then(|enclosure| {
+        Some(ImportedEnclosure {
+            url: enclosure.attribute("url")?.to_string(),
+            length: enclosure
+                .attribute("length")
+                .and_then(|length| length.parse().ok())
+                .filter(|&length: &i64| length > 0),
+            mime_type: enclosure.attribute("type").map(str::to_string),
+        })
+    });
+    let title = item.text_of("title").or_else(|| item.text_of("itunes:title"));
+    let Some(guid) = item
+        .text_of("guid")
+        .or_else(|| enclosure.as_ref().map(|enclosure| enclosure.url.clone()))
+    else {
+        warnings.push(format!(
+            "Skipped item {number} ({}): it has neither a guid nor an enclosure",
+            title.as_deref().unwrap_or("untitled")
+        ));
+        return None;
+    };
+    let title = title.unwrap_or_else(|| {
+        warnings.push(format!("Item {number} has no title"));
+        format!("Episode {number}")
+    });
+
+    let published_at = item.text_of("pubDate").and_then(|date| {
+        match DateTime::parse_from_rfc2822(&date) {
+            Ok(date) => Some(date.to_utc()),
+            Err(_) => {
+                warnings.push(format!("{title}: cannot read the date {date:?}, imported as a draft"));
+                None
+            }
+        }
+    });
+    let mut tags: Vec<String> = item
+        .text_of("itunes:keywords")
+        .map(|keywords| split_tags(&keywords))
+        .unwrap_or_default();
+    for category in item.children("category") {
+        let category = category.text.trim();
+        if !category.is_empty() && !tags.iter().any(|tag| tag == category) {
+            tags.push(category.to_string());
+        }
+    }
+
+    Some(ImportedFeedEpisode {
+        guid,
+        summary: item
+            .text_of("description")
+            .or_else(|| item.text_of("itunes:summary"))
+            .or_else(|| item.text_of("content:encoded"))
+            .unwrap_or_default(),
+        tags: tags.join(", "),
+        published_at,
+        enclosure,
+        duration_ms: item.text_of("itunes:duration").and_then(|duration| parse_duration(&duration)),
+        artwork_url: image_url(item),
+        title,
+    })
+}
+
+/// Reads an RSS 2.0 feed with iTunes extensions. Items are returned oldest first.
+pub fn parse_feed(xml: &str) -> Result<ImportedFeed, FeedImportError> {
+    let root = parse_tree(xml)?;
+    let channel = root
+        .child("rss")
+        .and_then(|rss| rss.child("channel"))
+        .ok_or(FeedImportError::NoChannel)?;
+
+    let owner = channel.child("itunes:owner");
+    let author = channel.text_of("itunes:author").unwrap_or_default();
+    let show = ImportedShow {
+        title: channel.text_of("title").unwrap_or_else(|| "Imported podcast".to_string()),
+        description: channel
+            .text_of("description")
+            .or_else(|| channel.text_of("itunes:summary"))
+            .unwrap_or_default(),
+        language: channel.text_of("language").unwrap_or_else(|| "en".to_string()),
+        owner_name: owner
+            .and_then(|owner| owner.text_of("itunes:name"))
+            .unwrap_or_else(|| author.clone()),
+        owner_email: owner.and_then(|owner| owner.text_of("itunes:email")).unwrap_or_default(),
+        author,
+        artwork_url: image_url(channel),
+        category: categories(channel),
+        explicit: channel
+            .text_of("itunes:explicit")
+            .is_some_and(|explicit| parse_explicit(&explicit)),
+    };
+
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::new();
+    let mut episodes = Vec::new();
+    for (i, item) in channel.children("item").enumerate() {
+        let Some(episode) = parse_item(item, i + 1, &mut warnings) else {
+            continue;
+        };
+        if !seen.insert(episode.guid.clone()) {
+            warnings.push(format!("Skipped {}: its guid is used by an earlier item", episode.title));
+            continue;
+        }
+        episodes.push(episode);
+    }
+    // Feeds list the newest episode first.
+    episodes.sort_by_key(|episode| episode.published_at);
+
+    Ok(ImportedFeed { show, episodes, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+    use crate::{Mutation, Query};
+    use chrono::TimeZone;
+    use entities::show::Entity as Show;
+    use sea_orm::{DbConn, EntityTrait, PaginatorTrait};
+
+    const FEED: &str = include_str!("fixtures/import.xml");
+
+    fn date(y: i32, m: u32, d: u32, h: u32) -> Option<DateTimeUtc> {
+        Some(chrono::Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn parses_feed_files() {
+        let feed = parse_feed(FEED).unwrap();
+
+        let show = &feed.show;
+        assert_eq!(show.title, "Crab Talk");
+        assert_eq!(show.description, "Conversations <b>about</b> crabs.");
+        assert_eq!(show.language, "en-gb");
+        assert_eq!(show.author, "Jane Doe");
+        assert_eq!(show.owner_name, "Crab Media");
+        assert_eq!(show.owner_email, "hello@crab.example");
+        assert_eq!(show.artwork_url.as_deref(), Some("https://crab.example/show.jpg"));
+        assert_eq!(show.category, "Science > Nature, Science > Life Sciences, Comedy");
+        assert!(show.explicit);
+
+        let guids: Vec<&str> = feed.episodes.iter().map(|episode| episode.guid.as_str()).collect();
+        // Undated items come first, then the oldest.
+        assert_eq!(guids, ["https://cdn.example/behind.mp3", "crab-1", "crab-2", "crab-3"]);
+
+        let behind = &feed.episodes[0];
+        assert_eq!(behind.title, "Behind the scenes");
+        assert_eq!(behind.summary, "<p>Recorded on the beach.</p>");
+        assert_eq!(behind.published_at, None);
+        assert_eq!(behind.duration_ms, Some(750_000));
+
+        let first = &feed.episodes[1];
+        assert_eq!(first.summary, "Where it all started.");
+        assert_eq!(first.published_at, date(2024, 9, 2, 8));
+        assert_eq!(first.duration_ms, Some(95_000));
+        assert_eq!(first.enclosure.as_ref().unwrap().length, None, "a zero length is unknown");
+
+        let molting = &feed.episodes[2];
+        assert_eq!(molting.title, "Molting season");
+        assert_eq!(molting.summary, "Shells & what comes after them.");
+        assert_eq!(molting.tags, "molting, shells, Nature");
+        assert_eq!(molting.published_at, date(2024, 9, 10, 4));
+        assert_eq!(molting.duration_ms, Some(3_723_000));
+        assert_eq!(molting.artwork_url.as_deref(), Some("https://crab.example/2.jpg"));
+        assert_eq!(
+            molting.enclosure,
+            Some(ImportedEnclosure {
+                url: "https://cdn.example/crab-2.mp3".to_string(),
+                length: Some(2048),
+                mime_type: Some("audio/mpeg".to_string()),
+            })
+        );
+
+        assert_eq!(
+            feed.warnings,
+            [
+                "Skipped Molting season (repost): its guid is used by an earlier item",
+                "Skipped item 4 (Bonus: no identity): it has neither a guid nor an enclosure",
+                "Behind the scenes: cannot read the date \"sometime next week\", imported as a draft",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_feeds() {
+        for xml in [
+            "<rss><channel><title>Cut short</title>",
+            "<rss><channel></rss>",
+            "<rss><channel></channel></rss></rss>",
+            "<rss><channel><title a=\"1\" a=\"2\">Twice</title></channel></rss>",
+            "<rss><channel><title>&bogus;</title></channel></rss>",
+        ] {
+            assert!(matches!(parse_feed(xml), Err(FeedImportError::Xml(_))), "{xml}");
+        }
+        assert_eq!(parse_feed("").unwrap_err(), FeedImportError::NoChannel);
+        assert_eq!(parse_feed("<feed><entry/></feed>").unwrap_err(), FeedImportError::NoChannel);
+        assert_eq!(parse_feed("<channel><item/></channel>").unwrap_err(), FeedImportError::NoChannel);
+
+        let feed = parse_feed("<rss><channel/></rss>").unwrap();
+        assert_eq!(feed.show.title, "Imported podcast");
+        assert_eq!(feed.show.language, "en");
+        assert!(feed.episodes.is_empty());
+    }
+
+    async fn plan_for(db: &DbConn, xml: &str, show_id: Option<Uuid>) -> FeedImportPlan {
+        let existing = match show_id {
+            Some(show_id) => Query::find_all_episodes(db, Some(show_id)).await.unwrap(),
+            None => Vec::new(),
+        };
+        FeedImportPlan::new(parse_feed(xml).unwrap(), show_id, &existing)
+    }
+
+    #[tokio::test]
+    async fn imports_new_shows() {
+        let db = test_db().await;
+        let user = Mutation::create_user(&db, "jane@example.com", "Jane").await.unwrap();
+        let now = date(2025, 1, 1, 0).unwrap();
+
+        // Planning is the dry run: it writes nothing.
+        let plan = plan_for(&db, FEED, None).await;
+        assert_eq!((plan.new_count, plan.changed_count, plan.unchanged_count), (4, 0, 0));
+        assert_eq!(Show::find().count(&db).await.unwrap(), 0);
+        assert!(Query::find_all_episodes(&db, None).await.unwrap().is_empty());
+        let report = plan.report();
+        assert!(report.starts_with(
+            "Show: Crab Talk (new show)\n  + Behind the scenes\n  + The first crab (2024-09-02)\n"
+        ));
+        assert!(report.contains("  ! Behind the scenes: cannot read the date"));
+        assert!(report.ends_with("4 new, 0 changed, 0 unchanged"));
+
+        let result = Mutation::apply_feed_import(&db, &plan, user.id, now).await.unwrap();
+        assert_eq!(result.created.len(), 4);
+        assert_eq!(result.updated, 0);
+        let show = Query::find_show_by_id(&db, result.show_id).await.unwrap().unwrap();
+        assert_eq!(show.title, "Crab Talk");
+        assert_eq!(show.category, "Science > Nature, Science > Life Sciences, Comedy");
+
+        let episodes = Query::find_all_episodes(&db, Some(show.id)).await.unwrap();
+        let stored = |guid: &str| {
+            episodes.iter().find(|episode| episode.guid.as_deref() == Some(guid)).unwrap()
+        };
+        let behind = stored("https://cdn.example/behind.mp3");
+        assert_eq!((behind.status, behind.published_at), (EpisodeStatus::Draft, None));
+        let molting = stored("crab-2");
+        assert_eq!(molting.status, EpisodeStatus::Published);
+        assert_eq!(molting.published_at, date(2024, 9, 10, 4));
+        assert_eq!(molting.url.as_deref(), Some("https://cdn.example/crab-2.mp3"));
+        assert_eq!((molting.media_size, molting.media_type.as_deref()), (Some(2048), Some("audio/mpeg")));
+        assert_eq!(molting.user_id, user.id);
+        let future = stored("crab-3");
+        assert_eq!(future.status, EpisodeStatus::Scheduled);
+        assert_eq!((future.published_at, future.scheduled_for), (None, date(2100, 1, 1, 0)));
+    }
+
+    #[tokio::test]
+    async fn reimports_only_change_what_changed() {
+        let db = test_db().await;
+        let user = Mutation::create_user(&db, "jane@example.com", "Jane").await.unwrap();
+        let now = date(2025, 1, 1, 0).unwrap();
+        let plan = plan_for(&db, FEED, None).await;
+        let show_id = Mutation::apply_feed_import(&db, &plan, user.id, now).await.unwrap().show_id;
+        let imported = Query::find_all_episodes(&db, Some(show_id)).await.unwrap();
+
+        // The same file again matches every episode by its GUID.
+        let plan = plan_for(&db, FEED, Some(show_id)).await;
+        assert_eq!((plan.new_count, plan.changed_count, plan.unchanged_count), (0, 0, 4));
+        assert!(plan.episodes.iter().all(|planned| planned.existing_id.is_some()));
+        assert!(plan.report().starts_with("Show: Crab Talk (existing show)\n"));
+        let result = Mutation::apply_feed_import(&db, &plan, user.id, now).await.unwrap();
+        assert_eq!((result.show_id, result.created.len(), result.updated), (show_id, 0, 0));
+        assert_eq!(Query::find_all_episodes(&db, Some(show_id)).await.unwrap(), imported);
+        assert_eq!(Show::find().count(&db).await.unwrap(), 1);
+
+        let newer = FEED
+            .replace("<title>The first crab</title>", "<title>The very first crab</title>")
+            .replace(
+                "<title>Molting season (repost)</title>\n      <guid>crab-2</guid>",
+                "<title>Molting season (repost)</title>\n      <guid>crab-4</guid>",
+            );
+        let plan = plan_for(&db, &newer, Some(show_id)).await;
+        assert_eq!((plan.new_count, plan.changed_count, plan.unchanged_count), (1, 1, 3));
+        let changed = plan.episodes.iter().find(|planned| planned.episode.guid == "crab-1").unwrap();
+        let EpisodeChange::Changed(changes) = &changed.change else {
+            panic!("the title changed");
+        };
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].field, changes[0].old.as_str()), ("title", "The first crab"));
+        assert!(plan.report().contains(
+            "  ~ The very first crab (2024-09-02)\n      title: \"The first crab\" -> \"The very first crab\"\n"
+        ));
+
+        // Applying it leaves the untouched episodes exactly as they were.
+        let result = Mutation::apply_feed_import(&db, &plan, user.id, now).await.unwrap();
+        assert_eq!((result.created.len(), result.updated), (1, 1));
+        let episodes = Query::find_all_episodes(&db, Some(show_id)).await.unwrap();
+        assert_eq!(episodes.len(), 5);
+        for before in &imported {
+            let after = episodes.iter().find(|episode| episode.id == before.id).unwrap();
+            if before.guid.as_deref() == Some("crab-1") {
+                assert_eq!(after.title, "The very first crab");
+            } else {
+                assert_eq!(after, before);
+            }
+        }
+        let plan = plan_for(&db, &newer, Some(show_id)).await;
+        assert_eq!((plan.new_count, plan.changed_count, plan.unchanged_count), (0, 0, 5));
+    }
+}
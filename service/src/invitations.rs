@@ -0,0 +1,41 @@
+//! Who may get an account: everybody who logs in, or only people an admin invited.
+//!
+//! An invitation is mailed as a link carrying a token. Like one-time tokens, only the
+//! token's SHA-256 is stored, and an invitation can be accepted once before it expires.
+//! Invitations are also honoured when the invitee logs in through a provider that vouches
+//! for the invited address, without following the link.
+
+use chrono::Duration;
+use sea_orm::prelude::DateTimeUtc;
+
+/// How long an invitation stays usable after it was sent.
+pub const INVITATION_LIFETIME_DAYS: i64 = 7;
+
+/// When an invitation sent at `now` expires.
+pub fn invitation_expiry(now: DateTimeUtc) -> DateTimeUtc {
+    now + Duration::days(INVITATION_LIFETIME_DAYS)
+}
+
+/// Whether people without an account and without an invitation may create one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RegistrationPolicy {
+    /// Anybody can sign up or log in through a provider and gets an account.
+    #[default]
+    Open,
+    /// Only invited addresses get an account.
+    InviteOnly,
+}
+
+impl RegistrationPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(RegistrationPolicy::Open),
+            "invite_only" => Some(RegistrationPolicy::InviteOnly),
+            _ => None,
+        }
+    }
+
+    pub fn is_open(self) -> bool {
+        self == RegistrationPolicy::Open
+    }
+}
@@ -1,5 +1,20 @@
+pub mod accounts;
+pub mod api_tokens;
+pub mod backups;
+pub mod chapters;
+pub mod exports;
+pub mod feed;
+pub mod feed_import;
+pub mod invitations;
 mod mutation;
+pub mod permissions;
+pub mod publishing;
+pub mod seo;
+pub mod sessions;
+pub mod transcripts;
 mod query;
+#[cfg(test)]
+mod test_support;
 
 pub use mutation::*;
 pub use query::*;
@@ -1,32 +1,699 @@
-use entities::{episode, member, member::Entity as Member};
+use crate::accounts::{generate_one_time_token, name_from_email, normalize_email, TokenPurpose};
+use crate::api_tokens::{display_prefix, format_scopes, generate_token, hash_token, TokenScope};
+use crate::backups::{rebase_url, RestoredShow, ShowBackup};
+use crate::chapters::ImportedChapter;
+use crate::exports::ExportedEpisode;
+use crate::feed_import::{EpisodeChange, FeedImportPlan, FeedImportResult};
+use crate::invitations::{invitation_expiry, RegistrationPolicy};
+use crate::permissions::{DEFAULT_ROLE, DEFAULT_SHOW_ROLE, SUPER_ADMIN_ROLE};
+use crate::publishing::can_transition;
+use crate::sessions::idle_cutoff;
+use crate::transcripts::{Cue, TranscriptFormat};
+use crate::Query;
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{api_token, api_token::Entity as ApiToken};
+use entities::{chapter, chapter::Entity as Chapter};
+use entities::{episode, episode::Entity as Episode, member, member::Entity as Member};
+use entities::{invitation, invitation::Entity as Invitation};
+use entities::{one_time_token, one_time_token::Entity as OneTimeToken};
 use entities::{post, post::Entity as Post};
+use entities::{transcript, transcript::Entity as Transcript};
+use entities::{show, show::Entity as Show, show_user, show_user::Entity as ShowUser};
+use entities::{user, user::Entity as User, user_identity, user_identity::Entity as UserIdentity};
+use entities::{user_session, user_session::Entity as UserSession};
+use entities::{web_session, web_session::Entity as WebSession};
 
-use sea_orm::prelude::Uuid;
+use sea_orm::prelude::{DateTimeUtc, Uuid};
 use sea_orm::*;
 
 pub struct Mutation;
 
+/// A stored audio file together with its technical metadata.
+#[derive(Clone, Debug)]
+pub struct EpisodeMedia {
+    /// Where listeners download the file from.
+    pub url: String,
+    pub key: String,
+    pub size: i64,
+    pub mime_type: String,
+    pub duration_ms: i64,
+    pub bitrate: i32,
+    pub sample_rate: i32,
+    pub channels: i16,
+    pub codec: String,
+    pub artwork_url: Option<String>,
+    /// Chapters embedded in the file, as JSON.
+    pub embedded_chapters: Option<String>,
+}
+
+/// Who an OpenID Connect provider says is logging in.
+#[derive(Clone, Debug)]
+pub struct ExternalIdentity {
+    /// Name of the provider in the deployment's configuration.
+    pub provider: String,
+    /// The `sub` claim.
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// How logging in through a provider turned out.
+#[derive(Clone, Debug)]
+pub enum IdentitySignIn {
+    SignedIn(user::Model),
+    /// The identity comes without an email address, or with one the provider has not
+    /// verified.
+    NoUsableEmail,
+    /// Nobody has an account with the address or an invitation for it, and only invited
+    /// people may sign up.
+    NotInvited,
+}
+
+/// Where a session is used from.
+#[derive(Clone, Debug, Default)]
+pub struct SessionClient {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// The provider a session was started through, with the access token it handed out.
+#[derive(Clone, Debug)]
+pub struct ProviderGrant {
+    pub provider: String,
+    pub access_token: String,
+}
+
 impl Mutation {
     pub async fn create_episode(
-        db: &DatabaseConnection,
+        db: &DbConn,
+        form_data: episode::Model,
+    ) -> Result<episode::Model, DbErr> {
+        episode::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            title: Set(form_data.title.to_owned()),
+            summary: Set(form_data.summary.to_owned()),
+            tags: Set(form_data.tags.to_owned()),
+            url: Set(form_data.url.to_owned()),
+            user_id: Set(form_data.user_id),
+            media_key: Set(form_data.media_key.to_owned()),
+            media_size: Set(form_data.media_size),
+            media_type: Set(form_data.media_type.to_owned()),
+            show_id: Set(form_data.show_id),
+            status: Set(EpisodeStatus::Draft),
+            published_at: Set(None),
+            scheduled_for: Set(None),
+            duration_ms: Set(form_data.duration_ms),
+            bitrate: Set(form_data.bitrate),
+            sample_rate: Set(form_data.sample_rate),
+            channels: Set(form_data.channels),
+            codec: Set(form_data.codec.to_owned()),
+            artwork_url: Set(form_data.artwork_url.to_owned()),
+            embedded_chapters: Set(form_data.embedded_chapters.to_owned()),
+            guid: Set(None),
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn update_episode_by_id(
+        db: &DbConn,
+        id: Uuid,
         form_data: episode::Model,
-    ) -> Result<episode::ActiveModel, DbErr> {
-        form_data.into_active_model().save(db).await
+    ) -> Result<episode::Model, DbErr> {
+        let episode: episode::ActiveModel = Episode::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find episode.".to_owned()))
+            .map(Into::into)?;
+
+        episode::ActiveModel {
+            id: episode.id,
+            title: Set(form_data.title.to_owned()),
+            summary: Set(form_data.summary.to_owned()),
+            tags: Set(form_data.tags.to_owned()),
+            url: Set(form_data.url.to_owned()),
+            user_id: episode.user_id,
+            media_key: episode.media_key,
+            media_size: episode.media_size,
+            media_type: episode.media_type,
+            show_id: Set(form_data.show_id),
+            status: episode.status,
+            published_at: episode.published_at,
+            scheduled_for: episode.scheduled_for,
+            duration_ms: episode.duration_ms,
+            bitrate: episode.bitrate,
+            sample_rate: episode.sample_rate,
+            channels: episode.channels,
+            codec: episode.codec,
+            artwork_url: episode.artwork_url,
+            embedded_chapters: episode.embedded_chapters,
+            guid: episode.guid,
+        }
+        .update(db)
+        .await
     }
+
+    /// Points an episode at a freshly stored audio file and records what was learned about it.
+    pub async fn attach_episode_media(
+        db: &DbConn,
+        id: Uuid,
+        media: EpisodeMedia,
+    ) -> Result<episode::Model, DbErr> {
+        let mut episode: episode::ActiveModel = Episode::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find episode.".to_owned()))
+            .map(Into::into)?;
+
+        episode.url = Set(Some(media.url));
+        episode.media_key = Set(Some(media.key));
+        episode.media_size = Set(Some(media.size));
+        episode.media_type = Set(Some(media.mime_type));
+        episode.duration_ms = Set(Some(media.duration_ms));
+        episode.bitrate = Set(Some(media.bitrate));
+        episode.sample_rate = Set(Some(media.sample_rate));
+        episode.channels = Set(Some(media.channels));
+        episode.codec = Set(Some(media.codec));
+        episode.artwork_url = Set(media.artwork_url);
+        episode.embedded_chapters = Set(media.embedded_chapters);
+        episode.update(db).await
+    }
+
+    /// Moves an episode through the publishing workflow. `scheduled_for` is required, and
+    /// must lie in the future, when scheduling.
+    pub async fn change_episode_status(
+        db: &DbConn,
+        id: Uuid,
+        status: EpisodeStatus,
+        scheduled_for: Option<DateTimeUtc>,
+        now: DateTimeUtc,
+    ) -> Result<episode::Model, DbErr> {
+        let current = Episode::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find episode.".to_owned()))?;
+
+        if !can_transition(current.status, status) {
+            return Err(DbErr::Custom(format!(
+                "Cannot move episode from {:?} to {:?}.",
+                current.status, status
+            )));
+        }
+
+        let mut episode: episode::ActiveModel = current.into();
+        episode.status = Set(status);
+        match status {
+            EpisodeStatus::Scheduled => {
+                let scheduled_for = scheduled_for
+                    .filter(|scheduled_for| *scheduled_for > now)
+                    .ok_or(DbErr::Custom("Scheduling needs a time in the future.".to_owned()))?;
+                episode.scheduled_for = Set(Some(scheduled_for));
+            }
+            EpisodeStatus::Published => {
+                episode.published_at = Set(Some(now));
+                episode.scheduled_for = Set(None);
+            }
+            EpisodeStatus::Draft => {
+                episode.scheduled_for = Set(None);
+            }
+            EpisodeStatus::Archived => {}
+        }
+        episode.update(db).await
+    }
+
+    /// Publishes every scheduled episode that is due. Returns how many were published.
+    pub async fn publish_due_episodes(db: &DbConn, now: DateTimeUtc) -> Result<u64, DbErr> {
+        let due = Episode::find()
+            .filter(episode::Column::Status.eq(EpisodeStatus::Scheduled))
+            .filter(episode::Column::ScheduledFor.lte(now))
+            .all(db)
+            .await?;
+
+        let count = due.len() as u64;
+        for episode in due {
+            let published_at = episode.scheduled_for.unwrap_or(now);
+            let mut episode: episode::ActiveModel = episode.into();
+            episode.status = Set(EpisodeStatus::Published);
+            episode.published_at = Set(Some(published_at));
+            episode.scheduled_for = Set(None);
+            episode.update(db).await?;
+        }
+        Ok(count)
+    }
+
+    pub async fn delete_episode(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        let episode: episode::ActiveModel = Episode::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find episode.".to_owned()))
+            .map(Into::into)?;
+
+        episode.delete(db).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn delete_all_episodes(db: &DbConn) -> Result<DeleteResult, DbErr> {
+        Episode::delete_many().exec(db).await
+    }
+
+    pub async fn create_chapter(
+        db: &DbConn,
+        episode_id: Uuid,
+        form_data: chapter::Model,
+    ) -> Result<chapter::Model, DbErr> {
+        chapter::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            episode_id: Set(episode_id),
+            start_ms: Set(form_data.start_ms),
+            title: Set(form_data.title.to_owned()),
+            url: Set(form_data.url.to_owned()),
+            image_url: Set(form_data.image_url.to_owned()),
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn update_chapter_by_id(
+        db: &DbConn,
+        id: Uuid,
+        form_data: chapter::Model,
+    ) -> Result<chapter::Model, DbErr> {
+        let chapter: chapter::ActiveModel = Chapter::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find chapter.".to_owned()))
+            .map(Into::into)?;
+
+        chapter::ActiveModel {
+            id: chapter.id,
+            episode_id: chapter.episode_id,
+            start_ms: Set(form_data.start_ms),
+            title: Set(form_data.title.to_owned()),
+            url: Set(form_data.url.to_owned()),
+            image_url: Set(form_data.image_url.to_owned()),
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn delete_chapter(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        Chapter::delete_by_id(id).exec(db).await
+    }
+
+    /// Replaces all chapters of an episode, e.g. with the ones embedded in its audio file.
+    pub async fn replace_chapters(
+        db: &DbConn,
+        episode_id: Uuid,
+        chapters: &[ImportedChapter],
+    ) -> Result<u64, DbErr> {
+        let txn = db.begin().await?;
+        Chapter::delete_many()
+            .filter(chapter::Column::EpisodeId.eq(episode_id))
+            .exec(&txn)
+            .await?;
+        for imported in chapters {
+            chapter::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                episode_id: Set(episode_id),
+                start_ms: Set(imported.start_ms),
+                title: Set(imported.title.to_owned()),
+                url: Set(imported.url.to_owned()),
+                image_url: Set(imported.image_url.to_owned()),
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(chapters.len() as u64)
+    }
+
+    /// Writes an import planned with `FeedImportPlan::new`: creates the show unless the
+    /// plan names one, adds the new episodes as `user_id`'s and updates the changed ones.
+    /// Items dated in the future are scheduled, undated ones become drafts.
+    pub async fn apply_feed_import(
+        db: &DbConn,
+        plan: &FeedImportPlan,
+        user_id: Uuid,
+        now: DateTimeUtc,
+    ) -> Result<FeedImportResult, DbErr> {
+        let txn = db.begin().await?;
+        let show_id = match plan.show_id {
+            Some(show_id) => show_id,
+            None => {
+                let show = plan.show.to_model();
+                show::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    title: Set(show.title),
+                    description: Set(show.description),
+                    artwork_url: Set(show.artwork_url),
+                    language: Set(show.language),
+                    category: Set(show.category),
+                    author: Set(show.author),
+                    owner_name: Set(show.owner_name),
+                    owner_email: Set(show.owner_email),
+                    explicit: Set(show.explicit),
+                }
+                .insert(&txn)
+                .await?
+                .id
+            }
+        };
+
+        let mut created = Vec::new();
+        let mut updated = 0;
+        for planned in &plan.episodes {
+            let imported = &planned.episode;
+            let enclosure = imported.enclosure.as_ref();
+            match (&planned.change, planned.existing_id) {
+                (EpisodeChange::New, _) => {
+                    let (status, published_at, scheduled_for) = match imported.published_at {
+                        Some(date) if date > now => (EpisodeStatus::Scheduled, None, Some(date)),
+                        Some(date) => (EpisodeStatus::Published, Some(date), None),
+                        None => (EpisodeStatus::Draft, None, None),
+                    };
+                    let url = enclosure.map(|enclosure| enclosure.url.clone());
+                    let episode = episode::ActiveModel {
+                        id: Set(Uuid::new_v4()),
+                        title: Set(imported.title.clone()),
+                        summary: Set(imported.summary.clone()),
+                        tags: Set(imported.tags.clone()),
+                        url: Set(url.clone()),
+                        user_id: Set(user_id),
+                        media_key: Set(None),
+                        media_size: Set(enclosure.and_then(|enclosure| enclosure.length)),
+                        media_type: Set(enclosure.and_then(|enclosure| enclosure.mime_type.clone())),
+                        show_id: Set(Some(show_id)),
+                        status: Set(status),
+                        published_at: Set(published_at),
+                        scheduled_for: Set(scheduled_for),
+                        duration_ms: Set(imported.duration_ms),
+                        bitrate: Set(None),
+                        sample_rate: Set(None),
+                        channels: Set(None),
+                        codec: Set(None),
+                        artwork_url: Set(imported.artwork_url.clone()),
+                        embedded_chapters: Set(None),
+                        guid: Set(Some(imported.guid.clone())),
+                    }
+                    .insert(&txn)
+                    .await?;
+                    created.push((episode.id, url));
+                }
+                (EpisodeChange::Changed(_), Some(id)) => {
+                    let stored = Episode::find_by_id(id)
+                        .one(&txn)
+                        .await?
+                        .ok_or(DbErr::Custom("Cannot find episode.".to_owned()))?;
+                    let media_stored = stored.media_key.is_some();
+                    let published = stored.status == EpisodeStatus::Published;
+                    let mut episode: episode::ActiveModel = stored.into();
+                    episode.title = Set(imported.title.clone());
+                    episode.summary = Set(imported.summary.clone());
+                    episode.tags = Set(imported.tags.clone());
+                    if published && imported.published_at.is_some() {
+                        episode.published_at = Set(imported.published_at);
+                    }
+                    if !media_stored {
+                        episode.url = Set(enclosure.map(|enclosure| enclosure.url.clone()));
+                        episode.media_size = Set(enclosure.and_then(|enclosure| enclosure.length));
+                        episode.media_type = Set(enclosure.and_then(|enclosure| enclosure.mime_type.clone()));
+                    }
+                    episode.update(&txn).await?;
+                    updated += 1;
+                }
+                _ => {}
+            }
+        }
+        txn.commit().await?;
+
+        Ok(FeedImportResult { show_id, created, updated })
+    }
+
+    /// Creates an episode of `user_id` in `show_id` from an export, keeping its status and
+    /// dates, with its chapters. Returns `None` when the show already has an episode with the
+    /// same GUID, so importing an export twice adds nothing.
+    pub async fn import_episode(
+        db: &DbConn,
+        exported: &ExportedEpisode,
+        user_id: Uuid,
+        show_id: Uuid,
+    ) -> Result<Option<episode::Model>, DbErr> {
+        let txn = db.begin().await?;
+        if let Some(guid) = &exported.guid {
+            let known = Episode::find()
+                .filter(episode::Column::ShowId.eq(show_id))
+                .filter(episode::Column::Guid.eq(guid))
+                .one(&txn)
+                .await?;
+            if known.is_some() {
+                return Ok(None);
+            }
+        }
+        let episode = episode::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            title: Set(exported.title.to_owned()),
+            summary: Set(exported.summary.to_owned()),
+            tags: Set(exported.tags.to_owned()),
+            url: Set(exported.url.to_owned()),
+            user_id: Set(user_id),
+            media_key: Set(None),
+            media_size: Set(exported.media_size),
+            media_type: Set(exported.media_type.to_owned()),
+            show_id: Set(Some(show_id)),
+            status: Set(exported.status),
+            published_at: Set(exported.published_at),
+            scheduled_for: Set(exported.scheduled_for),
+            duration_ms: Set(exported.duration_ms),
+            bitrate: Set(exported.bitrate),
+            sample_rate: Set(exported.sample_rate),
+            channels: Set(exported.channels),
+            codec: Set(exported.codec.to_owned()),
+            artwork_url: Set(exported.artwork_url.to_owned()),
+            embedded_chapters: Set(None),
+            guid: Set(exported.guid.to_owned()),
+        }
+        .insert(&txn)
+        .await?;
+        for imported in &exported.chapters {
+            chapter::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                episode_id: Set(episode.id),
+                start_ms: Set(imported.start_ms),
+                title: Set(imported.title.to_owned()),
+                url: Set(imported.url.to_owned()),
+                image_url: Set(imported.image_url.to_owned()),
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(Some(episode))
+    }
+
+    /// Recreates a backed up show, keeping its id and those of its episodes. Accounts are
+    /// matched by address and created with the default role when missing, as an archive
+    /// must not hand out site-wide roles; show roles this deployment does not know fall back
+    /// to the default. Media URLs are moved from `backup.media_url` to `media_url`; the
+    /// files themselves are restored separately, before this is called.
+    pub async fn restore_show(
+        db: &DbConn,
+        backup: &ShowBackup,
+        media_url: &str,
+        now: DateTimeUtc,
+    ) -> Result<RestoredShow, DbErr> {
+        if Show::find_by_id(backup.show.id).one(db).await?.is_some() {
+            return Err(DbErr::Custom(format!("Show {} already exists.", backup.show.id)));
+        }
+        let roles = Query::find_roles(db).await?;
+        let rebase = |url: &Option<String>| {
+            url.as_deref()
+                .map(|url| rebase_url(url, &backup.media_url, media_url))
+        };
+
+        let txn = db.begin().await?;
+        let show = &backup.show;
+        show::ActiveModel {
+            id: Set(show.id),
+            title: Set(show.title.to_owned()),
+            description: Set(show.description.to_owned()),
+            artwork_url: Set(show.artwork_url.to_owned()),
+            language: Set(show.language.to_owned()),
+            category: Set(show.category.to_owned()),
+            author: Set(show.author.to_owned()),
+            owner_name: Set(show.owner_name.to_owned()),
+            owner_email: Set(show.owner_email.to_owned()),
+            explicit: Set(show.explicit),
+        }
+        .insert(&txn)
+        .await?;
+
+        let mut user_ids = Vec::with_capacity(backup.users.len());
+        let mut created_users = Vec::new();
+        for backed_up in &backup.users {
+            let existing = User::find()
+                .filter(user::Column::Email.eq(&backed_up.email))
+                .one(&txn)
+                .await?;
+            let user = match existing {
+                Some(user) => user,
+                None => {
+                    created_users.push(backed_up.email.clone());
+                    user::ActiveModel {
+                        id: Set(Uuid::new_v4()),
+                        email: Set(backed_up.email.to_owned()),
+                        name: Set(backed_up.name.to_owned()),
+                        role: Set(DEFAULT_ROLE.to_owned()),
+                        password_hash: Set(None),
+                        email_verified_at: Set(Some(now)),
+                        deactivated_at: Set(None),
+                    }
+                    .insert(&txn)
+                    .await?
+                }
+            };
+            user_ids.push((backed_up.email.as_str(), user.id));
+        }
+        let user_id = |email: &str| {
+            user_ids
+                .iter()
+                .find(|(known, _)| *known == email)
+                .map(|(_, id)| *id)
+                .ok_or(DbErr::Custom(format!("The backup has no account {email}.")))
+        };
+
+        for member in &backup.members {
+            let role = if roles.contains(&member.role) { &member.role } else { DEFAULT_SHOW_ROLE };
+            show_user::ActiveModel {
+                show_id: Set(show.id),
+                user_id: Set(user_id(&member.email)?),
+                role: Set(role.to_owned()),
+            }
+            .insert(&txn)
+            .await?;
+        }
+
+        for backed_up in &backup.episodes {
+            let exported = &backed_up.episode;
+            episode::ActiveModel {
+                id: Set(backed_up.id),
+                title: Set(exported.title.to_owned()),
+                summary: Set(exported.summary.to_owned()),
+                tags: Set(exported.tags.to_owned()),
+                url: Set(rebase(&exported.url)),
+                user_id: Set(user_id(&backed_up.owner)?),
+                media_key: Set(backed_up.media_key.to_owned()),
+                media_size: Set(exported.media_size),
+                media_type: Set(exported.media_type.to_owned()),
+                show_id: Set(Some(show.id)),
+                status: Set(exported.status),
+                published_at: Set(exported.published_at),
+                scheduled_for: Set(exported.scheduled_for),
+                duration_ms: Set(exported.duration_ms),
+                bitrate: Set(exported.bitrate),
+                sample_rate: Set(exported.sample_rate),
+                channels: Set(exported.channels),
+                codec: Set(exported.codec.to_owned()),
+                artwork_url: Set(rebase(&exported.artwork_url)),
+                embedded_chapters: Set(backed_up
+                    .embedded_chapters
+                    .as_deref()
+                    .map(|chapters| chapters.replace(&backup.media_url, media_url))),
+                guid: Set(exported.guid.to_owned()),
+            }
+            .insert(&txn)
+            .await?;
+            for imported in &exported.chapters {
+                chapter::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    episode_id: Set(backed_up.id),
+                    start_ms: Set(imported.start_ms),
+                    title: Set(imported.title.to_owned()),
+                    url: Set(imported.url.to_owned()),
+                    image_url: Set(rebase(&imported.image_url)),
+                }
+                .insert(&txn)
+                .await?;
+            }
+            for backed_up_transcript in &backed_up.transcripts {
+                let cues = serde_json::to_string(&backed_up_transcript.cues)
+                    .map_err(|err| DbErr::Custom(err.to_string()))?;
+                transcript::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    episode_id: Set(backed_up.id),
+                    language: Set(backed_up_transcript.language.to_owned()),
+                    source_format: Set(backed_up_transcript.source_format.to_owned()),
+                    cues: Set(cues),
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+        txn.commit().await?;
+
+        Ok(RestoredShow {
+            show_id: show.id,
+            episodes: backup.episodes.len(),
+            created_users,
+        })
+    }
+
+    /// Stores parsed cues as the episode's transcript in `language`, replacing any
+    /// earlier upload in the same language.
+    pub async fn save_transcript(
+        db: &DbConn,
+        episode_id: Uuid,
+        language: &str,
+        source_format: TranscriptFormat,
+        cues: &[Cue],
+    ) -> Result<transcript::Model, DbErr> {
+        let cues = serde_json::to_string(cues).map_err(|err| DbErr::Custom(err.to_string()))?;
+        let existing = Transcript::find()
+            .filter(transcript::Column::EpisodeId.eq(episode_id))
+            .filter(transcript::Column::Language.eq(language))
+            .one(db)
+            .await?;
+
+        match existing {
+            Some(existing) => {
+                let mut transcript: transcript::ActiveModel = existing.into();
+                transcript.source_format = Set(source_format.extension().to_owned());
+                transcript.cues = Set(cues);
+                transcript.update(db).await
+            }
+            None => {
+                transcript::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    episode_id: Set(episode_id),
+                    language: Set(language.to_owned()),
+                    source_format: Set(source_format.extension().to_owned()),
+                    cues: Set(cues),
+                }
+                .insert(db)
+                .await
+            }
+        }
+    }
+
+    pub async fn delete_transcript(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        Transcript::delete_by_id(id).exec(db).await
+    }
+
     pub async fn create_member(
         db: &DbConn,
         form_data: member::Model,
     ) -> Result<member::ActiveModel, DbErr> {
         member::ActiveModel {
+            id: Set(Uuid::new_v4()),
             first_name: Set(form_data.first_name.to_owned()),
             last_name: Set(form_data.last_name.to_owned()),
             email: Set(form_data.email.to_owned()),
             mobile_phone: Set(form_data.mobile_phone.to_owned()),
             birth_date: Set(form_data.birth_date.to_owned()),
-            ..Default::default()
         }
-        .save(db)
+        .insert(db)
         .await
+        .map(Into::into)
     }
 
     pub async fn update_member_by_id(
@@ -72,12 +739,13 @@ impl Mutation {
         form_data: post::Model,
     ) -> Result<post::ActiveModel, DbErr> {
         post::ActiveModel {
+            id: Set(Uuid::new_v4()),
             title: Set(form_data.title.to_owned()),
             text: Set(form_data.text.to_owned()),
-            ..Default::default()
         }
-        .save(db)
+        .insert(db)
         .await
+        .map(Into::into)
     }
 
     pub async fn update_post_by_id(
@@ -114,4 +782,890 @@ impl Mutation {
     pub async fn delete_all_posts(db: &DbConn) -> Result<DeleteResult, DbErr> {
         Post::delete_many().exec(db).await
     }
+
+    pub async fn create_show(
+        db: &DbConn,
+        form_data: show::Model,
+    ) -> Result<show::ActiveModel, DbErr> {
+        show::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            title: Set(form_data.title.to_owned()),
+            description: Set(form_data.description.to_owned()),
+            artwork_url: Set(form_data.artwork_url.to_owned()),
+            language: Set(form_data.language.to_owned()),
+            category: Set(form_data.category.to_owned()),
+            author: Set(form_data.author.to_owned()),
+            owner_name: Set(form_data.owner_name.to_owned()),
+            owner_email: Set(form_data.owner_email.to_owned()),
+            explicit: Set(form_data.explicit),
+        }
+        .insert(db)
+        .await
+        .map(Into::into)
+    }
+
+    pub async fn update_show_by_id(
+        db: &DbConn,
+        id: Uuid,
+        form_data: show::Model,
+    ) -> Result<show::Model, DbErr> {
+        let show: show::ActiveModel = Show::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find show.".to_owned()))
+            .map(Into::into)?;
+
+        show::ActiveModel {
+            id: show.id,
+            title: Set(form_data.title.to_owned()),
+            description: Set(form_data.description.to_owned()),
+            artwork_url: Set(form_data.artwork_url.to_owned()),
+            language: Set(form_data.language.to_owned()),
+            category: Set(form_data.category.to_owned()),
+            author: Set(form_data.author.to_owned()),
+            owner_name: Set(form_data.owner_name.to_owned()),
+            owner_email: Set(form_data.owner_email.to_owned()),
+            explicit: Set(form_data.explicit),
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn delete_show(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        let show: show::ActiveModel = Show::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find show.".to_owned()))
+            .map(Into::into)?;
+
+        show.delete(db).await
+    }
+
+    /// Lets a user manage a show. Granting twice is harmless.
+    /// Grants `user_id` access to the show with `role`, replacing an earlier grant.
+    pub async fn grant_show_access(db: &DbConn, show_id: Uuid, user_id: Uuid, role: &str) -> Result<(), DbErr> {
+        let grant = show_user::ActiveModel {
+            show_id: Set(show_id),
+            user_id: Set(user_id),
+            role: Set(role.to_owned()),
+        };
+        ShowUser::insert(grant)
+            .on_conflict(
+                sea_query::OnConflict::columns([show_user::Column::ShowId, show_user::Column::UserId])
+                    .update_column(show_user::Column::Role)
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn revoke_show_access(
+        db: &DbConn,
+        show_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<DeleteResult, DbErr> {
+        ShowUser::delete_by_id((show_id, user_id)).exec(db).await
+    }
+
+    /// Mints a token for `user_id`. Returns the stored token and its secret, which is
+    /// not kept anywhere.
+    pub async fn create_api_token(
+        db: &DbConn,
+        user_id: Uuid,
+        name: &str,
+        scopes: &[TokenScope],
+        expires_at: Option<DateTimeUtc>,
+        now: DateTimeUtc,
+    ) -> Result<(api_token::Model, String), DbErr> {
+        let secret = generate_token();
+        let token = api_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            name: Set(name.to_owned()),
+            token_hash: Set(hash_token(&secret)),
+            prefix: Set(display_prefix(&secret)),
+            scopes: Set(format_scopes(scopes)),
+            expires_at: Set(expires_at),
+            last_used_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(db)
+        .await?;
+        Ok((token, secret))
+    }
+
+    pub async fn touch_api_token(db: &DbConn, id: Uuid, now: DateTimeUtc) -> Result<(), DbErr> {
+        ApiToken::update_many()
+            .col_expr(api_token::Column::LastUsedAt, sea_query::Expr::value(now))
+            .filter(api_token::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+    }
+
+    /// Deletes one of the user's tokens. Tokens of other users are left alone.
+    pub async fn revoke_api_token(
+        db: &DbConn,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<DeleteResult, DbErr> {
+        ApiToken::delete_many()
+            .filter(api_token::Column::Id.eq(id))
+            .filter(api_token::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+    }
+
+    pub async fn create_user(db: &DbConn, email: &str, name: &str) -> Result<user::Model, DbErr> {
+        user::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            email: Set(email.to_owned()),
+            name: Set(name.to_owned()),
+            role: Set(DEFAULT_ROLE.to_owned()),
+            password_hash: Set(None),
+            email_verified_at: Set(None),
+            deactivated_at: Set(None),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Creates a user with a verified address and `role`, e.g. from the command line.
+    /// Without a password they log in through a provider or with a magic link.
+    pub async fn create_account(
+        db: &DbConn,
+        email: &str,
+        name: &str,
+        role: &str,
+        password_hash: Option<String>,
+        now: DateTimeUtc,
+    ) -> Result<user::Model, DbErr> {
+        user::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            email: Set(email.to_owned()),
+            name: Set(name.to_owned()),
+            role: Set(role.to_owned()),
+            password_hash: Set(password_hash),
+            email_verified_at: Set(Some(now)),
+            deactivated_at: Set(None),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Creates a user who logs in with a password. The address stays unverified until the
+    /// link sent to it is followed.
+    pub async fn register_local_user(
+        db: &DbConn,
+        email: &str,
+        name: &str,
+        password_hash: String,
+    ) -> Result<user::Model, DbErr> {
+        user::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            email: Set(email.to_owned()),
+            name: Set(name.to_owned()),
+            role: Set(DEFAULT_ROLE.to_owned()),
+            password_hash: Set(Some(password_hash)),
+            email_verified_at: Set(None),
+            deactivated_at: Set(None),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Marks the user's address as verified, keeping their password.
+    pub async fn verify_email(
+        db: &DbConn,
+        user: user::Model,
+        now: DateTimeUtc,
+    ) -> Result<user::Model, DbErr> {
+        if user.email_verified_at.is_some() {
+            return Ok(user);
+        }
+        let mut user: user::ActiveModel = user.into();
+        user.email_verified_at = Set(Some(now));
+        user.update(db).await
+    }
+
+    /// Marks the user's address as verified after its owner proved they can read it some
+    /// other way than through the verification link, e.g. with a magic link.
+    ///
+    /// A password, provider identities and sessions from before the address was verified
+    /// are dropped: anybody could have registered with somebody else's address, and must
+    /// not keep access to the account once its rightful owner shows up.
+    pub async fn claim_email(
+        db: &DbConn,
+        user: user::Model,
+        now: DateTimeUtc,
+    ) -> Result<user::Model, DbErr> {
+        if user.email_verified_at.is_some() {
+            return Ok(user);
+        }
+        UserIdentity::delete_many()
+            .filter(user_identity::Column::UserId.eq(user.id))
+            .exec(db)
+            .await?;
+        Self::end_user_sessions_of_user(db, user.id, None).await?;
+        let mut user: user::ActiveModel = user.into();
+        user.email_verified_at = Set(Some(now));
+        user.password_hash = Set(None);
+        user.update(db).await
+    }
+
+    /// Replaces the user's password. Only call this after the user proved they own the
+    /// address, so it counts as verified too.
+    pub async fn set_password(
+        db: &DbConn,
+        user: user::Model,
+        password_hash: String,
+        now: DateTimeUtc,
+    ) -> Result<user::Model, DbErr> {
+        let verified_at = user.email_verified_at.unwrap_or(now);
+        let mut user: user::ActiveModel = user.into();
+        user.password_hash = Set(Some(password_hash));
+        user.email_verified_at = Set(Some(verified_at));
+        user.update(db).await
+    }
+
+    /// Creates a one-time token for `purpose` and returns it. The token itself is not
+    /// stored, so this is the only chance to mail it.
+    pub async fn create_one_time_token(
+        db: &DbConn,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+        now: DateTimeUtc,
+    ) -> Result<String, DbErr> {
+        let token = generate_one_time_token();
+        one_time_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            purpose: Set(purpose.as_str().to_owned()),
+            token_hash: Set(hash_token(&token)),
+            expires_at: Set(now + purpose.lifetime()),
+            used_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(db)
+        .await?;
+        Ok(token)
+    }
+
+    /// Uses up a one-time token and returns its user. Returns `None` for unknown, expired
+    /// and already used tokens, and for tokens meant for something else.
+    ///
+    /// The other unused tokens of the same purpose are used up as well, so only the newest
+    /// email someone acts on counts.
+    pub async fn consume_one_time_token(
+        db: &DbConn,
+        token: &str,
+        purpose: TokenPurpose,
+        now: DateTimeUtc,
+    ) -> Result<Option<user::Model>, DbErr> {
+        let found = OneTimeToken::find()
+            .filter(one_time_token::Column::TokenHash.eq(hash_token(token)))
+            .filter(one_time_token::Column::Purpose.eq(purpose.as_str()))
+            .filter(one_time_token::Column::UsedAt.is_null())
+            .filter(one_time_token::Column::ExpiresAt.gt(now))
+            .find_also_related(User)
+            .one(db)
+            .await?;
+        let Some((token, Some(user))) = found else {
+            return Ok(None);
+        };
+
+        // Guards against the same link being followed twice at once.
+        let used = OneTimeToken::update_many()
+            .col_expr(one_time_token::Column::UsedAt, sea_query::Expr::value(now))
+            .filter(one_time_token::Column::Id.eq(token.id))
+            .filter(one_time_token::Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+        if used.rows_affected != 1 {
+            return Ok(None);
+        }
+
+        OneTimeToken::update_many()
+            .col_expr(one_time_token::Column::UsedAt, sea_query::Expr::value(now))
+            .filter(one_time_token::Column::UserId.eq(user.id))
+            .filter(one_time_token::Column::Purpose.eq(purpose.as_str()))
+            .filter(one_time_token::Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(Some(user))
+    }
+
+    pub async fn link_identity(
+        db: &DbConn,
+        user_id: Uuid,
+        identity: &ExternalIdentity,
+        now: DateTimeUtc,
+    ) -> Result<user_identity::Model, DbErr> {
+        user_identity::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            provider: Set(identity.provider.to_owned()),
+            subject: Set(identity.subject.to_owned()),
+            email: Set(identity.email.to_owned()),
+            created_at: Set(now),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Finds the user behind `identity`, linking the identity on first use.
+    ///
+    /// A new identity needs an address the provider vouches for. It is linked to the user
+    /// with that address, or else a new user is created: from a pending invitation for the
+    /// address if there is one, and only then unless `policy` is open.
+    pub async fn sign_in_with_identity(
+        db: &DbConn,
+        identity: &ExternalIdentity,
+        policy: RegistrationPolicy,
+        now: DateTimeUtc,
+    ) -> Result<IdentitySignIn, DbErr> {
+        let linked = UserIdentity::find()
+            .filter(user_identity::Column::Provider.eq(&identity.provider))
+            .filter(user_identity::Column::Subject.eq(&identity.subject))
+            .find_also_related(User)
+            .one(db)
+            .await?;
+        if let Some((_, Some(user))) = linked {
+            return Ok(IdentitySignIn::SignedIn(user));
+        }
+
+        // Providers hand out addresses as typed at sign up, e.g. `Jane@Example.com`.
+        let email = identity.email.as_deref().map(normalize_email);
+        // An unverified address could be anybody's: an account made from it would be the
+        // real owner's later.
+        let email = email.as_deref().filter(|email| !email.is_empty() && identity.email_verified);
+        let Some(email) = email else {
+            return Ok(IdentitySignIn::NoUsableEmail);
+        };
+        let existing = User::find()
+            .filter(user::Column::Email.eq(email))
+            .one(db)
+            .await?;
+        let invitation = match &existing {
+            None => Query::find_pending_invitation_by_email(db, email, now).await?,
+            Some(_) => None,
+        };
+        let name = identity.name.as_deref().unwrap_or_else(|| name_from_email(email));
+        let user = match (existing, invitation) {
+            (Some(user), _) => user,
+            (None, Some(invitation)) => match Self::accept_invitation(db, invitation, name, None, now).await? {
+                Some(user) => user,
+                None => return Ok(IdentitySignIn::NotInvited),
+            },
+            (None, None) if policy.is_open() => Self::create_user(db, email, name).await?,
+            (None, None) => return Ok(IdentitySignIn::NotInvited),
+        };
+        let user = Self::claim_email(db, user, now).await?;
+        Self::link_identity(db, user.id, identity, now).await?;
+        Ok(IdentitySignIn::SignedIn(user))
+    }
+
+    /// Invites `email` to sign up with `role`. Earlier pending invitations for the address
+    /// are dropped, so only the newest link works. Returns the invitation and its token,
+    /// which is not stored, so this is the only chance to mail it.
+    pub async fn create_invitation(
+        db: &DbConn,
+        email: &str,
+        role: &str,
+        invited_by: Uuid,
+        now: DateTimeUtc,
+    ) -> Result<(invitation::Model, String), DbErr> {
+        Invitation::delete_many()
+            .filter(invitation::Column::Email.eq(email))
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .exec(db)
+            .await?;
+
+        let token = generate_one_time_token();
+        let invitation = invitation::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            email: Set(email.to_owned()),
+            role: Set(role.to_owned()),
+            token_hash: Set(hash_token(&token)),
+            invited_by: Set(Some(invited_by)),
+            expires_at: Set(invitation_expiry(now)),
+            accepted_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(db)
+        .await?;
+        Ok((invitation, token))
+    }
+
+    pub async fn revoke_invitation(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        Invitation::delete_many()
+            .filter(invitation::Column::Id.eq(id))
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .exec(db)
+            .await
+    }
+
+    /// Uses up `invitation` and returns the invitee's account, with the invited role and a
+    /// verified address: the invitee got the invitation by mail. Returns `None` when the
+    /// invitation was accepted in the meantime.
+    ///
+    /// Somebody may have signed up with the address since it was invited. Their account is
+    /// taken over like with [`Mutation::claim_email`] and gets the invited role.
+    pub async fn accept_invitation(
+        db: &DbConn,
+        invitation: invitation::Model,
+        name: &str,
+        password_hash: Option<String>,
+        now: DateTimeUtc,
+    ) -> Result<Option<user::Model>, DbErr> {
+        // Guards against the same link being followed twice at once.
+        let accepted = Invitation::update_many()
+            .col_expr(invitation::Column::AcceptedAt, sea_query::Expr::value(now))
+            .filter(invitation::Column::Id.eq(invitation.id))
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .exec(db)
+            .await?;
+        if accepted.rows_affected != 1 {
+            return Ok(None);
+        }
+
+        let existing = User::find()
+            .filter(user::Column::Email.eq(&invitation.email))
+            .one(db)
+            .await?;
+        let user = match existing {
+            Some(user) => {
+                let user = Self::claim_email(db, user, now).await?;
+                let mut user: user::ActiveModel = user.into();
+                user.role = Set(invitation.role);
+                if let Some(password_hash) = password_hash {
+                    user.password_hash = Set(Some(password_hash));
+                }
+                user.update(db).await?
+            }
+            None => {
+                user::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    email: Set(invitation.email),
+                    name: Set(name.to_owned()),
+                    role: Set(invitation.role),
+                    password_hash: Set(password_hash),
+                    email_verified_at: Set(Some(now)),
+                    deactivated_at: Set(None),
+                }
+                .insert(db)
+                .await?
+            }
+        };
+        Ok(Some(user))
+    }
+
+    pub async fn change_user_role(
+        db: &DbConn,
+        user: user::Model,
+        role: &str,
+    ) -> Result<user::Model, DbErr> {
+        let mut user: user::ActiveModel = user.into();
+        user.role = Set(role.to_owned());
+        user.update(db).await
+    }
+
+    /// Makes the owner of `email` a super admin, creating a verified account for them if
+    /// there is none. A deactivated account is let back in. Returns the user and whether
+    /// they are new.
+    pub async fn create_super_admin(
+        db: &DbConn,
+        email: &str,
+        name: Option<&str>,
+        now: DateTimeUtc,
+    ) -> Result<(user::Model, bool), DbErr> {
+        let existing = User::find()
+            .filter(user::Column::Email.eq(email))
+            .one(db)
+            .await?;
+        if let Some(user) = existing {
+            let user = Self::claim_email(db, user, now).await?;
+            let mut user: user::ActiveModel = user.into();
+            user.role = Set(SUPER_ADMIN_ROLE.to_owned());
+            user.deactivated_at = Set(None);
+            if let Some(name) = name {
+                user.name = Set(name.to_owned());
+            }
+            return Ok((user.update(db).await?, false));
+        }
+
+        let user = user::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            email: Set(email.to_owned()),
+            name: Set(name.unwrap_or_else(|| name_from_email(email)).to_owned()),
+            role: Set(SUPER_ADMIN_ROLE.to_owned()),
+            password_hash: Set(None),
+            email_verified_at: Set(Some(now)),
+            deactivated_at: Set(None),
+        }
+        .insert(db)
+        .await?;
+        Ok((user, true))
+    }
+
+    /// Promotes `user` to super admin if they are the bootstrap admin: their verified
+    /// address is `bootstrap_email` and there is no super admin yet. Anybody else is
+    /// returned unchanged.
+    pub async fn promote_bootstrap_admin(
+        db: &DbConn,
+        user: user::Model,
+        bootstrap_email: &str,
+    ) -> Result<user::Model, DbErr> {
+        if user.email != bootstrap_email
+            || user.email_verified_at.is_none()
+            || user.role == SUPER_ADMIN_ROLE
+            || Query::count_users_with_role(db, SUPER_ADMIN_ROLE).await? > 0
+        {
+            return Ok(user);
+        }
+        Self::change_user_role(db, user, SUPER_ADMIN_ROLE).await
+    }
+
+    /// Deactivates the user as of `deactivated_at`, or lets them back in with `None`. Their
+    /// sessions are not ended here.
+    pub async fn set_user_deactivated(
+        db: &DbConn,
+        user: user::Model,
+        deactivated_at: Option<DateTimeUtc>,
+    ) -> Result<user::Model, DbErr> {
+        let mut user: user::ActiveModel = user.into();
+        user.deactivated_at = Set(deactivated_at);
+        user.update(db).await
+    }
+
+    /// Deletes the user along with everything that belongs to them, episodes included.
+    pub async fn delete_user(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        User::delete_by_id(id).exec(db).await
+    }
+
+    /// Records a new session for `user_id`. Sessions of the user that have ended by being
+    /// idle are cleaned up on the way.
+    pub async fn start_user_session(
+        db: &DbConn,
+        user_id: Uuid,
+        client: SessionClient,
+        grant: Option<ProviderGrant>,
+        now: DateTimeUtc,
+    ) -> Result<user_session::Model, DbErr> {
+        UserSession::delete_many()
+            .filter(user_session::Column::UserId.eq(user_id))
+            .filter(user_session::Column::LastSeenAt.lte(idle_cutoff(now)))
+            .exec(db)
+            .await?;
+
+        let (provider, provider_access_token) = match grant {
+            Some(grant) => (Some(grant.provider), Some(grant.access_token)),
+            None => (None, None),
+        };
+        user_session::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            ip_address: Set(client.ip_address),
+            user_agent: Set(client.user_agent),
+            provider: Set(provider),
+            provider_access_token: Set(provider_access_token),
+            created_at: Set(now),
+            last_seen_at: Set(now),
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn touch_user_session(
+        db: &DbConn,
+        id: Uuid,
+        client: SessionClient,
+        now: DateTimeUtc,
+    ) -> Result<(), DbErr> {
+        UserSession::update_many()
+            .col_expr(user_session::Column::LastSeenAt, sea_query::Expr::value(now))
+            .col_expr(user_session::Column::IpAddress, sea_query::Expr::value(client.ip_address))
+            .col_expr(user_session::Column::UserAgent, sea_query::Expr::value(client.user_agent))
+            .filter(user_session::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Ends the session with `id`, optionally only if it belongs to `user_id`. Returns the
+    /// ended session so its provider token can be revoked.
+    pub async fn end_user_session(
+        db: &DbConn,
+        id: Uuid,
+        user_id: Option<Uuid>,
+    ) -> Result<Option<user_session::Model>, DbErr> {
+        let mut query = UserSession::find_by_id(id);
+        if let Some(user_id) = user_id {
+            query = query.filter(user_session::Column::UserId.eq(user_id));
+        }
+        let Some(session) = query.one(db).await? else {
+            return Ok(None);
+        };
+        UserSession::delete_by_id(session.id).exec(db).await?;
+        Ok(Some(session))
+    }
+
+    /// Ends every session of `user_id`, except `keep` if given, and returns them.
+    pub async fn end_user_sessions_of_user(
+        db: &DbConn,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+    ) -> Result<Vec<user_session::Model>, DbErr> {
+        let mut query = UserSession::find().filter(user_session::Column::UserId.eq(user_id));
+        if let Some(keep) = keep {
+            query = query.filter(user_session::Column::Id.ne(keep));
+        }
+        let sessions = query.all(db).await?;
+        UserSession::delete_many()
+            .filter(user_session::Column::Id.is_in(sessions.iter().map(|session| session.id)))
+            .exec(db)
+            .await?;
+        Ok(sessions)
+    }
+
+    /// Stores the entries of the web session with `id`, replacing what was stored before.
+    pub async fn save_web_session(
+        db: &DbConn,
+        id: &str,
+        entries: String,
+        expires_at: Option<DateTimeUtc>,
+        now: DateTimeUtc,
+    ) -> Result<(), DbErr> {
+        let session = web_session::ActiveModel {
+            id: Set(id.to_string()),
+            entries: Set(entries),
+            expires_at: Set(expires_at),
+            updated_at: Set(now),
+        };
+        WebSession::insert(session)
+            .on_conflict(
+                sea_query::OnConflict::column(web_session::Column::Id)
+                    .update_columns([
+                        web_session::Column::Entries,
+                        web_session::Column::ExpiresAt,
+                        web_session::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_web_session(db: &DbConn, id: &str) -> Result<(), DbErr> {
+        WebSession::delete_by_id(id).exec(db).await?;
+        Ok(())
+    }
+
+    /// Deletes the web sessions that have expired and returns how many there were.
+    pub async fn delete_expired_web_sessions(db: &DbConn, now: DateTimeUtc) -> Result<u64, DbErr> {
+        let result = WebSession::delete_many()
+            .filter(web_session::Column::ExpiresAt.lte(now))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+
+    /// What a provider tells about somebody after they logged in there.
+    fn identity(subject: &str, email: Option<&str>, email_verified: bool) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: "mock".to_owned(),
+            subject: subject.to_owned(),
+            email: email.map(str::to_owned),
+            email_verified,
+            name: None,
+        }
+    }
+
+    async fn sign_in(db: &DbConn, identity: &ExternalIdentity, policy: RegistrationPolicy) -> IdentitySignIn {
+        Mutation::sign_in_with_identity(db, identity, policy, chrono::Utc::now())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn identities_link_to_accounts_regardless_of_case() {
+        let db = test_db().await;
+        let existing = Mutation::create_user(&db, "jane@example.com", "Jane").await.unwrap();
+
+        let first = identity("jane-1", Some("  Jane@Example.COM "), true);
+        let IdentitySignIn::SignedIn(user) = sign_in(&db, &first, RegistrationPolicy::InviteOnly).await else {
+            panic!("the verified address belongs to an account");
+        };
+        assert_eq!(user.id, existing.id);
+        assert!(user.email_verified_at.is_some());
+
+        // From now on the identity is linked, whatever address it comes with.
+        let again = identity("jane-1", Some("jane@elsewhere.example"), false);
+        let IdentitySignIn::SignedIn(user) = sign_in(&db, &again, RegistrationPolicy::InviteOnly).await else {
+            panic!("the identity is linked");
+        };
+        assert_eq!(user.id, existing.id);
+        assert_eq!(User::find().count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn unverified_addresses_of_other_accounts_are_not_taken_over() {
+        let db = test_db().await;
+        Mutation::create_user(&db, "jane@example.com", "Jane").await.unwrap();
+
+        let impostor = identity("mallory", Some("JANE@example.com"), false);
+        let result = sign_in(&db, &impostor, RegistrationPolicy::Open).await;
+        assert!(matches!(result, IdentitySignIn::NoUsableEmail), "{result:?}");
+        let nobody = identity("ghost", None, true);
+        let result = sign_in(&db, &nobody, RegistrationPolicy::Open).await;
+        assert!(matches!(result, IdentitySignIn::NoUsableEmail), "{result:?}");
+        assert_eq!(User::find().count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn unverified_addresses_get_no_account() {
+        let db = test_db().await;
+        let impostor = identity("mallory", Some("jane@example.com"), false);
+        let result = sign_in(&db, &impostor, RegistrationPolicy::Open).await;
+        assert!(matches!(result, IdentitySignIn::NoUsableEmail), "{result:?}");
+        assert_eq!(User::find().count(&db).await.unwrap(), 0);
+
+        let owner = identity("jane-1", Some("jane@example.com"), true);
+        let IdentitySignIn::SignedIn(user) = sign_in(&db, &owner, RegistrationPolicy::Open).await else {
+            panic!("registration is open");
+        };
+        let result = sign_in(&db, &impostor, RegistrationPolicy::Open).await;
+        assert!(matches!(result, IdentitySignIn::NoUsableEmail), "{result:?}");
+        let identities = UserIdentity::find().all(&db).await.unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!((identities[0].user_id, identities[0].subject.as_str()), (user.id, "jane-1"));
+    }
+
+    #[tokio::test]
+    async fn owners_claiming_their_address_lock_out_earlier_logins() {
+        let db = test_db().await;
+        let now = chrono::Utc::now();
+        // Somebody got in with the address before it was verified: a password, a linked
+        // identity and a session.
+        let user = Mutation::register_local_user(&db, "jane@example.com", "Not Jane", "hash".to_owned())
+            .await
+            .unwrap();
+        let impostor = identity("mallory", Some("jane@example.com"), false);
+        Mutation::link_identity(&db, user.id, &impostor, now).await.unwrap();
+        Mutation::start_user_session(&db, user.id, SessionClient::default(), None, now)
+            .await
+            .unwrap();
+
+        let owner = identity("jane-1", Some("jane@example.com"), true);
+        let IdentitySignIn::SignedIn(claimed) = sign_in(&db, &owner, RegistrationPolicy::InviteOnly).await else {
+            panic!("the verified address belongs to an account");
+        };
+        assert_eq!(claimed.id, user.id);
+        assert!(claimed.email_verified_at.is_some());
+        assert_eq!(claimed.password_hash, None);
+        let identities = UserIdentity::find().all(&db).await.unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].subject, "jane-1");
+        assert_eq!(UserSession::find().count(&db).await.unwrap(), 0);
+
+        let result = sign_in(&db, &impostor, RegistrationPolicy::Open).await;
+        assert!(matches!(result, IdentitySignIn::NoUsableEmail), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn new_accounts_are_stored_normalized() {
+        let db = test_db().await;
+        let newcomer = identity("new-1", Some("New.Person@Example.com"), true);
+        let IdentitySignIn::SignedIn(user) = sign_in(&db, &newcomer, RegistrationPolicy::Open).await else {
+            panic!("registration is open");
+        };
+        assert_eq!(user.email, "new.person@example.com");
+        assert_eq!(user.name, "new.person");
+
+        // The same person through another provider ends up in the same account.
+        let other = ExternalIdentity {
+            provider: "other".to_owned(),
+            ..identity("x", Some("NEW.PERSON@example.com"), true)
+        };
+        let IdentitySignIn::SignedIn(same) = sign_in(&db, &other, RegistrationPolicy::Open).await else {
+            panic!("the verified address belongs to an account");
+        };
+        assert_eq!(same.id, user.id);
+    }
+
+    #[tokio::test]
+    async fn invitations_match_addresses_regardless_of_case() {
+        let db = test_db().await;
+        let admin = Mutation::create_user(&db, "admin@example.com", "Admin").await.unwrap();
+        let now = chrono::Utc::now();
+        Mutation::create_invitation(&db, "guest@example.com", "admin", admin.id, now)
+            .await
+            .unwrap();
+
+        let stranger = identity("stranger", Some("stranger@example.com"), true);
+        let result = sign_in(&db, &stranger, RegistrationPolicy::InviteOnly).await;
+        assert!(matches!(result, IdentitySignIn::NotInvited), "{result:?}");
+
+        let guest = identity("guest", Some("Guest@Example.com"), true);
+        let IdentitySignIn::SignedIn(user) = sign_in(&db, &guest, RegistrationPolicy::InviteOnly).await else {
+            panic!("the address was invited");
+        };
+        assert_eq!(user.email, "guest@example.com");
+        assert_eq!(user.role, "admin");
+        let pending = Query::find_pending_invitation_by_email(&db, "guest@example.com", now).await.unwrap();
+        assert!(pending.is_none(), "the invitation is used up");
+    }
+
+    async fn insert_show(db: &DbConn, title: &str) -> Uuid {
+        let show = show::Model {
+            id: Uuid::nil(),
+            title: title.to_owned(),
+            description: String::new(),
+            artwork_url: None,
+            language: "en".to_owned(),
+            category: String::new(),
+            author: String::new(),
+            owner_name: String::new(),
+            owner_email: String::new(),
+            explicit: false,
+        };
+        Mutation::create_show(db, show).await.unwrap().id.unwrap()
+    }
+
+    #[tokio::test]
+    async fn importing_an_export_twice_adds_nothing() {
+        let db = test_db().await;
+        let user = Mutation::create_user(&db, "host@example.com", "Host").await.unwrap();
+        let show_id = insert_show(&db, "Crab Talk").await;
+        let episodes: Vec<ExportedEpisode> = serde_json::from_str(
+            r#"[{"title": "One", "guid": "crab-1", "url": null, "published_at": null,
+                 "chapters": [{"start_ms": 0, "title": "Hello", "url": null, "image_url": null}]},
+                {"title": "Two", "guid": "crab-2", "url": null, "published_at": null}]"#,
+        )
+        .unwrap();
+
+        for exported in &episodes {
+            let episode = Mutation::import_episode(&db, exported, user.id, show_id).await.unwrap();
+            assert!(episode.is_some(), "{}", exported.title);
+        }
+        for exported in &episodes {
+            let episode = Mutation::import_episode(&db, exported, user.id, show_id).await.unwrap();
+            assert!(episode.is_none(), "{} was imported again", exported.title);
+        }
+        assert_eq!(Episode::find().count(&db).await.unwrap(), 2);
+        assert_eq!(Chapter::find().count(&db).await.unwrap(), 1);
+
+        // GUIDs only have to be unique within a show.
+        let other_show = insert_show(&db, "Crab Talk Extra").await;
+        let episode = Mutation::import_episode(&db, &episodes[0], user.id, other_show).await.unwrap();
+        assert_eq!(episode.unwrap().show_id, Some(other_show));
+    }
 }
@@ -0,0 +1,249 @@
+//! Who may do what.
+//!
+//! Users have one role, stored in the `role` table. A role grants the permissions listed
+//! for it in `role_permission` plus everything its parent role grants, so `super_admin`
+//! can do whatever `admin` can. A user's own role applies everywhere. Access to a single
+//! show is granted with a role as well, which then only applies to that show.
+
+use entities::{role, role_permission};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeSet, HashMap};
+
+/// The role new users get.
+pub const DEFAULT_ROLE: &str = "user";
+/// The role of the account created on first start.
+pub const SUPER_ADMIN_ROLE: &str = "super_admin";
+/// The role show access is granted with unless another one is picked.
+pub const DEFAULT_SHOW_ROLE: &str = "host";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    /// Create, edit and delete posts.
+    PostEdit,
+    /// Create, edit and delete members.
+    MemberEdit,
+    /// Create, edit and delete shows.
+    ShowManage,
+    /// Decide who may work on a show.
+    ShowGrant,
+    /// Create, edit and delete episodes, with their chapters and transcripts.
+    EpisodeEdit,
+    /// Publish, schedule and archive episodes.
+    EpisodePublish,
+    /// See everybody's accounts and sessions and log people out.
+    UserManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 7] = [
+        Permission::PostEdit,
+        Permission::MemberEdit,
+        Permission::ShowManage,
+        Permission::ShowGrant,
+        Permission::EpisodeEdit,
+        Permission::EpisodePublish,
+        Permission::UserManage,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::PostEdit => "post:edit",
+            Permission::MemberEdit => "member:edit",
+            Permission::ShowManage => "show:manage",
+            Permission::ShowGrant => "show:grant",
+            Permission::EpisodeEdit => "episode:edit",
+            Permission::EpisodePublish => "episode:publish",
+            Permission::UserManage => "user:manage",
+        }
+    }
+
+    pub fn parse(permission: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == permission)
+    }
+}
+
+/// A set of permissions. Serializes as a list of names like `episode:publish`, which is
+/// how templates get to see it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Permissions(BTreeSet<Permission>);
+
+impl Permissions {
+    pub fn has(&self, permission: Permission) -> bool {
+        self.0.contains(&permission)
+    }
+
+    /// Everything in `self` or `other`.
+    pub fn union(mut self, other: Permissions) -> Self {
+        self.0.extend(other.0);
+        self
+    }
+}
+
+impl FromIterator<Permission> for Permissions {
+    fn from_iter<I: IntoIterator<Item = Permission>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|permission| permission.as_str()))
+    }
+}
+
+/// Every role with its inherited permissions resolved.
+#[derive(Clone, Debug, Default)]
+pub struct Roles {
+    roles: Vec<role::Model>,
+    permissions: HashMap<String, Permissions>,
+}
+
+impl Roles {
+    /// Resolves the inheritance between `roles`. Permission names nobody knows are
+    /// ignored, and so are parents that would make a role inherit from itself.
+    pub fn new(roles: Vec<role::Model>, grants: Vec<role_permission::Model>) -> Self {
+        let mut own: HashMap<&str, Vec<Permission>> = HashMap::new();
+        for grant in &grants {
+            if let Some(permission) = Permission::parse(&grant.permission) {
+                own.entry(grant.role.as_str()).or_default().push(permission);
+            }
+        }
+        let parents: HashMap<&str, Option<&str>> = roles
+            .iter()
+            .map(|role| (role.name.as_str(), role.parent.as_deref()))
+            .collect();
+
+        let permissions = roles
+            .iter()
+            .map(|role| {
+                let mut seen = BTreeSet::new();
+                let mut permissions = BTreeSet::new();
+                let mut next = Some(role.name.as_str());
+                while let Some(name) = next.filter(|name| seen.insert(*name)) {
+                    permissions.extend(own.get(name).into_iter().flatten().copied());
+                    next = parents.get(name).copied().flatten();
+                }
+                (role.name.clone(), Permissions(permissions))
+            })
+            .collect();
+        Self { roles, permissions }
+    }
+
+    /// What `role` may do. Unknown roles may do nothing.
+    pub fn permissions_of(&self, role: &str) -> Permissions {
+        self.permissions.get(role).cloned().unwrap_or_default()
+    }
+
+    pub fn contains(&self, role: &str) -> bool {
+        self.permissions.contains_key(role)
+    }
+
+    /// Every role, for picking one.
+    pub fn all(&self) -> &[role::Model] {
+        &self.roles
+    }
+
+    /// Whether `role` inherits what `ancestor` grants, through its parent or further up,
+    /// which ranks it above `ancestor`.
+    pub fn inherits_from(&self, role: &str, ancestor: &str) -> bool {
+        let parent_of = |name: &str| {
+            self.roles
+                .iter()
+                .find(|role| role.name == name)
+                .and_then(|role| role.parent.as_deref())
+        };
+        let mut seen = BTreeSet::new();
+        let mut next = parent_of(role);
+        while let Some(name) = next.filter(|name| seen.insert(*name)) {
+            if name == ancestor {
+                return true;
+            }
+            next = parent_of(name);
+        }
+        false
+    }
+
+    /// Whether somebody with the role `granter` may give others `role`, or take it from
+    /// them: only roles below their own, so nobody can make anyone their equal. Super
+    /// admins may hand out any role, their own included.
+    pub fn may_grant(&self, granter: &str, role: &str) -> bool {
+        self.contains(role) && (granter == SUPER_ADMIN_ROLE || self.inherits_from(granter, role))
+    }
+
+    /// The roles `granter` may hand out, see [`Roles::may_grant`].
+    pub fn grantable_by(&self, granter: &str) -> Vec<&role::Model> {
+        self.roles
+            .iter()
+            .filter(|role| self.may_grant(granter, &role.name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles() -> Roles {
+        let chain = [
+            ("user", None),
+            ("contributor", Some("user")),
+            ("host", Some("contributor")),
+            ("admin", Some("host")),
+            ("super_admin", Some("admin")),
+            ("moderator", Some("user")),
+            ("loop_a", Some("loop_b")),
+            ("loop_b", Some("loop_a")),
+        ];
+        let roles = chain
+            .into_iter()
+            .map(|(name, parent)| role::Model {
+                name: name.to_string(),
+                label: name.to_string(),
+                parent: parent.map(str::to_string),
+            })
+            .collect();
+        let grants = [("contributor", "episode:edit"), ("host", "episode:publish"), ("super_admin", "user:manage")]
+            .into_iter()
+            .map(|(role, permission)| role_permission::Model {
+                role: role.to_string(),
+                permission: permission.to_string(),
+            })
+            .collect();
+        Roles::new(roles, grants)
+    }
+
+    #[test]
+    fn roles_inherit_from_their_parents() {
+        let roles = roles();
+        let host = roles.permissions_of("host");
+        assert!(host.has(Permission::EpisodeEdit) && host.has(Permission::EpisodePublish));
+        assert!(!host.has(Permission::UserManage));
+        assert!(roles.permissions_of("super_admin").has(Permission::EpisodeEdit));
+        assert_eq!(roles.permissions_of("nobody"), Permissions::default());
+
+        assert!(roles.inherits_from("super_admin", "user"));
+        assert!(roles.inherits_from("host", "contributor"));
+        assert!(!roles.inherits_from("host", "host"));
+        assert!(!roles.inherits_from("contributor", "host"));
+        assert!(!roles.inherits_from("moderator", "contributor"));
+        assert!(!roles.inherits_from("loop_a", "user"));
+    }
+
+    #[test]
+    fn only_lower_roles_are_grantable() {
+        let roles = roles();
+        assert!(roles.may_grant("admin", "host"));
+        assert!(roles.may_grant("admin", "user"));
+        assert!(!roles.may_grant("admin", "admin"));
+        assert!(!roles.may_grant("admin", "super_admin"));
+        assert!(!roles.may_grant("admin", "moderator"), "sideways roles are not below admin");
+        assert!(!roles.may_grant("host", "nobody"));
+
+        assert!(roles.may_grant(SUPER_ADMIN_ROLE, SUPER_ADMIN_ROLE));
+        assert!(roles.may_grant(SUPER_ADMIN_ROLE, "moderator"));
+        assert!(!roles.may_grant(SUPER_ADMIN_ROLE, "nobody"));
+
+        let names: Vec<&str> = roles.grantable_by("host").iter().map(|role| role.name.as_str()).collect();
+        assert_eq!(names, ["user", "contributor"]);
+    }
+}
@@ -0,0 +1,90 @@
+//! The episode publishing workflow.
+//!
+//! Episodes start out as drafts and move on to scheduled, published and finally archived.
+//! A scheduled episode can be published early or pulled back to draft, and an archived
+//! episode can be republished or reworked as a draft.
+
+use entities::sea_orm_active_enums::EpisodeStatus;
+
+/// Statuses an episode in `from` may be moved to.
+pub fn allowed_transitions(from: EpisodeStatus) -> &'static [EpisodeStatus] {
+    use EpisodeStatus::*;
+    match from {
+        Draft => &[Scheduled, Published],
+        Scheduled => &[Draft, Published],
+        Published => &[Archived],
+        Archived => &[Published, Draft],
+    }
+}
+
+pub fn can_transition(from: EpisodeStatus, to: EpisodeStatus) -> bool {
+    allowed_transitions(from).contains(&to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+    use crate::{Mutation, Query};
+    use chrono::{Duration, Utc};
+    use entities::episode;
+    use EpisodeStatus::*;
+
+    #[test]
+    fn episodes_move_only_along_the_workflow() {
+        for (from, to) in [
+            (Draft, Scheduled),
+            (Draft, Published),
+            (Scheduled, Draft),
+            (Scheduled, Published),
+            (Published, Archived),
+            (Archived, Published),
+            (Archived, Draft),
+        ] {
+            assert!(can_transition(from, to), "{from:?} to {to:?}");
+        }
+        for (from, to) in [
+            (Draft, Archived),
+            (Published, Draft),
+            (Published, Scheduled),
+            (Archived, Scheduled),
+            (Draft, Draft),
+            (Published, Published),
+        ] {
+            assert!(!can_transition(from, to), "{from:?} to {to:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn scheduled_episodes_go_live_when_due() {
+        let db = test_db().await;
+        let user = Mutation::create_user(&db, "host@example.com", "Host").await.unwrap();
+        let form: episode::Model =
+            serde_json::from_str(r#"{"title": "One", "summary": "", "tags": "", "url": null, "show_id": null}"#)
+                .unwrap();
+        let draft = Mutation::create_episode(&db, episode::Model { user_id: user.id, ..form }).await.unwrap();
+        let now = Utc::now();
+
+        let an_hour_ago = Some(now - Duration::hours(1));
+        let past = Mutation::change_episode_status(&db, draft.id, Scheduled, an_hour_ago, now).await;
+        assert!(past.is_err(), "scheduling needs a time in the future");
+        let release = now + Duration::hours(1);
+        let scheduled = Mutation::change_episode_status(&db, draft.id, Scheduled, Some(release), now)
+            .await
+            .unwrap();
+        assert_eq!((scheduled.status, scheduled.scheduled_for), (Scheduled, Some(release)));
+
+        assert_eq!(Mutation::publish_due_episodes(&db, now).await.unwrap(), 0);
+        assert_eq!(Mutation::publish_due_episodes(&db, release).await.unwrap(), 1);
+        let published = Query::find_episode_by_id(&db, draft.id).await.unwrap().unwrap();
+        assert_eq!(published.status, Published);
+        assert_eq!(published.published_at, Some(release), "published when it was due, not when noticed");
+        assert_eq!(published.scheduled_for, None);
+        assert_eq!(Mutation::publish_due_episodes(&db, release).await.unwrap(), 0);
+
+        Mutation::change_episode_status(&db, draft.id, Archived, None, release).await.unwrap();
+        let later = Some(release + Duration::days(1));
+        let rescheduled = Mutation::change_episode_status(&db, draft.id, Scheduled, later, release).await;
+        assert!(rescheduled.is_err(), "archived episodes cannot be scheduled");
+    }
+}
@@ -1,26 +1,182 @@
+use crate::api_tokens::hash_token;
+use crate::backups::{BackedUpEpisode, BackedUpMember, BackedUpShow, BackedUpUser, ShowBackup, SHOW_BACKUP_VERSION};
+use crate::permissions::{Permission, Permissions, Roles};
+use crate::sessions::idle_cutoff;
 use entities::prelude::User;
+use entities::{api_token, api_token::Entity as ApiToken};
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{chapter, chapter::Entity as Chapter};
 use entities::{episode, episode::Entity as Episode, member, member::Entity as Member, user};
+use entities::{invitation, invitation::Entity as Invitation};
+use entities::{show, show::Entity as Show, show_user, show_user::Entity as ShowUser};
 use entities::{post, post::Entity as Post};
-use sea_orm::prelude::Uuid;
+use entities::{role::Entity as Role, role_permission::Entity as RolePermission};
+use entities::{transcript, transcript::Entity as Transcript};
+use entities::{user_session, user_session::Entity as UserSession};
+use entities::{web_session, web_session::Entity as WebSession};
+use sea_orm::prelude::{DateTimeUtc, Uuid};
 use sea_orm::*;
 
 pub struct Query;
 
+/// One page of results together with the totals needed to page through the rest.
+#[derive(Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// One based.
+    pub page: u64,
+    pub per_page: u64,
+    pub total_items: u64,
+    pub total_pages: u64,
+}
+
+/// Restricts an episode listing; `None` fields do not filter.
+#[derive(Clone, Debug, Default)]
+pub struct EpisodeFilter {
+    pub show_ids: Option<Vec<Uuid>>,
+    pub status: Option<EpisodeStatus>,
+    /// Matched against the title.
+    pub search: Option<String>,
+}
+
+async fn fetch_page<E>(
+    db: &DbConn,
+    select: Select<E>,
+    page: u64,
+    per_page: u64,
+) -> Result<Page<E::Model>, DbErr>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+{
+    let page = page.max(1);
+    let paginator = select.paginate(db, per_page.max(1));
+    let totals = paginator.num_items_and_pages().await?;
+    let items = paginator.fetch_page(page - 1).await?;
+    Ok(Page {
+        items,
+        page,
+        per_page,
+        total_items: totals.number_of_items,
+        total_pages: totals.number_of_pages,
+    })
+}
+
 impl Query {
     pub async fn find_member_by_id(db: &DbConn, id: Uuid) -> Result<Option<member::Model>, DbErr> {
         Member::find_by_id(id).one(db).await
     }
+    pub async fn find_episode_by_id(db: &DbConn, id: Uuid) -> Result<Option<episode::Model>, DbErr> {
+        Episode::find_by_id(id).one(db).await
+    }
+
+    /// Published episodes, newest first.
+    pub async fn find_published_episodes(db: &DbConn) -> Result<Vec<episode::Model>, DbErr> {
+        Episode::find()
+            .filter(episode::Column::Status.eq(EpisodeStatus::Published))
+            .order_by_desc(episode::Column::PublishedAt)
+            .all(db)
+            .await
+    }
+
+    /// Published episodes of a show, newest first.
+    pub async fn find_published_episodes_by_show(
+        db: &DbConn,
+        show_id: Uuid,
+    ) -> Result<Vec<episode::Model>, DbErr> {
+        Episode::find()
+            .filter(episode::Column::ShowId.eq(show_id))
+            .filter(episode::Column::Status.eq(EpisodeStatus::Published))
+            .order_by_desc(episode::Column::PublishedAt)
+            .all(db)
+            .await
+    }
+
+    /// Every episode, or those of one show, oldest first.
+    pub async fn find_all_episodes(
+        db: &DbConn,
+        show_id: Option<Uuid>,
+    ) -> Result<Vec<episode::Model>, DbErr> {
+        let mut select = Episode::find();
+        if let Some(show_id) = show_id {
+            select = select.filter(episode::Column::ShowId.eq(show_id));
+        }
+        select
+            .order_by_asc(episode::Column::PublishedAt)
+            .order_by_asc(episode::Column::Title)
+            .all(db)
+            .await
+    }
+
+    /// Chapters of an episode in playback order.
+    pub async fn find_chapters_by_episode(
+        db: &DbConn,
+        episode_id: Uuid,
+    ) -> Result<Vec<chapter::Model>, DbErr> {
+        Chapter::find()
+            .filter(chapter::Column::EpisodeId.eq(episode_id))
+            .order_by_asc(chapter::Column::StartMs)
+            .all(db)
+            .await
+    }
+
+    /// Which of the given episodes have at least one chapter.
+    pub async fn find_episode_ids_with_chapters(
+        db: &DbConn,
+        episode_ids: &[Uuid],
+    ) -> Result<Vec<Uuid>, DbErr> {
+        Chapter::find()
+            .select_only()
+            .column(chapter::Column::EpisodeId)
+            .distinct()
+            .filter(chapter::Column::EpisodeId.is_in(episode_ids.iter().copied()))
+            .into_tuple()
+            .all(db)
+            .await
+    }
+
+    /// Transcripts of an episode, ordered by language.
+    pub async fn find_transcripts_by_episode(
+        db: &DbConn,
+        episode_id: Uuid,
+    ) -> Result<Vec<transcript::Model>, DbErr> {
+        Transcript::find()
+            .filter(transcript::Column::EpisodeId.eq(episode_id))
+            .order_by_asc(transcript::Column::Language)
+            .all(db)
+            .await
+    }
+
+    /// Transcripts of several episodes at once, e.g. for a feed.
+    pub async fn find_transcripts_by_episodes(
+        db: &DbConn,
+        episode_ids: &[Uuid],
+    ) -> Result<Vec<transcript::Model>, DbErr> {
+        Transcript::find()
+            .filter(transcript::Column::EpisodeId.is_in(episode_ids.iter().copied()))
+            .order_by_asc(transcript::Column::Language)
+            .all(db)
+            .await
+    }
+
+    /// If ok, returns (episode models, num pages). With `show_ids` only episodes of
+    /// those shows are returned.
     pub async fn find_episodes(
         db: &DatabaseConnection,
+        show_ids: Option<&[Uuid]>,
         page: u64,
         episodes_per_page: u64,
     ) -> Result<(Vec<episode::Model>, u64), DbErr> {
-        let paginator = Episode::find()
+        let mut query = Episode::find();
+        if let Some(show_ids) = show_ids {
+            query = query.filter(episode::Column::ShowId.is_in(show_ids.iter().copied()));
+        }
+        let paginator = query
             .order_by_asc(episode::Column::Id)
             .paginate(db, episodes_per_page);
         let num_pages = paginator.num_pages().await?;
 
-        // Fetch paginated members
+        // Fetch paginated episodes
         paginator.fetch_page(page - 1).await.map(|p| (p, num_pages))
     }
 
@@ -64,8 +220,389 @@ impl Query {
         email: &str,
     ) -> Result<Option<user::Model>, DbErr> {
         User::find()
-            .filter(user::Column::Email.contains(email))
+            .filter(user::Column::Email.eq(email))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_user_by_id(db: &DbConn, id: Uuid) -> Result<Option<user::Model>, DbErr> {
+        User::find_by_id(id).one(db).await
+    }
+
+    /// A user's API tokens, newest first.
+    pub async fn find_api_tokens_by_user(
+        db: &DbConn,
+        user_id: Uuid,
+    ) -> Result<Vec<api_token::Model>, DbErr> {
+        ApiToken::find()
+            .filter(api_token::Column::UserId.eq(user_id))
+            .order_by_desc(api_token::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    /// The unexpired token with secret `token`, along with its owner.
+    pub async fn find_api_token_user(
+        db: &DbConn,
+        token: &str,
+        now: DateTimeUtc,
+    ) -> Result<Option<(api_token::Model, user::Model)>, DbErr> {
+        let found = ApiToken::find()
+            .filter(api_token::Column::TokenHash.eq(hash_token(token)))
+            .find_also_related(User)
+            .one(db)
+            .await?;
+        Ok(found
+            .filter(|(token, _)| token.expires_at.is_none_or(|expires_at| expires_at > now))
+            .and_then(|(token, user)| Some((token, user?))))
+    }
+
+    /// The session with `id` and its user as currently stored, unless the session has ended.
+    pub async fn find_active_user_session(
+        db: &DbConn,
+        id: Uuid,
+        now: DateTimeUtc,
+    ) -> Result<Option<(user_session::Model, user::Model)>, DbErr> {
+        let session = UserSession::find_by_id(id)
+            .filter(user_session::Column::LastSeenAt.gt(idle_cutoff(now)))
+            .find_also_related(User)
+            .one(db)
+            .await?;
+        Ok(session.and_then(|(session, user)| Some((session, user?))))
+    }
+
+    /// The stored web session with `id`, unless it has expired.
+    pub async fn find_web_session(
+        db: &DbConn,
+        id: &str,
+        now: DateTimeUtc,
+    ) -> Result<Option<web_session::Model>, DbErr> {
+        WebSession::find_by_id(id)
+            .filter(
+                Condition::any()
+                    .add(web_session::Column::ExpiresAt.is_null())
+                    .add(web_session::Column::ExpiresAt.gt(now)),
+            )
+            .one(db)
+            .await
+    }
+
+    /// A user's sessions that have not ended, most recently used first.
+    pub async fn find_active_user_sessions_by_user(
+        db: &DbConn,
+        user_id: Uuid,
+        now: DateTimeUtc,
+    ) -> Result<Vec<user_session::Model>, DbErr> {
+        UserSession::find()
+            .filter(user_session::Column::UserId.eq(user_id))
+            .filter(user_session::Column::LastSeenAt.gt(idle_cutoff(now)))
+            .order_by_desc(user_session::Column::LastSeenAt)
+            .all(db)
+            .await
+    }
+
+    /// Everybody's sessions that have not ended, with their users, most recently used first.
+    pub async fn find_all_active_user_sessions(
+        db: &DbConn,
+        now: DateTimeUtc,
+    ) -> Result<Vec<(user_session::Model, user::Model)>, DbErr> {
+        let sessions = UserSession::find()
+            .filter(user_session::Column::LastSeenAt.gt(idle_cutoff(now)))
+            .order_by_desc(user_session::Column::LastSeenAt)
+            .find_also_related(User)
+            .all(db)
+            .await?;
+        Ok(sessions
+            .into_iter()
+            .filter_map(|(session, user)| Some((session, user?)))
+            .collect())
+    }
+
+    /// Users ordered by email, optionally only those with `role` or matching `search`.
+    pub async fn find_users_page(
+        db: &DbConn,
+        role: Option<&str>,
+        search: Option<&str>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<user::Model>, DbErr> {
+        let mut select = User::find().order_by_asc(user::Column::Email);
+        if let Some(role) = role {
+            select = select.filter(user::Column::Role.eq(role));
+        }
+        if let Some(search) = search {
+            select = select.filter(
+                Condition::any()
+                    .add(user::Column::Email.contains(search))
+                    .add(user::Column::Name.contains(search)),
+            );
+        }
+        fetch_page(db, select, page, per_page).await
+    }
+
+    /// Shows ordered by title. With `show_ids` only those shows are returned.
+    pub async fn find_shows_page(
+        db: &DbConn,
+        show_ids: Option<&[Uuid]>,
+        search: Option<&str>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<show::Model>, DbErr> {
+        let mut select = Show::find().order_by_asc(show::Column::Title);
+        if let Some(show_ids) = show_ids {
+            select = select.filter(show::Column::Id.is_in(show_ids.iter().copied()));
+        }
+        if let Some(search) = search {
+            select = select.filter(show::Column::Title.contains(search));
+        }
+        fetch_page(db, select, page, per_page).await
+    }
+
+    /// Episodes matching `filter`, newest first.
+    pub async fn find_episodes_page(
+        db: &DbConn,
+        filter: &EpisodeFilter,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<episode::Model>, DbErr> {
+        let mut select = Episode::find()
+            .order_by_desc(episode::Column::PublishedAt)
+            .order_by_asc(episode::Column::Title);
+        if let Some(show_ids) = &filter.show_ids {
+            select = select.filter(episode::Column::ShowId.is_in(show_ids.iter().copied()));
+        }
+        if let Some(status) = filter.status {
+            select = select.filter(episode::Column::Status.eq(status));
+        }
+        if let Some(search) = &filter.search {
+            select = select.filter(episode::Column::Title.contains(search));
+        }
+        fetch_page(db, select, page, per_page).await
+    }
+
+    /// Members ordered by last name, optionally matching `search` on name or email.
+    pub async fn find_members_page(
+        db: &DbConn,
+        search: Option<&str>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<member::Model>, DbErr> {
+        let mut select = Member::find()
+            .order_by_asc(member::Column::LastName)
+            .order_by_asc(member::Column::FirstName);
+        if let Some(search) = search {
+            select = select.filter(
+                Condition::any()
+                    .add(member::Column::FirstName.contains(search))
+                    .add(member::Column::LastName.contains(search))
+                    .add(member::Column::Email.contains(search)),
+            );
+        }
+        fetch_page(db, select, page, per_page).await
+    }
+
+    /// Posts ordered by title, optionally matching `search` on the title.
+    pub async fn find_posts_page(
+        db: &DbConn,
+        search: Option<&str>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<post::Model>, DbErr> {
+        let mut select = Post::find().order_by_asc(post::Column::Title);
+        if let Some(search) = search {
+            select = select.filter(post::Column::Title.contains(search));
+        }
+        fetch_page(db, select, page, per_page).await
+    }
+
+    pub async fn find_show_by_id(db: &DbConn, id: Uuid) -> Result<Option<show::Model>, DbErr> {
+        Show::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_all_shows(db: &DbConn) -> Result<Vec<show::Model>, DbErr> {
+        Show::find().order_by_asc(show::Column::Title).all(db).await
+    }
+
+    /// Shows the user may work on: every show for users whose role lets them edit episodes,
+    /// the shows they were granted access to for everybody else.
+    pub async fn find_shows_for_user(
+        db: &DbConn,
+        user: &user::Model,
+    ) -> Result<Vec<show::Model>, DbErr> {
+        if Self::find_user_permissions(db, user).await?.has(Permission::EpisodeEdit) {
+            return Self::find_all_shows(db).await;
+        }
+        user.find_related(Show)
+            .order_by_asc(show::Column::Title)
+            .all(db)
+            .await
+    }
+
+    /// Everybody granted access to the show, with the role they were granted.
+    pub async fn find_show_users(
+        db: &DbConn,
+        show_id: Uuid,
+    ) -> Result<Vec<(show_user::Model, user::Model)>, DbErr> {
+        let grants = ShowUser::find()
+            .filter(show_user::Column::ShowId.eq(show_id))
+            .find_also_related(User)
+            .order_by_asc(user::Column::Email)
+            .all(db)
+            .await?;
+        Ok(grants
+            .into_iter()
+            .filter_map(|(grant, user)| Some((grant, user?)))
+            .collect())
+    }
+
+    /// Everything about a show that goes into a backup. `media_url` is what URLs of
+    /// stored media start with; the files they point at are listed for the archive.
+    pub async fn find_show_backup(
+        db: &DbConn,
+        show_id: Uuid,
+        media_url: &str,
+        now: DateTimeUtc,
+    ) -> Result<Option<ShowBackup>, DbErr> {
+        let Some(show) = Self::find_show_by_id(db, show_id).await? else {
+            return Ok(None);
+        };
+        let grants = Self::find_show_users(db, show_id).await?;
+        let episodes = Self::find_all_episodes(db, Some(show_id)).await?;
+
+        let mut users: Vec<user::Model> = grants.iter().map(|(_, user)| user.clone()).collect();
+        for episode in &episodes {
+            if !users.iter().any(|user| user.id == episode.user_id) {
+                let owner = Self::find_user_by_id(db, episode.user_id)
+                    .await?
+                    .ok_or(DbErr::Custom("Cannot find the owner of an episode.".to_owned()))?;
+                users.push(owner);
+            }
+        }
+
+        let mut backed_up_episodes = Vec::with_capacity(episodes.len());
+        for episode in &episodes {
+            let owner = users
+                .iter()
+                .find(|user| user.id == episode.user_id)
+                .map(|user| user.email.as_str())
+                .unwrap_or_default();
+            let chapters = Self::find_chapters_by_episode(db, episode.id).await?;
+            let transcripts = Self::find_transcripts_by_episode(db, episode.id).await?;
+            let backed_up = BackedUpEpisode::new(episode, owner, &chapters, &transcripts, media_url)
+                .map_err(|err| DbErr::Custom(err.to_string()))?;
+            backed_up_episodes.push(backed_up);
+        }
+
+        Ok(Some(ShowBackup {
+            version: SHOW_BACKUP_VERSION,
+            created_at: now,
+            media_url: media_url.to_owned(),
+            show: BackedUpShow::from(&show),
+            users: users
+                .iter()
+                .map(|user| BackedUpUser {
+                    email: user.email.clone(),
+                    name: user.name.clone(),
+                    role: user.role.clone(),
+                })
+                .collect(),
+            members: grants
+                .iter()
+                .map(|(grant, user)| BackedUpMember {
+                    email: user.email.clone(),
+                    role: grant.role.clone(),
+                })
+                .collect(),
+            episodes: backed_up_episodes,
+        }))
+    }
+
+    pub async fn find_roles(db: &DbConn) -> Result<Roles, DbErr> {
+        let roles = Role::find().all(db).await?;
+        let grants = RolePermission::find().all(db).await?;
+        Ok(Roles::new(roles, grants))
+    }
+
+    /// What the user's own role allows, everywhere.
+    pub async fn find_user_permissions(db: &DbConn, user: &user::Model) -> Result<Permissions, DbErr> {
+        Ok(Self::find_roles(db).await?.permissions_of(&user.role))
+    }
+
+    /// What the user may do with one show: what their own role allows plus what the role
+    /// they were granted for the show allows.
+    pub async fn find_show_permissions(
+        db: &DbConn,
+        user: &user::Model,
+        show_id: Uuid,
+    ) -> Result<Permissions, DbErr> {
+        let roles = Self::find_roles(db).await?;
+        let permissions = roles.permissions_of(&user.role);
+        let grant = ShowUser::find_by_id((show_id, user.id)).one(db).await?;
+        Ok(match grant {
+            Some(grant) => permissions.union(roles.permissions_of(&grant.role)),
+            None => permissions,
+        })
+    }
+
+    /// Invitations that were neither accepted nor expired, newest first.
+    pub async fn find_pending_invitations(
+        db: &DbConn,
+        now: DateTimeUtc,
+    ) -> Result<Vec<invitation::Model>, DbErr> {
+        Invitation::find()
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .filter(invitation::Column::ExpiresAt.gt(now))
+            .order_by_desc(invitation::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_invitation_by_id(db: &DbConn, id: Uuid) -> Result<Option<invitation::Model>, DbErr> {
+        Invitation::find_by_id(id).one(db).await
+    }
+
+    /// The pending invitation mailed with `token`.
+    pub async fn find_pending_invitation_by_token(
+        db: &DbConn,
+        token: &str,
+        now: DateTimeUtc,
+    ) -> Result<Option<invitation::Model>, DbErr> {
+        Invitation::find()
+            .filter(invitation::Column::TokenHash.eq(hash_token(token)))
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .filter(invitation::Column::ExpiresAt.gt(now))
+            .one(db)
+            .await
+    }
+
+    /// The newest pending invitation for `email`.
+    pub async fn find_pending_invitation_by_email(
+        db: &DbConn,
+        email: &str,
+        now: DateTimeUtc,
+    ) -> Result<Option<invitation::Model>, DbErr> {
+        Invitation::find()
+            .filter(invitation::Column::Email.eq(email))
+            .filter(invitation::Column::AcceptedAt.is_null())
+            .filter(invitation::Column::ExpiresAt.gt(now))
+            .order_by_desc(invitation::Column::CreatedAt)
             .one(db)
             .await
     }
+
+    /// How many users have `role` itself, not counting roles that inherit from it.
+    pub async fn count_users_with_role(db: &DbConn, role: &str) -> Result<u64, DbErr> {
+        User::find()
+            .filter(user::Column::Role.eq(role))
+            .count(db)
+            .await
+    }
+
+    /// How many episodes the user created. Deleting the user would delete them too.
+    pub async fn count_episodes_by_user(db: &DbConn, user_id: Uuid) -> Result<u64, DbErr> {
+        Episode::find()
+            .filter(episode::Column::UserId.eq(user_id))
+            .count(db)
+            .await
+    }
 }
@@ -0,0 +1,91 @@
+//! Structured data for the public pages, as schema.org JSON-LD.
+//!
+//! Like the feed builder this only deals in models and URLs, so the output can be
+//! dropped into any template.
+
+use entities::{episode, show};
+use serde_json::{json, Value};
+
+/// Formats a number of seconds as an ISO 8601 duration, e.g. `PT1H2M3S`.
+pub fn iso8601_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    let mut duration = String::from("PT");
+    if hours > 0 {
+        duration.push_str(&format!("{hours}H"));
+    }
+    if minutes > 0 {
+        duration.push_str(&format!("{minutes}M"));
+    }
+    if seconds > 0 || duration.len() == 2 {
+        duration.push_str(&format!("{seconds}S"));
+    }
+    duration
+}
+
+/// Serializes JSON-LD so that it can sit inside a `<script>` element.
+fn to_script(value: &Value) -> String {
+    // `</script>` or `<!--` in a title must not end the element early.
+    value.to_string().replace('<', "\\u003c").replace('>', "\\u003e")
+}
+
+fn series(show: &show::Model, show_url: &str) -> Value {
+    let mut series = json!({
+        "@type": "PodcastSeries",
+        "name": show.title,
+        "url": show_url,
+    });
+    if !show.description.is_empty() {
+        series["description"] = json!(show.description);
+    }
+    if !show.author.is_empty() {
+        series["author"] = json!({ "@type": "Person", "name": show.author });
+    }
+    if let Some(artwork_url) = show.artwork_url.as_deref().filter(|url| !url.is_empty()) {
+        series["image"] = json!(artwork_url);
+    }
+    series
+}
+
+/// `PodcastSeries` markup for a show homepage.
+pub fn show_json_ld(show: &show::Model, show_url: &str, feed_url: &str) -> String {
+    let mut series = series(show, show_url);
+    series["@context"] = json!("https://schema.org");
+    series["webFeed"] = json!(feed_url);
+    series["inLanguage"] = json!(show.language);
+    to_script(&series)
+}
+
+/// `PodcastEpisode` markup for an episode page.
+pub fn episode_json_ld(
+    show: &show::Model,
+    show_url: &str,
+    episode: &episode::Model,
+    episode_url: &str,
+) -> String {
+    let mut markup = json!({
+        "@context": "https://schema.org",
+        "@type": "PodcastEpisode",
+        "url": episode_url,
+        "name": episode.title,
+        "description": episode.summary,
+        "inLanguage": show.language,
+        "partOfSeries": series(show, show_url),
+    });
+    if let Some(published_at) = episode.published_at {
+        markup["datePublished"] = json!(published_at.date_naive().to_string());
+    }
+    if let Some(duration_ms) = episode.duration_ms {
+        markup["timeRequired"] = json!(iso8601_duration((duration_ms.max(0) as u64 + 500) / 1000));
+    }
+    if let Some(url) = episode.url.as_deref().filter(|url| !url.is_empty()) {
+        let mut media = json!({ "@type": "AudioObject", "contentUrl": url });
+        if let Some(media_type) = &episode.media_type {
+            media["encodingFormat"] = json!(media_type);
+        }
+        markup["associatedMedia"] = media;
+    }
+    if let Some(artwork_url) = episode.artwork_url.as_deref().filter(|url| !url.is_empty()) {
+        markup["image"] = json!(artwork_url);
+    }
+    to_script(&markup)
+}
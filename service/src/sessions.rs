@@ -0,0 +1,49 @@
+//! Logged in browser sessions, as listed on the sessions page.
+
+use chrono::Duration;
+use sea_orm::prelude::DateTimeUtc;
+
+/// Sessions nobody used for this long are over, even without logging out.
+pub const IDLE_TIMEOUT_DAYS: i64 = 30;
+
+/// Sessions last seen before this are over.
+pub fn idle_cutoff(now: DateTimeUtc) -> DateTimeUtc {
+    now - Duration::days(IDLE_TIMEOUT_DAYS)
+}
+
+/// A short description of the browser and system behind a `User-Agent` header, e.g.
+/// `Firefox on Linux`. Good enough to tell one's own devices apart, nothing more.
+pub fn describe_user_agent(user_agent: &str) -> String {
+    // Order matters: Edge and Opera claim to be Chrome, Chrome claims to be Safari.
+    const BROWSERS: [(&str, &str); 7] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+        ("Mozilla/", "Browser"),
+    ];
+    const SYSTEMS: [(&str, &str); 7] = [
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ];
+
+    let find = |names: &[(&str, &'static str)]| {
+        names
+            .iter()
+            .find(|(needle, _)| user_agent.contains(needle))
+            .map(|(_, name)| *name)
+    };
+    match (find(&BROWSERS), find(&SYSTEMS)) {
+        (Some(browser), Some(system)) => format!("{browser} on {system}"),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(system)) => system.to_string(),
+        (None, None) => "Unknown device".to_string(),
+    }
+}
@@ -0,0 +1,11 @@
+//! A throwaway database for tests.
+
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{Database, DbConn};
+
+/// A migrated in-memory SQLite database, gone once the connection is dropped.
+pub(crate) async fn test_db() -> DbConn {
+    let db = Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    db
+}
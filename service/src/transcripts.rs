@@ -0,0 +1,515 @@
+//! Episode transcripts: parsing SRT and WebVTT uploads and rendering them back out as
+//! SRT, WebVTT, Podcasting 2.0 JSON or plain text.
+//!
+//! Uploads are parsed into a list of [`Cue`]s, which is what gets stored. Every output
+//! format is rendered from those cues on request.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TranscriptError {
+    #[error("the transcript contains no cues")]
+    Empty,
+    #[error("a WebVTT file has to start with a WEBVTT line")]
+    MissingHeader,
+    #[error("line {0}: expected a timing line like 00:00:01,000 --> 00:00:04,000")]
+    InvalidTiming(usize),
+    #[error("line {0}: the cue ends before it starts")]
+    EndBeforeStart(usize),
+    #[error("only SRT and WebVTT files can be uploaded")]
+    UnsupportedFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Srt,
+    Vtt,
+    Json,
+    Text,
+}
+
+impl TranscriptFormat {
+    pub const ALL: [TranscriptFormat; 4] = [Self::Vtt, Self::Srt, Self::Json, Self::Text];
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "json" => Some(Self::Json),
+            "txt" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+            Self::Text => "txt",
+        }
+    }
+
+    /// The type used for `podcast:transcript` and when serving the file.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Srt => "application/x-subrip",
+            Self::Vtt => "text/vtt",
+            Self::Json => "application/json",
+            Self::Text => "text/plain",
+        }
+    }
+}
+
+/// One timed piece of the transcript.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cue {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Parses an uploaded transcript, telling SRT and WebVTT apart by the `WEBVTT` header
+/// when the file name does not settle it. Returns the format it was read as.
+pub fn parse_upload(
+    file_name: &str,
+    content: &str,
+) -> Result<(TranscriptFormat, Vec<Cue>), TranscriptError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let extension = file_name.rsplit_once('.').map(|(_, extension)| extension).unwrap_or_default();
+    let format = match TranscriptFormat::from_extension(extension) {
+        Some(format @ (TranscriptFormat::Srt | TranscriptFormat::Vtt)) => format,
+        Some(_) => return Err(TranscriptError::UnsupportedFormat),
+        None if content.starts_with("WEBVTT") => TranscriptFormat::Vtt,
+        None => TranscriptFormat::Srt,
+    };
+    let cues = match format {
+        TranscriptFormat::Vtt => parse_vtt(content)?,
+        _ => parse_srt(content)?,
+    };
+    Ok((format, cues))
+}
+
+pub fn parse_srt(content: &str) -> Result<Vec<Cue>, TranscriptError> {
+    let mut cues = Vec::new();
+    for block in blocks(content) {
+        let mut lines = block.lines.iter().copied();
+        let mut line_number = block.first_line;
+        let mut timing = lines.next().unwrap_or_default();
+        // The sequence number is optional in practice, whatever the format says.
+        if !timing.contains("-->") {
+            timing = lines.next().unwrap_or_default();
+            line_number += 1;
+        }
+        let (start_ms, end_ms) = parse_timing(timing, line_number)?;
+        let text: Vec<&str> = lines.collect();
+        push_cue(&mut cues, start_ms, end_ms, None, &text.join("\n"));
+    }
+    normalize(cues)
+}
+
+pub fn parse_vtt(content: &str) -> Result<Vec<Cue>, TranscriptError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let header = content.lines().next().unwrap_or_default();
+    if header != "WEBVTT" && !header.starts_with("WEBVTT ") && !header.starts_with("WEBVTT\t") {
+        return Err(TranscriptError::MissingHeader);
+    }
+
+    let mut cues = Vec::new();
+    // The first block is the header and whatever metadata follows it.
+    for block in blocks(content).skip(1) {
+        let first = block.lines[0];
+        if first.starts_with("NOTE") || first == "STYLE" || first == "REGION" {
+            continue;
+        }
+        let mut lines = block.lines.iter().copied();
+        let mut line_number = block.first_line;
+        let mut timing = lines.next().unwrap_or_default();
+        if !timing.contains("-->") {
+            timing = lines.next().unwrap_or_default();
+            line_number += 1;
+        }
+        let (start_ms, end_ms) = parse_timing(timing, line_number)?;
+        let text: Vec<&str> = lines.collect();
+        let text = text.join("\n");
+        let (speaker, text) = split_voice(&text);
+        push_cue(&mut cues, start_ms, end_ms, speaker, text);
+    }
+    normalize(cues)
+}
+
+struct Block<'a> {
+    /// One based, for error messages.
+    first_line: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Splits content into runs of non-blank lines.
+fn blocks(content: &str) -> impl Iterator<Item = Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            blocks.extend(current.take());
+        } else {
+            current
+                .get_or_insert_with(|| Block {
+                    first_line: i + 1,
+                    lines: Vec::new(),
+                })
+                .lines
+                .push(line);
+        }
+    }
+    blocks.extend(current);
+    blocks.into_iter()
+}
+
+fn parse_timing(line: &str, line_number: usize) -> Result<(i64, i64), TranscriptError> {
+    let (start, rest) = line
+        .split_once("-->")
+        .ok_or(TranscriptError::InvalidTiming(line_number))?;
+    // WebVTT allows cue settings after the end time.
+    let end = rest.split_whitespace().next().unwrap_or_default();
+    let start_ms = parse_cue_time(start.trim()).ok_or(TranscriptError::InvalidTiming(line_number))?;
+    let end_ms = parse_cue_time(end).ok_or(TranscriptError::InvalidTiming(line_number))?;
+    if end_ms < start_ms {
+        return Err(TranscriptError::EndBeforeStart(line_number));
+    }
+    Ok((start_ms, end_ms))
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm` or `MM:SS.mmm`.
+fn parse_cue_time(text: &str) -> Option<i64> {
+    let (clock, millis) = text.split_once([',', '.'])?;
+    if millis.len() != 3 || !millis.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let fields: Vec<&str> = clock.split(':').collect();
+    let (hours, minutes, seconds) = match fields[..] {
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        [minutes, seconds] => ("0", minutes, seconds),
+        _ => return None,
+    };
+    let number = |field: &str| -> Option<i64> {
+        if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        field.parse().ok()
+    };
+    let (hours, minutes, seconds) = (number(hours)?, number(minutes)?, number(seconds)?);
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + number(millis)?)
+}
+
+/// Pulls the speaker out of a leading WebVTT voice span, `<v Speaker>text`.
+fn split_voice(text: &str) -> (Option<String>, &str) {
+    let Some(rest) = text.strip_prefix("<v") else {
+        return (None, text);
+    };
+    let Some((name, text)) = rest.split_once('>') else {
+        return (None, text);
+    };
+    // Voice spans may carry classes, `<v.loud Speaker>`.
+    let name = name.split_once(char::is_whitespace).map(|(_, name)| name).unwrap_or_default();
+    let name = decode_entities(name.trim());
+    ((!name.is_empty()).then_some(name), text)
+}
+
+fn push_cue(cues: &mut Vec<Cue>, start_ms: i64, end_ms: i64, speaker: Option<String>, text: &str) {
+    let text = clean_text(text);
+    if !text.is_empty() {
+        cues.push(Cue {
+            start_ms,
+            end_ms,
+            speaker,
+            text,
+        });
+    }
+}
+
+/// Strips markup such as `<i>` or `<c.yellow>` and collapses the cue onto one line.
+fn clean_text(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    let stripped = decode_entities(&stripped);
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+fn normalize(mut cues: Vec<Cue>) -> Result<Vec<Cue>, TranscriptError> {
+    if cues.is_empty() {
+        return Err(TranscriptError::Empty);
+    }
+    cues.sort_by_key(|cue| (cue.start_ms, cue.end_ms));
+    Ok(cues)
+}
+
+/// Formats milliseconds as `HH:MM:SS` followed by `separator` and the milliseconds.
+fn format_cue_time(ms: i64, separator: char) -> String {
+    let ms = ms.max(0);
+    let seconds = ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60,
+        ms % 1000
+    )
+}
+
+pub fn render(cues: &[Cue], format: TranscriptFormat) -> String {
+    match format {
+        TranscriptFormat::Srt => render_srt(cues),
+        TranscriptFormat::Vtt => render_vtt(cues),
+        TranscriptFormat::Json => render_json(cues),
+        TranscriptFormat::Text => render_text(cues),
+    }
+}
+
+pub fn render_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{}\n{} --> {}",
+            i + 1,
+            format_cue_time(cue.start_ms, ','),
+            format_cue_time(cue.end_ms, ',')
+        );
+        match &cue.speaker {
+            Some(speaker) => {
+                let _ = writeln!(out, "{speaker}: {}\n", cue.text);
+            }
+            None => {
+                let _ = writeln!(out, "{}\n", cue.text);
+            }
+        }
+    }
+    out
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn render_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let _ = writeln!(
+            out,
+            "{} --> {}",
+            format_cue_time(cue.start_ms, '.'),
+            format_cue_time(cue.end_ms, '.')
+        );
+        match &cue.speaker {
+            Some(speaker) => {
+                let _ = writeln!(out, "<v {}>{}\n", escape_vtt(speaker), escape_vtt(&cue.text));
+            }
+            None => {
+                let _ = writeln!(out, "{}\n", escape_vtt(&cue.text));
+            }
+        }
+    }
+    out
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSegment<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker: Option<&'a str>,
+    start_time: f64,
+    end_time: f64,
+    body: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonTranscript<'a> {
+    version: &'static str,
+    segments: Vec<JsonSegment<'a>>,
+}
+
+/// The Podcasting 2.0 JSON transcript format.
+pub fn render_json(cues: &[Cue]) -> String {
+    let transcript = JsonTranscript {
+        version: "1.0.0",
+        segments: cues
+            .iter()
+            .map(|cue| JsonSegment {
+                speaker: cue.speaker.as_deref(),
+                start_time: cue.start_ms as f64 / 1000.0,
+                end_time: cue.end_ms as f64 / 1000.0,
+                body: &cue.text,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&transcript).unwrap_or_default()
+}
+
+/// Plain text, one paragraph per change of speaker.
+pub fn render_text(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    let mut current_speaker: Option<&str> = None;
+    for (i, cue) in cues.iter().enumerate() {
+        let speaker = cue.speaker.as_deref();
+        if i == 0 || speaker != current_speaker {
+            if i > 0 {
+                out.push_str("\n\n");
+            }
+            if let Some(speaker) = speaker {
+                let _ = write!(out, "{speaker}: ");
+            }
+            current_speaker = speaker;
+        } else {
+            out.push(' ');
+        }
+        out.push_str(&cue.text);
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = include_str!("fixtures/interview.srt");
+    const VTT: &str = include_str!("fixtures/interview.vtt");
+
+    fn cue(start_ms: i64, end_ms: i64, speaker: Option<&str>, text: &str) -> Cue {
+        Cue {
+            start_ms,
+            end_ms,
+            speaker: speaker.map(str::to_owned),
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_srt_uploads() {
+        let (format, cues) = parse_upload("interview.srt", SRT).unwrap();
+        assert_eq!(format, TranscriptFormat::Srt);
+        assert_eq!(
+            cues,
+            [
+                cue(500, 3000, None, "Welcome to Crab Talk."),
+                cue(3000, 6250, None, "No sequence number here, and the cue spans two lines."),
+                cue(6250, 7000, None, "Out of order."),
+                cue(62_000, 64_000, None, "Fish & chips"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_vtt_uploads() {
+        let (format, cues) = parse_upload("interview.vtt", VTT).unwrap();
+        assert_eq!(format, TranscriptFormat::Vtt);
+        assert_eq!(
+            cues,
+            [
+                cue(500, 3000, Some("Jane Doe"), "Welcome to Crab Talk."),
+                cue(3000, 6250, Some("Sam"), "Thanks <3 for having me here."),
+                cue(6250, 7000, None, "No speaker on this one."),
+            ]
+        );
+    }
+
+    #[test]
+    fn tells_formats_apart_by_name_then_content() {
+        assert_eq!(parse_upload("episode", VTT).unwrap().0, TranscriptFormat::Vtt);
+        assert_eq!(parse_upload("episode", SRT).unwrap().0, TranscriptFormat::Srt);
+        assert_eq!(parse_upload("episode.VTT", VTT).unwrap().0, TranscriptFormat::Vtt);
+        assert_eq!(parse_upload("episode.vtt", SRT), Err(TranscriptError::MissingHeader));
+        assert_eq!(parse_upload("episode.json", VTT), Err(TranscriptError::UnsupportedFormat));
+        assert_eq!(parse_upload("episode.txt", SRT), Err(TranscriptError::UnsupportedFormat));
+    }
+
+    #[test]
+    fn rejects_malformed_transcripts() {
+        assert_eq!(parse_srt(""), Err(TranscriptError::Empty));
+        assert_eq!(parse_srt("1\n00:00:01,000 --> 00:00:02,000\n<i></i>\n"), Err(TranscriptError::Empty));
+        assert_eq!(parse_vtt("WEBVTT\n\nNOTE nothing else\n"), Err(TranscriptError::Empty));
+        assert_eq!(
+            parse_vtt("WEBVTTX\n\n00:01.000 --> 00:02.000\nHi\n"),
+            Err(TranscriptError::MissingHeader)
+        );
+        assert_eq!(
+            parse_srt("1\n00:00:01,000 --> 00:00:02,000\nHi\n\n2\n00:00:03 --> 00:00:04,000\nThere\n"),
+            Err(TranscriptError::InvalidTiming(6))
+        );
+        assert_eq!(parse_srt("1\nHello there\n"), Err(TranscriptError::InvalidTiming(2)));
+        assert_eq!(
+            parse_srt("00:00:01,000 --> 00:00:02,00\nHi\n"),
+            Err(TranscriptError::InvalidTiming(1))
+        );
+        assert_eq!(
+            parse_srt("00:00:01,000 --> 00:61:02,000\nHi\n"),
+            Err(TranscriptError::InvalidTiming(1))
+        );
+        assert_eq!(
+            parse_vtt("WEBVTT\n\n00:05.000 --> 00:04.000\nBackwards\n"),
+            Err(TranscriptError::EndBeforeStart(3))
+        );
+    }
+
+    #[test]
+    fn renders_every_format() {
+        let (_, cues) = parse_upload("interview.vtt", VTT).unwrap();
+
+        assert_eq!(
+            render(&cues, TranscriptFormat::Srt),
+            "1\n00:00:00,500 --> 00:00:03,000\nJane Doe: Welcome to Crab Talk.\n\n\
+             2\n00:00:03,000 --> 00:00:06,250\nSam: Thanks <3 for having me here.\n\n\
+             3\n00:00:06,250 --> 00:00:07,000\nNo speaker on this one.\n\n"
+        );
+        assert_eq!(
+            render(&cues, TranscriptFormat::Vtt),
+            "WEBVTT\n\n00:00:00.500 --> 00:00:03.000\n<v Jane Doe>Welcome to Crab Talk.\n\n\
+             00:00:03.000 --> 00:00:06.250\n<v Sam>Thanks &lt;3 for having me here.\n\n\
+             00:00:06.250 --> 00:00:07.000\nNo speaker on this one.\n\n"
+        );
+        assert_eq!(
+            render(&cues, TranscriptFormat::Text),
+            "Jane Doe: Welcome to Crab Talk.\n\nSam: Thanks <3 for having me here.\n\n\
+             No speaker on this one.\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&render(&cues, TranscriptFormat::Json)).unwrap();
+        assert_eq!(json["version"], "1.0.0");
+        assert_eq!(json["segments"][0]["speaker"], "Jane Doe");
+        assert_eq!(json["segments"][0]["startTime"], 0.5);
+        assert_eq!(json["segments"][1]["endTime"], 6.25);
+        assert!(json["segments"][2].get("speaker").is_none());
+    }
+
+    #[test]
+    fn rendered_transcripts_parse_back_to_the_same_cues() {
+        for fixture in [("interview.srt", SRT), ("interview.vtt", VTT)] {
+            let (_, cues) = parse_upload(fixture.0, fixture.1).unwrap();
+            assert_eq!(parse_vtt(&render_vtt(&cues)).unwrap(), cues, "{}", fixture.0);
+        }
+    }
+}
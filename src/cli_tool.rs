@@ -1,146 +1,958 @@
-use clap::{App, Arg, SubCommand};
+//! `pod-crab-gen`: scaffolds a model the way the hand-written ones are built. For a name
+//! and a list of fields it writes a migration, an entity, service functions, a handlers
+//! module with the same routes as posts, and templates, and registers each of them where
+//! the existing ones are listed. Nothing is overwritten.
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use service::permissions::Permission;
 use std::fs;
-use std::process::Command;
-
-fn main() {
-    let matches = App::new("My Rust Scaffold Tool")
-        .version("1.0")
-        .author("Your Name <your.email@example.com>")
-        .about("Generates scaffolds for SeaORM and Poem projects")
-        .subcommand(
-            SubCommand::with_name("generate")
-                .about("Generates scaffolds")
-                .arg(
-                    Arg::with_name("scaffold")
-                        .help("The type of scaffold to generate")
-                        .required(true)
-                        .possible_value("scaffold"),
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "pod-crab-gen", about = "Generates code for new pod-crab models", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a table with its entity, service functions, pages and routes.
+    Scaffold {
+        /// Singular name of the model, e.g. guest_star or GuestStar.
+        name: String,
+        /// Columns besides the id, as NAME:TYPE. TYPE is string, text, integer, big_integer,
+        /// double, boolean, date_time or uuid. A trailing ? makes a string or text optional.
+        #[arg(required = true, value_parser = Field::parse)]
+        fields: Vec<Field>,
+        /// Plural used for routes, templates and the handlers module. Defaults to NAME
+        /// with an s.
+        #[arg(long)]
+        plural: Option<String>,
+        /// Permission needed to create, edit and delete.
+        #[arg(long, default_value = "post:edit", value_parser = parse_permission)]
+        permission: Permission,
+        /// The workspace to generate into.
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+        /// List the files that would be written without writing them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Rust keywords, and what the generated handlers import, which cannot be used as names.
+const RESERVED: [&str; 42] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while", "id", "get", "post", "handler", "try",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FieldType {
+    String,
+    Text,
+    Integer,
+    BigInteger,
+    Double,
+    Boolean,
+    DateTime,
+    Uuid,
+}
+
+impl FieldType {
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "string" => Some(FieldType::String),
+            "text" => Some(FieldType::Text),
+            "integer" => Some(FieldType::Integer),
+            "big_integer" => Some(FieldType::BigInteger),
+            "double" => Some(FieldType::Double),
+            "boolean" => Some(FieldType::Boolean),
+            "date_time" => Some(FieldType::DateTime),
+            "uuid" => Some(FieldType::Uuid),
+            _ => None,
+        }
+    }
+
+    fn rust_type(self) -> &'static str {
+        match self {
+            FieldType::String | FieldType::Text => "String",
+            FieldType::Integer => "i32",
+            FieldType::BigInteger => "i64",
+            FieldType::Double => "f64",
+            FieldType::Boolean => "bool",
+            FieldType::DateTime => "DateTimeUtc",
+            FieldType::Uuid => "Uuid",
+        }
+    }
+
+    /// The `sea_orm_migration::schema` function creating the column.
+    fn column_fn(self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Text => "text",
+            FieldType::Integer => "integer",
+            FieldType::BigInteger => "big_integer",
+            FieldType::Double => "double",
+            FieldType::Boolean => "boolean",
+            FieldType::DateTime => "timestamp_with_time_zone",
+            FieldType::Uuid => "uuid",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    name: String,
+    kind: FieldType,
+    optional: bool,
+}
+
+impl Field {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let (name, kind) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("{spec} is not NAME:TYPE"))?;
+        let (kind, optional) = match kind.strip_suffix('?') {
+            Some(kind) => (kind, true),
+            None => (kind, false),
+        };
+        check_identifier(name)?;
+        let kind = FieldType::parse(kind).ok_or_else(|| format!("{kind} is not a field type"))?;
+        // Forms send empty strings for empty inputs, which only strings can hold.
+        if optional && !matches!(kind, FieldType::String | FieldType::Text) {
+            return Err(format!("{name}: only string and text fields can be optional"));
+        }
+        Ok(Field {
+            name: name.to_owned(),
+            kind,
+            optional,
+        })
+    }
+
+    fn rust_type(&self) -> String {
+        if self.optional {
+            format!("Option<{}>", self.kind.rust_type())
+        } else {
+            self.kind.rust_type().to_owned()
+        }
+    }
+
+    fn iden(&self) -> String {
+        pascal_case(&self.name)
+    }
+
+    fn label(&self) -> String {
+        humanize(&self.name)
+    }
+}
+
+/// How the model is called in the places the generated code goes.
+struct Names {
+    /// Table, entity module and template variable, e.g. `guest_star`.
+    singular: String,
+    /// Routes, handlers module and template directory, e.g. `guest_stars`.
+    plural: String,
+    /// Entity and migration identifier, e.g. `GuestStar`.
+    entity: String,
+    /// Headings, e.g. `Guest star`.
+    title: String,
+    titles: String,
+}
+
+impl Names {
+    fn new(name: &str, plural: Option<&str>) -> Result<Self, String> {
+        let singular = snake_case(name);
+        check_identifier(&singular)?;
+        let plural = match plural {
+            Some(plural) => snake_case(plural),
+            None => pluralize(&singular),
+        };
+        check_identifier(&plural)?;
+        if plural == singular {
+            return Err("The plural must differ from the name".to_owned());
+        }
+        Ok(Names {
+            entity: pascal_case(&singular),
+            title: humanize(&singular),
+            titles: humanize(&plural),
+            singular,
+            plural,
+        })
+    }
+}
+
+/// A file to create, or an existing one with generated code spliced in.
+struct Change {
+    path: PathBuf,
+    contents: String,
+    created: bool,
+}
+
+fn parse_permission(permission: &str) -> Result<Permission, String> {
+    Permission::parse(permission).ok_or_else(|| {
+        let known: Vec<&str> = Permission::ALL.iter().map(|permission| permission.as_str()).collect();
+        format!("expected one of {}", known.join(", "))
+    })
+}
+
+fn check_identifier(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|first| first.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !name.ends_with('_')
+        && !name.contains("__");
+    if !valid {
+        return Err(format!("{name} is not a snake_case name"));
+    }
+    if RESERVED.contains(&name) {
+        return Err(format!("{name} cannot be used as a name"));
+    }
+    Ok(())
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else if c == '-' {
+            snake.push('_');
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn humanize(name: &str) -> String {
+    let words = name.replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => words,
+    }
+}
+
+fn pluralize(singular: &str) -> String {
+    if let Some(stem) = singular.strip_suffix('y') {
+        if !stem.ends_with(['a', 'e', 'i', 'o', 'u']) {
+            return format!("{stem}ies");
+        }
+    }
+    if singular.ends_with(['s', 'x', 'z']) || singular.ends_with("ch") || singular.ends_with("sh") {
+        return format!("{singular}es");
+    }
+    format!("{singular}s")
+}
+
+/// Whether `word` appears in `code` as a whole identifier.
+fn mentions(code: &str, word: &str) -> bool {
+    code.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .any(|token| token == word)
+}
+
+/// Fills the `@name@` placeholders of a template.
+fn fill(template: &str, names: &Names, extra: &[(&str, &str)]) -> String {
+    let mut filled = template
+        .replace("@singular@", &names.singular)
+        .replace("@plural@", &names.plural)
+        .replace("@Entity@", &names.entity)
+        .replace("@Title@", &names.title)
+        .replace("@Titles@", &names.titles)
+        .replace("@title@", &names.singular.replace('_', " "));
+    for (placeholder, value) in extra {
+        filled = filled.replace(placeholder, value);
+    }
+    filled
+}
+
+fn read(root: &Path, path: &str) -> Result<String, String> {
+    fs::read_to_string(root.join(path)).map_err(|err| format!("Cannot read {path}: {err}"))
+}
+
+/// Inserts `line` after the last line starting with `prefix`.
+fn insert_after_last(contents: &str, prefix: &str, line: &str) -> Option<String> {
+    let mut lines: Vec<&str> = contents.lines().collect();
+    let last = lines.iter().rposition(|existing| existing.trim_start().starts_with(prefix))?;
+    lines.insert(last + 1, line);
+    Some(lines.join("\n") + "\n")
+}
+
+/// Inserts `line` among the lines starting with `prefix`, keeping them sorted.
+fn insert_sorted(contents: &str, prefix: &str, line: &str, skip: &str) -> Option<String> {
+    let mut lines: Vec<&str> = contents.lines().collect();
+    let listed: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].starts_with(prefix) && lines[i] != skip)
+        .collect();
+    let position = listed
+        .iter()
+        .find(|&&i| lines[i] > line)
+        .copied()
+        .or_else(|| listed.last().map(|last| last + 1))?;
+    lines.insert(position, line);
+    Some(lines.join("\n") + "\n")
+}
+
+/// Inserts `code` at the end of the block opened by the line `opening`, whose closing
+/// brace is the first unindented one after it.
+fn insert_into_block(contents: &str, opening: &str, code: &str) -> Option<String> {
+    let start = contents.find(&format!("\n{opening}\n"))?;
+    let end = start + 1 + contents[start + 1..].find("\n}")?;
+    Some(format!("{}\n{}{}", &contents[..end], code.trim_end(), &contents[end..]))
+}
+
+/// `m<today>_<n>`, numbered after the migrations already written today.
+fn migration_name(migrations: &str, singular: &str) -> String {
+    let date = Utc::now().format("%Y%m%d").to_string();
+    let prefix = format!("mod m{date}_");
+    let sequence = migrations
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix))
+        .filter_map(|rest| rest.get(..6)?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    format!("m{date}_{sequence:06}_create_{singular}_table")
+}
+
+fn migration(names: &Names, fields: &[Field]) -> String {
+    let columns: String = fields
+        .iter()
+        .map(|field| {
+            let null = if field.optional { "_null" } else { "" };
+            format!(
+                "                    .col({}{null}({}::{}))\n",
+                field.kind.column_fn(),
+                names.entity,
+                field.iden()
+            )
+        })
+        .collect();
+    let idens: String = fields.iter().map(|field| format!("    {},\n", field.iden())).collect();
+    fill(MIGRATION, names, &[("@columns@", &columns), ("@idens@", &idens)])
+}
+
+fn entity(names: &Names, fields: &[Field]) -> String {
+    let mut model = String::new();
+    for field in fields {
+        match field.kind {
+            FieldType::Text => model.push_str("    #[sea_orm(column_type = \"Text\")]\n"),
+            FieldType::Double => model.push_str("    #[sea_orm(column_type = \"Double\")]\n"),
+            // Unchecked checkboxes are not sent at all.
+            FieldType::Boolean => model.push_str("    #[serde(default)]\n"),
+            _ => {}
+        }
+        model.push_str(&format!("    pub {}: {},\n", field.name, field.rust_type()));
+    }
+    // Floats are not `Eq`.
+    let eq = if fields.iter().any(|field| field.kind == FieldType::Double) { "" } else { ", Eq" };
+    fill(ENTITY, names, &[("@fields@", &model), ("@eq@", eq)])
+}
+
+fn query_functions(names: &Names, fields: &[Field]) -> String {
+    fill(QUERY, names, &[("@OrderBy@", &fields[0].iden())])
+}
+
+fn mutation_functions(names: &Names, fields: &[Field]) -> String {
+    let sets: String = fields
+        .iter()
+        .map(|field| format!("            {0}: Set(form_data.{0}),\n", field.name))
+        .collect();
+    fill(MUTATION, names, &[("@sets@", &sets)])
+}
+
+fn handlers(names: &Names, permission: Permission) -> String {
+    fill(HANDLERS, names, &[("@Permission@", &format!("{permission:?}"))])
+}
+
+/// Form inputs for the fields, empty or filled from the `@singular@` being edited.
+fn inputs(names: &Names, fields: &[Field], editing: bool) -> String {
+    let mut html = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        let name = &field.name;
+        let label = field.label();
+        let value = if editing {
+            format!("{{{{ {}.{name} }}}}", names.singular)
+        } else {
+            String::new()
+        };
+        let mut attributes = String::new();
+        if i == 0 {
+            attributes.push_str("\n                autofocus");
+        }
+        if !field.optional {
+            attributes.push_str("\n                required");
+        }
+        let input = match field.kind {
+            FieldType::Text => format!(
+                "        <textarea\n                placeholder=\"{label}\"\n                name=\"{name}\"\n                id=\"{name}\"{attributes}\n                class=\"textarea textarea-bordered w-full\"\n                rows=\"5\"\n        >{value}</textarea>\n"
+            ),
+            FieldType::Boolean => {
+                let checked = if editing {
+                    format!(" {{% if {}.{name} %}}checked{{% endif %}}", names.singular)
+                } else {
+                    String::new()
+                };
+                format!(
+                    "        <label class=\"label cursor-pointer justify-start gap-2\">\n          <input type=\"checkbox\" name=\"{name}\" id=\"{name}\" value=\"true\" class=\"checkbox\"{checked}/>\n          <span>{label}</span>\n        </label>\n"
+                )
+            }
+            kind => {
+                let (kind, placeholder) = match kind {
+                    FieldType::Integer | FieldType::BigInteger => ("number\"\n                step=\"1", label),
+                    FieldType::Double => ("number\"\n                step=\"any", label),
+                    FieldType::DateTime => ("text", format!("{label}, e.g. 2026-10-18T12:00:00Z")),
+                    _ => ("text", label),
+                };
+                format!(
+                    "        <input\n                type=\"{kind}\"\n                placeholder=\"{placeholder}\"\n                name=\"{name}\"\n                id=\"{name}\"\n                value=\"{value}\"{attributes}\n                class=\"input input-bordered w-full\"\n        />\n"
                 )
-                .arg(
-                    Arg::with_name("name")
-                        .help("The name of the model to generate")
-                        .required(true),
-                ),
+            }
+        };
+        html.push_str(&input);
+    }
+    html
+}
+
+fn templates(names: &Names, fields: &[Field], permission: Permission) -> Vec<(String, String)> {
+    let headers: String = fields
+        .iter()
+        .map(|field| format!("        <th>{}</th>\n", field.label()))
+        .collect();
+    let cells: String = fields
+        .iter()
+        .map(|field| format!("    <td>{{{{ {}.{} }}}}</td>\n", names.singular, field.name))
+        .collect();
+    let dir = format!("frontend/templates/{}", names.plural);
+    vec![
+        (
+            format!("{dir}/list.html.tera"),
+            fill(LIST_TEMPLATE, names, &[("@headers@", &headers), ("@permission@", permission.as_str())]),
+        ),
+        (
+            format!("{dir}/{}_row.html.tera", names.singular),
+            fill(ROW_TEMPLATE, names, &[("@cells@", &cells)]),
+        ),
+        (
+            format!("{dir}/new.html.tera"),
+            fill(NEW_TEMPLATE, names, &[("@inputs@", &inputs(names, fields, false))]),
+        ),
+        (
+            format!("{dir}/edit.html.tera"),
+            fill(EDIT_TEMPLATE, names, &[("@inputs@", &inputs(names, fields, true))]),
+        ),
+    ]
+}
+
+/// Everything the scaffold writes, checked against the workspace before anything is.
+fn plan(root: &Path, names: &Names, fields: &[Field], permission: Permission) -> Result<Vec<Change>, String> {
+    let missing = |path: &str| format!("Cannot find where to register the scaffold in {path}");
+    let mut names_seen = Vec::new();
+    for field in fields {
+        if names_seen.contains(&&field.name) {
+            return Err(format!("{} is listed twice", field.name));
+        }
+        names_seen.push(&field.name);
+    }
+
+    let mut changes = Vec::new();
+    let mut create = |path: String, contents: String| -> Result<(), String> {
+        if root.join(&path).exists() {
+            return Err(format!("{path} already exists"));
+        }
+        changes.push(Change {
+            path: PathBuf::from(path),
+            contents,
+            created: true,
+        });
+        Ok(())
+    };
+
+    let migrations = read(root, "migration/src/lib.rs")?;
+    let migration_name = migration_name(&migrations, &names.singular);
+    create(format!("migration/src/{migration_name}.rs"), migration(names, fields))?;
+    create(format!("entities/src/{}.rs", names.singular), entity(names, fields))?;
+    create(format!("api/src/handlers/{}/mod.rs", names.plural), handlers(names, permission))?;
+    for (path, contents) in templates(names, fields, permission) {
+        create(path, contents)?;
+    }
+
+    let mut update = |path: &str, contents: Option<String>| -> Result<(), String> {
+        changes.push(Change {
+            path: PathBuf::from(path),
+            contents: contents.ok_or_else(|| missing(path))?,
+            created: false,
+        });
+        Ok(())
+    };
+
+    let migrations = insert_after_last(&migrations, "mod m", &format!("mod {migration_name};"));
+    let migrations = migrations.and_then(|migrations| {
+        insert_after_last(
+            &migrations,
+            "Box::new(m",
+            &format!("            Box::new({migration_name}::Migration),"),
         )
-        .get_matches();
+    });
+    update("migration/src/lib.rs", migrations)?;
 
-    if let Some(matches) = matches.subcommand_matches("generate") {
-        let name = matches.value_of("name").unwrap();
+    let entities = read(root, "entities/src/lib.rs")?;
+    let line = format!("pub mod {};", names.singular);
+    update("entities/src/lib.rs", insert_sorted(&entities, "pub mod ", &line, "pub mod prelude;"))?;
+    let prelude = read(root, "entities/src/prelude.rs")?;
+    let line = format!("pub use super::{}::Entity as {};", names.singular, names.entity);
+    update("entities/src/prelude.rs", insert_sorted(&prelude, "pub use ", &line, ""))?;
 
-        // Call functions to generate the scaffold
-        generate_migration(name);
-        generate_entity(name);
-        generate_crud_handlers(name);
-        generate_views(name);
+    let import = fill("use entities::{@singular@, @singular@::Entity as @Entity@};", names, &[]);
+    for (path, opening, functions) in [
+        ("service/src/query.rs", "impl Query {", query_functions(names, fields)),
+        ("service/src/mutation.rs", "impl Mutation {", mutation_functions(names, fields)),
+    ] {
+        let service = read(root, path)?;
+        if mentions(&service, &names.entity) {
+            return Err(format!("{} is already used in {path}", names.entity));
+        }
+        let service = insert_after_last(&service, "use entities::", &import)
+            .and_then(|service| insert_into_block(&service, opening, &functions));
+        update(path, service)?;
     }
+
+    let modules = read(root, "api/src/handlers/mod.rs")?;
+    let line = format!("pub(crate) mod {};", names.plural);
+    update("api/src/handlers/mod.rs", insert_after_last(&modules, "pub(crate) mod ", &line))?;
+    let lib = read(root, "api/src/lib.rs")?;
+    update("api/src/lib.rs", register_routes(&lib, names))?;
+
+    Ok(changes)
 }
 
-fn generate_migration(name: &str) {
-    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
-    let migration_file = format!("migrations/{}_create_{}.rs", timestamp, name);
+/// Adds the handlers module to the `use crate::handlers::{...}` list of the api crate and
+/// nests its routes after the others.
+fn register_routes(lib: &str, names: &Names) -> Option<String> {
+    const IMPORT: &str = "use crate::handlers::{";
+    let start = lib.find(IMPORT)? + IMPORT.len();
+    let end = start + lib[start..].find('}')?;
+    let mut modules: Vec<&str> = lib[start..end].split(',').map(str::trim).filter(|m| !m.is_empty()).collect();
+    if modules.contains(&names.plural.as_str()) {
+        return None;
+    }
+    modules.push(&names.plural);
+    modules.sort_unstable();
+    let lib = format!("{}{}{}", &lib[..start], modules.join(", "), &lib[end..]);
 
-    // Example content for migration file
-    let content = format!(
-        r#"
-        use sea_orm_migration::prelude::*;
+    let mut lines: Vec<&str> = lib.lines().collect();
+    let last = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with(".nest(\"/") && line.ends_with("_routes())"))?;
+    let indent = &lines[last][..lines[last].len() - lines[last].trim_start().len()];
+    let nest = format!("{indent}.nest(\"/{0}\", {0}::{1}_routes())", names.plural, names.singular);
+    lines.insert(last + 1, &nest);
+    Some(lines.join("\n") + "\n")
+}
 
-        #[derive(DeriveMigrationName)]
-        pub struct Migration;
+fn scaffold(
+    root: &Path,
+    name: &str,
+    plural: Option<&str>,
+    fields: &[Field],
+    permission: Permission,
+    dry_run: bool,
+) -> Result<(), String> {
+    if !root.join("migration/src/lib.rs").is_file() {
+        return Err(format!("{} is not the pod-crab workspace", root.display()));
+    }
+    let names = Names::new(name, plural)?;
+    let changes = plan(root, &names, fields, permission)?;
 
-        #[async_trait::async_trait]
-        impl MigrationTrait for Migration {{
-            async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {{
-                manager.create_table(
-                    Table::create()
-                        .table({0})
-                        .if_not_exists()
-                        .col(ColumnDef::new(Alias::new("id")).integer().not_null().auto_increment().primary_key())
-                        .to_owned(),
-                )
-                .await
-            }}
-
-            async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {{
-                manager.drop_table(Table::drop().table({0}).to_owned()).await
-            }}
-        }}
-        "#,
-        name
-    );
-
-    fs::write(migration_file, content).expect("Failed to write migration file");
-    println!("Created migration for {}", name);
-}
-
-fn generate_entity(name: &str) {
-    Command::new("sea-orm-cli")
-        .arg("generate")
-        .arg("entity")
-        .arg("-o")
-        .arg(format!("src/entities/{}", name))
-        .status()
-        .expect("Failed to generate entity");
-    println!("Generated entity for {}", name);
-}
-
-fn generate_crud_handlers(name: &str) {
-    let handlers_file = format!("src/handlers/{}_handlers.rs", name);
-    let content = format!(
-        r#"
-        use poem::web::Json;
-
-        pub async fn list() -> Json<&'static str> {{
-            Json("List of {0}s")
-        }}
-
-        pub async fn create() -> Json<&'static str> {{
-            Json("Create a {0}")
-        }}
-
-        pub async fn update() -> Json<&'static str> {{
-            Json("Update a {0}")
-        }}
-
-        pub async fn delete() -> Json<&'static str> {{
-            Json("Delete a {0}")
-        }}
-        "#,
-        name
-    );
-
-    fs::write(handlers_file, content).expect("Failed to write CRUD handlers");
-    println!("Created CRUD handlers for {}", name);
-}
-
-fn generate_views(name: &str) {
-    let views_dir = format!("templates/{}", name);
-    fs::create_dir_all(&views_dir).expect("Failed to create views directory");
-
-    let index_file = format!("{}/index.tera", views_dir);
-    let index_content = format!(
-        r#"
-        <h1>List of {0}s</h1>
-        <table>
-            <thead>
-                <tr>
-                    <th>ID</th>
-                    <th>Name</th>
-                </tr>
-            </thead>
-            <tbody>
-                {{% for {0} in {0}s %}}
-                <tr>
-                    <td>{{{{ {0}.id }}}}</td>
-                    <td>{{{{ {0}.name }}}}</td>
-                </tr>
-                {{% endfor %}}
-            </tbody>
-        </table>
-        "#,
-        name
-    );
-
-    fs::write(index_file, index_content).expect("Failed to write index view");
-    println!("Created Tera views for {}", name);
+    for change in &changes {
+        let verb = match (dry_run, change.created) {
+            (true, true) => "Would create",
+            (true, false) => "Would update",
+            (false, true) => "Created",
+            (false, false) => "Updated",
+        };
+        if !dry_run {
+            let path = root.join(&change.path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|err| format!("Cannot create {}: {err}", dir.display()))?;
+            }
+            fs::write(&path, &change.contents).map_err(|err| format!("Cannot write {}: {err}", path.display()))?;
+        }
+        println!("{verb} {}", change.path.display());
+    }
+    if !dry_run {
+        println!(
+            "\nRun `cargo run -- migrate up`, or start the server, to create the {} table. Link /{} from a page such as frontend/templates/index.html.tera.",
+            names.singular, names.plural
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Scaffold {
+            name,
+            fields,
+            plural,
+            permission,
+            root,
+            dry_run,
+        } => scaffold(&root, &name, plural.as_deref(), &fields, permission, dry_run),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+const MIGRATION: &str = r#"use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(@Entity@::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(@Entity@::Id))
+@columns@                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(@Entity@::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum @Entity@ {
+    Table,
+    Id,
+@idens@}
+"#;
+
+const ENTITY: &str = r#"//! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel@eq@, Serialize, Deserialize)]
+#[sea_orm(table_name = "@singular@")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+@fields@}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+"#;
+
+const QUERY: &str = r#"
+    pub async fn find_@singular@_by_id(db: &DbConn, id: Uuid) -> Result<Option<@singular@::Model>, DbErr> {
+        @Entity@::find_by_id(id).one(db).await
+    }
+
+    /// If ok, returns (@singular@ models, num pages).
+    pub async fn find_@plural@_in_page(
+        db: &DbConn,
+        page: u64,
+        @plural@_per_page: u64,
+    ) -> Result<(Vec<@singular@::Model>, u64), DbErr> {
+        let paginator = @Entity@::find()
+            .order_by_asc(@singular@::Column::@OrderBy@)
+            .paginate(db, @plural@_per_page);
+        let num_pages = paginator.num_pages().await?;
+
+        paginator.fetch_page(page - 1).await.map(|p| (p, num_pages))
+    }
+"#;
+
+const MUTATION: &str = r#"
+    pub async fn create_@singular@(
+        db: &DbConn,
+        form_data: @singular@::Model,
+    ) -> Result<@singular@::Model, DbErr> {
+        @singular@::ActiveModel {
+            id: Set(Uuid::new_v4()),
+@sets@        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn update_@singular@_by_id(
+        db: &DbConn,
+        id: Uuid,
+        form_data: @singular@::Model,
+    ) -> Result<@singular@::Model, DbErr> {
+        let @singular@: @singular@::ActiveModel = @Entity@::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find @title@.".to_owned()))
+            .map(Into::into)?;
+
+        @singular@::ActiveModel {
+            id: @singular@.id,
+@sets@        }
+        .update(db)
+        .await
+    }
+
+    pub async fn delete_@singular@(db: &DbConn, id: Uuid) -> Result<DeleteResult, DbErr> {
+        let @singular@: @singular@::ActiveModel = @Entity@::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find @title@.".to_owned()))
+            .map(Into::into)?;
+
+        @singular@.delete(db).await
+    }
+"#;
+
+const HANDLERS: &str = r#"use crate::handlers::auth::login_required_middleware::login_required_middleware;
+use crate::handlers::auth::permissions::session_permissions;
+use crate::handlers::auth::require_permission_middleware::RequirePermission;
+use crate::{AppState, PaginationParams, DEFAULT_ITEMS_PER_PAGE};
+use entities::@singular@;
+use poem::error::InternalServerError;
+use poem::http::StatusCode;
+use poem::session::Session;
+use poem::web::{Data, Form, Html, Path, Query};
+use poem::{get, handler, post, EndpointExt, Error, IntoResponse, Route};
+use sea_orm::prelude::Uuid;
+use service::permissions::Permission;
+use service::{Mutation as MutationCore, Query as QueryCore};
+
+#[handler]
+pub async fn create(state: Data<&AppState>, form: Form<@singular@::Model>) -> poem::Result<impl IntoResponse> {
+    let form = form.0;
+    let conn = &state.conn;
+
+    MutationCore::create_@singular@(conn, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/@plural@"))
+}
+
+#[handler]
+pub async fn list(
+    state: Data<&AppState>,
+    session: &Session,
+    Query(params): Query<PaginationParams>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let page = params.page.unwrap_or(1).max(1);
+    let items_per_page = params.items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+
+    let (@plural@, num_pages) = QueryCore::find_@plural@_in_page(conn, page, items_per_page)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("@plural@", &@plural@);
+    ctx.insert("page", &page);
+    ctx.insert("items_per_page", &items_per_page);
+    ctx.insert("num_pages", &num_pages);
+    ctx.insert("permissions", &session_permissions(&state, session).await?);
+
+    let body = state
+        .templates
+        .render("@plural@/list.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn new(state: Data<&AppState>) -> poem::Result<impl IntoResponse> {
+    let ctx = tera::Context::new();
+    let body = state
+        .templates
+        .render("@plural@/new.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn edit(state: Data<&AppState>, Path(id): Path<Uuid>) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    let @singular@ = QueryCore::find_@singular@_by_id(conn, id)
+        .await
+        .map_err(InternalServerError)?
+        .ok_or_else(|| Error::from_status(StatusCode::NOT_FOUND))?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("@singular@", &@singular@);
+
+    let body = state
+        .templates
+        .render("@plural@/edit.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn update(
+    state: Data<&AppState>,
+    Path(id): Path<Uuid>,
+    form: Form<@singular@::Model>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+    let form = form.0;
+
+    let @singular@ = MutationCore::update_@singular@_by_id(conn, id, form)
+        .await
+        .map_err(InternalServerError)?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("@singular@", &@singular@);
+
+    let body = state
+        .templates
+        .render("@plural@/@singular@_row.html.tera", &ctx)
+        .map_err(InternalServerError)?;
+    Ok(Html(body))
+}
+
+#[handler]
+pub async fn destroy(
+    state: Data<&AppState>,
+    Path(id): Path<Uuid>,
+) -> poem::Result<impl IntoResponse> {
+    let conn = &state.conn;
+
+    MutationCore::delete_@singular@(conn, id)
+        .await
+        .map_err(InternalServerError)?;
+
+    Ok(StatusCode::ACCEPTED.with_header("HX-Redirect", "/@plural@"))
+}
+
+pub fn @singular@_routes() -> Route {
+    Route::new()
+        .at("/", get(list).around(login_required_middleware))
+        .at(
+            "/create",
+            post(create).with(RequirePermission::new(Permission::@Permission@)),
+        )
+        .at(
+            "/new",
+            get(new).with(RequirePermission::new(Permission::@Permission@)),
+        )
+        .at(
+            "/:id",
+            get(edit)
+                .patch(update)
+                .delete(destroy)
+                .with(RequirePermission::new(Permission::@Permission@)),
+        )
 }
+"#;
+
+const LIST_TEMPLATE: &str = r#"{% extends "layout.html.tera" %}
+{% block content %}
+  <div class="max-w-screen-lg mx-auto px-4 sm:px-6 lg:px-8 py-6">
+    <h1 class="text-3xl font-bold mb-4">@Titles@</h1>
+    <table class="table table-zebra w-full">
+      <thead>
+      <tr>
+@headers@      </tr>
+      </thead>
+      <tbody id="@singular@-list">
+      {% for @singular@ in @plural@ %}
+        {% include "@plural@/@singular@_row.html.tera" %}
+      {% endfor %}
+      </tbody>
+    </table>
+    <div class="flex justify-center gap-2 mt-4">
+      {% if page <= 1 %}
+        <span class="btn btn-disabled">Previous</span>
+      {% else %}
+        <a href="/@plural@?page={{ page - 1 }}&items_per_page={{ items_per_page }}" class="btn btn-secondary">Previous</a>
+      {% endif %}
+      {% if page >= num_pages %}
+        <span class="btn btn-disabled">Next</span>
+      {% else %}
+        <a href="/@plural@?page={{ page + 1 }}&items_per_page={{ items_per_page }}" class="btn btn-secondary">Next</a>
+      {% endif %}
+    </div>
+    {% if permissions is granted("@permission@") %}
+      <div class="mt-6">
+        <a href="/@plural@/new" class="btn btn-primary">Add @Title@</a>
+      </div>
+    {% endif %}
+  </div>
+{% endblock content %}
+"#;
+
+const ROW_TEMPLATE: &str = r#"<tr class="cursor-pointer" hx-get="/@plural@/{{ @singular@.id }}">
+@cells@</tr>
+"#;
+
+const NEW_TEMPLATE: &str = r#"{% extends "layout.html.tera" %}
+{% block content %}
+  <div class="max-w-screen-md mx-auto px-4 sm:px-6 lg:px-8 py-6" hx-boost="true">
+    <h4 class="text-2xl font-semibold mb-4">New @Title@</h4>
+    <form class="space-y-4" action="/@plural@/create" method="post">
+      <div class="space-y-4">
+@inputs@      </div>
+      <div class="flex justify-between items-center mt-4">
+        <a href="/@plural@" class="btn btn-secondary">Cancel</a>
+        <input type="submit" value="Save @Title@" class="btn btn-primary" />
+      </div>
+    </form>
+  </div>
+{% endblock content %}
+"#;
 
+const EDIT_TEMPLATE: &str = r#"  <div class="flex flex-col items-center p-6">
+    <h4 class="text-2xl font-semibold mb-4">Edit @Title@</h4>
+    <div class="w-full max-w-3xl">
+      <form class="space-y-4">
+        <div class="space-y-4">
+@inputs@        </div>
+        <div class="flex justify-between items-center mt-4">
+          <a href="/@plural@" class="btn btn-secondary">Cancel</a>
+          <button hx-patch="/@plural@/{{ @singular@.id }}" hx-target="closest tr" class="btn btn-primary">Save @Title@</button>
+        </div>
+      </form>
+      <div class="mt-6 text-right">
+        <button class="btn btn-error" hx-delete="/@plural@/{{ @singular@.id }}" hx-confirm="Delete this @title@?">Delete @Title@</button>
+      </div>
+    </div>
+  </div>
+"#;
@@ -0,0 +1,37 @@
+use super::{connect, load_config, CommandResult};
+use api::config::Profile;
+use chrono::Utc;
+use clap::Subcommand;
+use service::accounts::{looks_like_email, normalize_email};
+use service::Mutation;
+
+#[derive(Subcommand)]
+pub enum AdminCommand {
+    /// Make the owner of EMAIL a super admin, creating their account if there is none.
+    Create {
+        email: String,
+        /// Name for a new account. Defaults to the part of the address before the @.
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+pub async fn run(command: AdminCommand, profile: Option<Profile>) -> CommandResult {
+    match command {
+        AdminCommand::Create { email, name } => {
+            let email = normalize_email(&email);
+            if !looks_like_email(&email) {
+                return Err(format!("{email} does not look like an email address").into());
+            }
+
+            let conn = connect(&load_config(profile)?).await?;
+            let (user, created) = Mutation::create_super_admin(&conn, &email, name.as_deref(), Utc::now()).await?;
+            if created {
+                println!("Created super admin {} <{}>", user.name, user.email);
+            } else {
+                println!("Made {} <{}> a super admin", user.name, user.email);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use super::{connect, load_config, read_input, write_output, CommandResult};
+use api::config::Profile;
+use clap::Subcommand;
+use service::accounts::normalize_email;
+use service::exports::{EpisodeExport, ExportedEpisode};
+use service::{Mutation, Query};
+use std::path::PathBuf;
+use sea_orm::prelude::Uuid;
+
+#[derive(Subcommand)]
+pub enum EpisodeCommand {
+    /// Write episodes with their chapters as JSON. Media files are referenced by URL.
+    Export {
+        /// Only the episodes of this show.
+        #[arg(long)]
+        show: Option<Uuid>,
+        /// Write to this file instead of standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Create the episodes in an export. Episodes the show already has, going by their
+    /// GUID, are left alone, so an export can be imported again.
+    Import {
+        /// The export to read, or - for standard input.
+        file: PathBuf,
+        /// Address of the user who will own the episodes.
+        #[arg(long)]
+        user: String,
+        /// Add the episodes to this show.
+        #[arg(long)]
+        show: Uuid,
+    },
+}
+
+pub async fn run(command: EpisodeCommand, profile: Option<Profile>) -> CommandResult {
+    let conn = connect(&load_config(profile)?).await?;
+    match command {
+        EpisodeCommand::Export { show, output } => {
+            if let Some(show) = show {
+                Query::find_show_by_id(&conn, show)
+                    .await?
+                    .ok_or_else(|| format!("There is no show {show}"))?;
+            }
+            let mut episodes = Vec::new();
+            for episode in Query::find_all_episodes(&conn, show).await? {
+                let chapters = Query::find_chapters_by_episode(&conn, episode.id).await?;
+                episodes.push(ExportedEpisode::new(&episode, &chapters));
+            }
+            let count = episodes.len();
+            let json = serde_json::to_string_pretty(&EpisodeExport::new(episodes))?;
+            write_output(output.as_deref(), &json)?;
+            eprintln!("Exported {count} episode(s)");
+        }
+        EpisodeCommand::Import { file, user, show } => {
+            let export = EpisodeExport::from_json(&read_input(&file)?)?;
+            let email = normalize_email(&user);
+            let user = Query::find_user_by_email(&conn, &email)
+                .await?
+                .ok_or_else(|| format!("{email} has no account"))?;
+            Query::find_show_by_id(&conn, show)
+                .await?
+                .ok_or_else(|| format!("There is no show {show}"))?;
+            let mut imported = 0;
+            for exported in &export.episodes {
+                if Mutation::import_episode(&conn, exported, user.id, show).await?.is_some() {
+                    imported += 1;
+                }
+            }
+            println!(
+                "Imported {imported} episode(s), skipped {} the show already has",
+                export.episodes.len() - imported
+            );
+        }
+    }
+    Ok(())
+}
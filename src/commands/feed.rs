@@ -0,0 +1,95 @@
+use super::{connect, load_config, read_input, write_output, CommandResult};
+use api::config::Profile;
+use api::EnclosureDownloader;
+use clap::Subcommand;
+use service::accounts::normalize_email;
+use service::feed_import::{parse_feed, FeedImportPlan};
+use service::{Mutation, Query};
+use std::path::PathBuf;
+use sea_orm::prelude::Uuid;
+
+#[derive(Subcommand)]
+pub enum FeedCommand {
+    /// Write the RSS feed of the site, or of one show, as it would be served.
+    Render {
+        #[arg(long)]
+        show: Option<Uuid>,
+        /// Write to this file instead of standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Create a show and its episodes from the RSS feed of a podcast hosted elsewhere.
+    /// Importing the same feed again updates the episodes that changed.
+    Import {
+        /// The feed to read, or - for standard input.
+        file: PathBuf,
+        /// Address of the user who will own the show and episodes.
+        #[arg(long)]
+        user: String,
+        /// Import into this show instead of creating one.
+        #[arg(long)]
+        show: Option<Uuid>,
+        /// Copy the audio of new episodes into the media store.
+        #[arg(long)]
+        download_media: bool,
+        /// Only list what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+pub async fn run(command: FeedCommand, profile: Option<Profile>) -> CommandResult {
+    let config = load_config(profile)?;
+    let conn = connect(&config).await?;
+    match command {
+        FeedCommand::Render { show, output } => {
+            let feed = match show {
+                Some(show) => api::render_show_feed(&conn, &config, show)
+                    .await?
+                    .ok_or_else(|| format!("There is no show {show}"))?,
+                None => api::render_site_feed(&conn, &config).await?,
+            };
+            write_output(output.as_deref(), &feed)?;
+        }
+        FeedCommand::Import { file, user, show, download_media, dry_run } => {
+            let feed = parse_feed(&read_input(&file)?)?;
+            let email = normalize_email(&user);
+            let user = Query::find_user_by_email(&conn, &email)
+                .await?
+                .ok_or_else(|| format!("{email} has no account"))?;
+            let existing = match show {
+                Some(show) => {
+                    Query::find_show_by_id(&conn, show)
+                        .await?
+                        .ok_or_else(|| format!("There is no show {show}"))?;
+                    Query::find_all_episodes(&conn, Some(show)).await?
+                }
+                None => Vec::new(),
+            };
+            let plan = FeedImportPlan::new(feed, show, &existing);
+            println!("{}", plan.report());
+            if dry_run {
+                return Ok(());
+            }
+
+            let result = Mutation::apply_feed_import(&conn, &plan, user.id, chrono::Utc::now()).await?;
+            println!(
+                "Imported into show {}: created {} and updated {} episode(s)",
+                result.show_id,
+                result.created.len(),
+                result.updated
+            );
+            if download_media {
+                let media = media::from_config(&config.media)?;
+                let downloader = EnclosureDownloader::new(conn.clone(), media, config.imports.max_download_bytes);
+                let downloads = result
+                    .created
+                    .into_iter()
+                    .filter_map(|(id, url)| Some((id, url?)))
+                    .collect();
+                downloader.download_all(downloads).await;
+            }
+        }
+    }
+    Ok(())
+}
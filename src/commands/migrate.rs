@@ -0,0 +1,61 @@
+use super::{load_config, CommandResult};
+use api::config::Profile;
+use clap::Subcommand;
+use migration::{Migrator, MigratorTrait};
+use sea_orm::Database;
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Apply pending migrations.
+    Up {
+        /// Apply only this many.
+        #[arg(long)]
+        steps: Option<u32>,
+    },
+    /// Roll back the most recent migrations.
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// List every migration and whether it has been applied.
+    Status,
+    /// Drop every table and apply all migrations again. This deletes all data.
+    Fresh {
+        /// Confirm that all data may be deleted.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+pub async fn run(command: MigrateCommand, profile: Option<Profile>) -> CommandResult {
+    let config = load_config(profile)?;
+    let conn = Database::connect(&config.database.url).await?;
+
+    match command {
+        MigrateCommand::Up { steps } => {
+            let pending = Migrator::get_pending_migrations(&conn).await?.len();
+            Migrator::up(&conn, steps).await?;
+            let applied = pending - Migrator::get_pending_migrations(&conn).await?.len();
+            println!("Applied {applied} migration(s)");
+        }
+        MigrateCommand::Down { steps } => {
+            let applied = Migrator::get_applied_migrations(&conn).await?.len();
+            Migrator::down(&conn, Some(steps)).await?;
+            let rolled_back = applied - Migrator::get_applied_migrations(&conn).await?.len();
+            println!("Rolled back {rolled_back} migration(s)");
+        }
+        MigrateCommand::Status => {
+            for migration in Migrator::get_migration_with_status(&conn).await? {
+                println!("{:<8} {}", migration.status(), migration.name());
+            }
+        }
+        MigrateCommand::Fresh { yes } => {
+            if !yes {
+                return Err("This deletes all data. Pass --yes if that is what you want.".into());
+            }
+            Migrator::fresh(&conn).await?;
+            println!("Recreated the database schema");
+        }
+    }
+    Ok(())
+}
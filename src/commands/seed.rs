@@ -0,0 +1,114 @@
+use super::{connect, load_config, CommandResult};
+use api::config::Profile;
+use chrono::{Duration, Utc};
+use entities::sea_orm_active_enums::EpisodeStatus;
+use entities::{post, show};
+use sea_orm::prelude::Uuid;
+use service::accounts::{looks_like_email, normalize_email};
+use service::chapters::ImportedChapter;
+use service::exports::ExportedEpisode;
+use service::permissions::SUPER_ADMIN_ROLE;
+use service::{Mutation, Query};
+
+const EPISODES: [(&str, &str); 3] = [
+    ("Hello, crab", "Why we started a podcast about crustaceans, and what to expect."),
+    ("Molting season", "How crabs grow out of their shells, and how long it takes."),
+    ("Sideways", "The anatomy behind the famous walk."),
+];
+
+/// Fills an empty database with something to click through: a super admin without a
+/// password, a show with episodes and chapters, and a few posts.
+pub async fn run(email: &str, profile: Option<Profile>) -> CommandResult {
+    let config = load_config(profile)?;
+    if config.profile == Profile::Prod {
+        return Err("Refusing to seed demo data in the prod profile".into());
+    }
+    let email = normalize_email(email);
+    if !looks_like_email(&email) {
+        return Err(format!("{email} does not look like an email address").into());
+    }
+
+    let conn = connect(&config).await?;
+    if Query::find_user_by_email(&conn, &email).await?.is_some() {
+        println!("{email} already has an account, so the database was seeded before");
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let user = Mutation::create_account(&conn, &email, "Demo Admin", SUPER_ADMIN_ROLE, None, now).await?;
+    let show = Mutation::create_show(
+        &conn,
+        show::Model {
+            id: Uuid::nil(),
+            title: "Crab Talk".to_owned(),
+            description: "A weekly show about crabs, made with pod-crab.".to_owned(),
+            artwork_url: None,
+            language: "en".to_owned(),
+            category: "Science".to_owned(),
+            author: user.name.clone(),
+            owner_name: user.name.clone(),
+            owner_email: user.email.clone(),
+            explicit: false,
+        },
+    )
+    .await?;
+    let show_id = show.id.unwrap();
+
+    for (number, (title, summary)) in EPISODES.into_iter().enumerate() {
+        let published_at = now - Duration::weeks((EPISODES.len() - number) as i64);
+        let episode = ExportedEpisode {
+            title: title.to_owned(),
+            guid: None,
+            summary: summary.to_owned(),
+            tags: "crabs, demo".to_owned(),
+            url: None,
+            status: EpisodeStatus::Published,
+            published_at: Some(published_at),
+            scheduled_for: None,
+            media_size: None,
+            media_type: None,
+            duration_ms: Some(20 * 60 * 1000),
+            bitrate: None,
+            sample_rate: None,
+            channels: None,
+            codec: None,
+            artwork_url: None,
+            chapters: vec![
+                ImportedChapter {
+                    start_ms: 0,
+                    title: "Introduction".to_owned(),
+                    url: None,
+                    image_url: None,
+                },
+                ImportedChapter {
+                    start_ms: 5 * 60 * 1000,
+                    title: title.to_owned(),
+                    url: None,
+                    image_url: None,
+                },
+            ],
+        };
+        Mutation::import_episode(&conn, &episode, user.id, show_id).await?;
+    }
+
+    for (title, text) in [
+        ("Welcome", "This site was filled with demo data by pod-crab seed."),
+        ("Recording setup", "A microphone, a quiet room and a lot of patience."),
+    ] {
+        Mutation::create_post(
+            &conn,
+            post::Model {
+                id: Uuid::nil(),
+                title: title.to_owned(),
+                text: text.to_owned(),
+            },
+        )
+        .await?;
+    }
+
+    println!(
+        "Seeded a show with {} episodes and 2 posts. Log in as {email} through a provider, or with a magic link when local accounts are on.",
+        EPISODES.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use super::{connect, load_config, CommandResult};
+use api::config::Profile;
+use clap::Subcommand;
+use service::backups::{ArchiveReader, ArchiveWriter};
+use service::feed::mime_type_for;
+use service::{Mutation, Query};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use sea_orm::prelude::Uuid;
+
+#[derive(Subcommand)]
+pub enum ShowCommand {
+    /// Write a show with its episodes, chapters, transcripts, members and media files
+    /// into a .tar.gz archive.
+    Export {
+        show: Uuid,
+        /// The archive to write, or - for standard output.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Restore a show from an archive written by `show export`. The show must not exist
+    /// yet.
+    Import {
+        /// The archive to read, or - for standard input.
+        file: PathBuf,
+    },
+}
+
+pub async fn run(command: ShowCommand, profile: Option<Profile>) -> CommandResult {
+    let config = load_config(profile)?;
+    let conn = connect(&config).await?;
+    let media = media::from_config(&config.media)?;
+    let media_url = media.public_url("");
+
+    match command {
+        ShowCommand::Export { show, output } => {
+            let backup = Query::find_show_backup(&conn, show, &media_url, chrono::Utc::now())
+                .await?
+                .ok_or_else(|| format!("There is no show {show}"))?;
+            let writer: Box<dyn Write> = if output.as_os_str() == "-" {
+                Box::new(io::stdout().lock())
+            } else {
+                Box::new(File::create(&output)?)
+            };
+            let mut archive = ArchiveWriter::new(BufWriter::new(writer), &backup)?;
+            let mut files = 0;
+            for key in backup.media_keys() {
+                // One file gone missing should not make the rest of the backup impossible.
+                match media.get(key).await {
+                    Ok(bytes) => {
+                        archive.add_media(key, &bytes)?;
+                        files += 1;
+                    }
+                    Err(err) => eprintln!("Skipped media file {key}: {err}"),
+                }
+            }
+            archive.finish()?.flush()?;
+            eprintln!(
+                "Exported {} with {} episode(s) and {files} media file(s)",
+                backup.show.title,
+                backup.episodes.len()
+            );
+        }
+        ShowCommand::Import { file } => {
+            let reader: Box<dyn Read> = if file.as_os_str() == "-" {
+                Box::new(io::stdin().lock())
+            } else {
+                Box::new(File::open(&file)?)
+            };
+            let mut archive = ArchiveReader::new(BufReader::new(reader));
+            let (backup, files) = archive.read()?;
+            if Query::find_show_by_id(&conn, backup.show.id).await?.is_some() {
+                return Err(format!("Show {} already exists", backup.show.id).into());
+            }
+            // Media goes first: should the restore fail halfway, running it again simply
+            // writes the same files once more, while the show is only recorded at the end.
+            let mut missing: HashSet<&str> = backup.media_keys().collect();
+            for file in files {
+                let (key, bytes) = file?;
+                if !backup.lists_media(&key) {
+                    eprintln!("Skipped {key}, which the manifest does not list");
+                    continue;
+                }
+                media.put(&key, &bytes, mime_type_for(&key)).await?;
+                missing.remove(key.as_str());
+            }
+            for key in &missing {
+                eprintln!("The archive has no media file {key}");
+            }
+
+            let restored = Mutation::restore_show(&conn, &backup, &media_url, chrono::Utc::now()).await?;
+            println!(
+                "Restored {} with {} episode(s) and {} media file(s)",
+                backup.show.title,
+                restored.episodes,
+                backup.media_keys().count() - missing.len()
+            );
+            for email in &restored.created_users {
+                println!("Created an account for {email}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use super::{connect, load_config, CommandResult};
+use api::config::Profile;
+use chrono::Utc;
+use clap::Subcommand;
+use sea_orm::DatabaseConnection;
+use service::accounts::{hash_password, looks_like_email, name_from_email, normalize_email, validate_password};
+use service::permissions::{DEFAULT_ROLE, SUPER_ADMIN_ROLE};
+use service::{Mutation, Query};
+use std::error::Error;
+use std::io;
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Create an account with a verified address.
+    Create {
+        email: String,
+        /// Defaults to the part of the address before the @.
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, default_value = DEFAULT_ROLE)]
+        role: String,
+        /// Read a password for logging in locally from the first line of standard input.
+        /// Without one the user logs in through a provider or with a magic link.
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Give an existing user another role.
+    Promote {
+        email: String,
+        #[arg(long, default_value = SUPER_ADMIN_ROLE)]
+        role: String,
+    },
+    /// List users, optionally only those with a role or matching a search.
+    List {
+        #[arg(long)]
+        role: Option<String>,
+        /// Part of a name or address.
+        #[arg(long)]
+        search: Option<String>,
+    },
+}
+
+pub async fn run(command: UserCommand, profile: Option<Profile>) -> CommandResult {
+    let conn = connect(&load_config(profile)?).await?;
+    match command {
+        UserCommand::Create {
+            email,
+            name,
+            role,
+            password_stdin,
+        } => {
+            let email = checked_email(&email)?;
+            check_role(&conn, &role).await?;
+            if Query::find_user_by_email(&conn, &email).await?.is_some() {
+                return Err(format!("{email} already has an account").into());
+            }
+            let password_hash = if password_stdin {
+                let mut password = String::new();
+                io::stdin().read_line(&mut password)?;
+                let password = password.trim_end_matches(['\r', '\n']);
+                validate_password(password).map_err(|err| format!("Password rejected: {err}"))?;
+                Some(hash_password(password).map_err(|err| err.to_string())?)
+            } else {
+                None
+            };
+
+            let name = name.as_deref().unwrap_or_else(|| name_from_email(&email));
+            let user = Mutation::create_account(&conn, &email, name, &role, password_hash, Utc::now()).await?;
+            println!("Created {} <{}> with the role {}", user.name, user.email, user.role);
+        }
+        UserCommand::Promote { email, role } => {
+            let email = checked_email(&email)?;
+            check_role(&conn, &role).await?;
+            let user = Query::find_user_by_email(&conn, &email)
+                .await?
+                .ok_or_else(|| format!("{email} has no account"))?;
+            let user = Mutation::change_user_role(&conn, user, &role).await?;
+            println!("{} <{}> now has the role {}", user.name, user.email, user.role);
+        }
+        UserCommand::List { role, search } => {
+            let mut page = 1;
+            loop {
+                let users = Query::find_users_page(&conn, role.as_deref(), search.as_deref(), page, 100).await?;
+                for user in &users.items {
+                    let state = if user.deactivated_at.is_some() {
+                        "deactivated"
+                    } else if user.email_verified_at.is_none() {
+                        "unverified"
+                    } else {
+                        "active"
+                    };
+                    println!("{:<40} {:<16} {:<12} {}", user.email, user.role, state, user.name);
+                }
+                if page >= users.total_pages {
+                    break;
+                }
+                page += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn checked_email(email: &str) -> Result<String, Box<dyn Error>> {
+    let email = normalize_email(email);
+    if !looks_like_email(&email) {
+        return Err(format!("{email} does not look like an email address").into());
+    }
+    Ok(email)
+}
+
+async fn check_role(conn: &DatabaseConnection, role: &str) -> CommandResult {
+    if !Query::find_roles(conn).await?.contains(role) {
+        return Err(format!("There is no role named {role}").into());
+    }
+    Ok(())
+}
@@ -1,4 +1,86 @@
+//! The `pod-crab` command: runs the server and everything operators script against it.
 
-fn main() {
-    api::main(None);
+mod commands;
+
+use api::config::Profile;
+use clap::{Parser, Subcommand};
+use commands::admin::AdminCommand;
+use commands::episode::EpisodeCommand;
+use commands::feed::FeedCommand;
+use commands::migrate::MigrateCommand;
+use commands::show::ShowCommand;
+use commands::user::UserCommand;
+use commands::CommandResult;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "pod-crab", about = "Podcast hosting", version)]
+struct Cli {
+    /// Configuration profile: dev, test or prod. Defaults to POD_CRAB_PROFILE, then dev.
+    #[arg(long, global = true, value_parser = parse_profile)]
+    profile: Option<Profile>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the web server. This is what happens without a command, too.
+    Serve,
+    /// Apply, roll back or inspect database migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Manage user accounts.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Manage super admins.
+    #[command(subcommand)]
+    Admin(AdminCommand),
+    /// Move episodes in and out as JSON.
+    #[command(subcommand)]
+    Episode(EpisodeCommand),
+    /// Render RSS feeds without running the server.
+    #[command(subcommand)]
+    Feed(FeedCommand),
+    /// Back up shows into archives and restore them.
+    #[command(subcommand)]
+    Show(ShowCommand),
+    /// Load the configuration and report every problem with it.
+    CheckConfig,
+    /// Fill the database with a demo show, episodes and posts. Not in the prod profile.
+    Seed {
+        /// Address of the demo super admin. Seeding is skipped if it has an account.
+        #[arg(long, default_value = "demo@example.com")]
+        email: String,
+    },
+}
+
+fn parse_profile(profile: &str) -> Result<Profile, String> {
+    Profile::parse(profile).ok_or_else(|| "expected dev, test or prod".to_string())
+}
+
+async fn run(cli: Cli) -> CommandResult {
+    let profile = cli.profile;
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => commands::serve(profile).await,
+        Command::Migrate(command) => commands::migrate::run(command, profile).await,
+        Command::User(command) => commands::user::run(command, profile).await,
+        Command::Admin(command) => commands::admin::run(command, profile).await,
+        Command::Episode(command) => commands::episode::run(command, profile).await,
+        Command::Feed(command) => commands::feed::run(command, profile).await,
+        Command::Show(command) => commands::show::run(command, profile).await,
+        Command::CheckConfig => commands::check_config(profile),
+        Command::Seed { email } => commands::seed::run(&email, profile).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }